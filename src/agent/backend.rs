@@ -0,0 +1,153 @@
+//! Bootstrapping a session with the backend of the application.
+
+use super::{AgentCallback, OAuth2Error};
+use reqwest::{StatusCode, Url};
+use std::time::Duration;
+
+/// Establishing a session with the backend of the application, after the login.
+///
+/// Once a login completes, the agent will `POST` the ID token (or the access token, if there is
+/// no ID token) to the endpoint, as form parameter `id_token` (or `access_token`). The request
+/// includes credentials, so that the backend can answer with a session cookie, allowing the
+/// application to use cookie based authentication for its API, while still using this crate for
+/// the interaction with the issuer.
+///
+/// To protect the endpoint against CSRF, the request carries a custom header, which can't be
+/// sent by a cross-site form and requires a CORS preflight. The backend must reject requests
+/// missing it.
+///
+/// The outcome is reported to the callback, and doesn't affect the session with the issuer.
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct BackendSession {
+    /// The endpoint creating the backend session.
+    pub endpoint: Url,
+
+    /// The name of the header protecting against CSRF.
+    ///
+    /// Defaults to `X-Requested-With`.
+    pub csrf_header: String,
+
+    /// The value of the header protecting against CSRF.
+    ///
+    /// Defaults to `yew-oauth2`.
+    pub csrf_value: String,
+
+    /// Called with the outcome of establishing the backend session.
+    pub callback: Option<AgentCallback<Result<(), OAuth2Error>>>,
+}
+
+impl BackendSession {
+    pub fn new(endpoint: Url) -> Self {
+        Self {
+            endpoint,
+            csrf_header: "X-Requested-With".into(),
+            csrf_value: "yew-oauth2".into(),
+            callback: None,
+        }
+    }
+
+    /// Set the header protecting against CSRF
+    pub fn with_csrf_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.csrf_header = name.into();
+        self.csrf_value = value.into();
+        self
+    }
+
+    /// Set the callback, receiving the outcome
+    pub fn with_callback(
+        mut self,
+        callback: impl Into<AgentCallback<Result<(), OAuth2Error>>>,
+    ) -> Self {
+        self.callback = Some(callback.into());
+        self
+    }
+
+    /// Establish the backend session, reporting the outcome.
+    pub(crate) async fn establish(&self, id_token: Option<String>, access_token: String) {
+        let result = self.request(id_token, access_token).await;
+
+        match &result {
+            Ok(()) => log::debug!("Established backend session"),
+            Err(err) => log::warn!("Failed to establish backend session: {err}"),
+        }
+
+        if let Some(callback) = &self.callback {
+            callback.emit(result);
+        }
+    }
+
+    async fn request(
+        &self,
+        id_token: Option<String>,
+        access_token: String,
+    ) -> Result<(), OAuth2Error> {
+        let param = match id_token {
+            Some(id_token) => ("id_token", id_token),
+            None => ("access_token", access_token),
+        };
+
+        let request = reqwest::Client::new()
+            .post(self.endpoint.clone())
+            .header(&self.csrf_header, &self.csrf_value)
+            .form(&[param]);
+
+        // the backend should set a session cookie
+        #[cfg(target_arch = "wasm32")]
+        let request = request.fetch_credentials_include();
+
+        request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(OAuth2Error::network)?;
+
+        Ok(())
+    }
+}
+
+/// Pinging the backend of the application, while the session is authenticated.
+///
+/// The agent will periodically `GET` the endpoint, using the access token of the session as
+/// bearer token. This keeps server-side session stores warm. While the page is hidden, no pings
+/// are sent.
+///
+/// A response of `401 Unauthorized` is handled like a report using
+/// [`super::OAuth2Operations::report_unauthorized`], applying the configured
+/// [`super::UnauthorizedPolicy`].
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeepAlive {
+    /// The endpoint to ping.
+    pub endpoint: Url,
+    /// The interval between two pings.
+    pub interval: Duration,
+}
+
+impl KeepAlive {
+    pub fn new(endpoint: Url, interval: Duration) -> Self {
+        Self { endpoint, interval }
+    }
+
+    /// Ping the backend, returning `true` if the session was rejected.
+    pub(crate) async fn ping(&self, access_token: &str) -> bool {
+        match reqwest::Client::new()
+            .get(self.endpoint.clone())
+            .bearer_auth(access_token)
+            .send()
+            .await
+        {
+            Ok(response) => response.status() == StatusCode::UNAUTHORIZED,
+            Err(err) => {
+                log::info!("Backend keep-alive ping failed: {err}");
+                false
+            }
+        }
+    }
+}
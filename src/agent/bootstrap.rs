@@ -0,0 +1,99 @@
+//! Initializing the session from a bootstrap, handed over by the server.
+
+use crate::context::Authentication;
+use gloo_utils::document;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+
+/// A session handed over by the server, skipping the interactive login.
+///
+/// When the server already authenticated the user, e.g. while rendering the page or during the
+/// handshake of a backend for frontend, it can embed the session into the page. The agent then
+/// starts with that session, instead of redirecting to the issuer.
+///
+/// The bootstrap uses the same format as a persisted session (see
+/// [`super::SessionPersistence`]):
+///
+/// ```json
+/// {
+///   "authentication": {
+///     "access_token": "…",
+///     "refresh_token": "…",
+///     "expires": 1700000000
+///   },
+///   "session_state": null
+/// }
+/// ```
+///
+/// The session state is specific to the client. For OpenID Connect, it holds the ID token and
+/// its claims. Without it, the session can't be refreshed, and ends when it expires.
+///
+/// A bootstrap which is missing, invalid, or expired is ignored, and the agent continues as
+/// usual. An element, or meta tag, holding the bootstrap is removed from the page once it is
+/// read, so that its tokens don't linger in the DOM.
+///
+/// **NOTE**: The agent trusts the bootstrap, the claims it contains are not verified again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bootstrap {
+    /// The text of an element, by its ID, e.g. `<script type="application/json" id="…">`
+    Element(String),
+    /// The content of a `<meta>` tag, by its name
+    Meta(String),
+    /// The JSON value itself
+    Json(String),
+}
+
+impl Bootstrap {
+    /// Read the bootstrap from the text of an element, by its ID.
+    pub fn element(id: impl Into<String>) -> Self {
+        Self::Element(id.into())
+    }
+
+    /// Read the bootstrap from the content of a `<meta>` tag, by its name.
+    pub fn meta(name: impl Into<String>) -> Self {
+        Self::Meta(name.into())
+    }
+
+    /// Take the session from the page, removing it.
+    pub(crate) fn take<S: DeserializeOwned>(&self) -> Option<(Authentication, Option<S>)> {
+        let json = match self {
+            Self::Element(id) => {
+                let element = document().get_element_by_id(id)?;
+                element.remove();
+                element.text_content()?
+            }
+            Self::Meta(name) => {
+                let element = document()
+                    .query_selector(&format!(r#"meta[name="{name}"]"#))
+                    .ok()??;
+                element.remove();
+                element.get_attribute("content")?
+            }
+            Self::Json(json) => json.clone(),
+        };
+
+        let session = match serde_json::from_str::<BootstrapSession<S>>(&json) {
+            Ok(session) => session,
+            Err(err) => {
+                log::warn!("Failed to read bootstrap session: {err}");
+                return None;
+            }
+        };
+
+        // a client without a session state, can do with none
+        let session_state = session
+            .session_state
+            .or_else(|| serde_json::from_value(Value::Null).ok());
+
+        Some((session.authentication, session_state))
+    }
+}
+
+/// A session, in the format of a persisted session, with an optional session state.
+#[derive(Deserialize)]
+#[serde(bound = "S: DeserializeOwned")]
+struct BootstrapSession<S> {
+    authentication: Authentication,
+    #[serde(default)]
+    session_state: Option<S>,
+}
@@ -0,0 +1,147 @@
+//! Cache for access tokens of additional scopes and audiences.
+
+use super::{deadline::Deadline, ExpiryPolicy};
+use std::{collections::HashMap, time::Duration};
+
+/// A request for an access token, valid for a set of scopes and an audience.
+///
+/// In addition to the access token of the session, the agent can acquire access tokens for
+/// different scopes and audiences (resources), using the refresh token of the session.
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TokenRequest {
+    /// The scopes the token must be valid for.
+    ///
+    /// If empty, the scopes of the session are used.
+    pub scopes: Vec<String>,
+
+    /// The audience the token must be valid for.
+    ///
+    /// If `None`, the audience of the session is used. Otherwise, the `aud` claim of the token is
+    /// verified to contain the audience.
+    pub audience: Option<String>,
+
+    /// Start an interactive login if the token cannot be acquired silently.
+    pub interactive: bool,
+}
+
+impl TokenRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the scopes of the request
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scopes = scopes.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Add a scope to the request
+    pub fn add_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Set the audience of the request
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Set if an interactive login should be started, if required
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+}
+
+/// An access token, acquired by the agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccessToken {
+    /// The access token
+    pub access_token: String,
+    /// Expiration timestamp in seconds
+    pub expires: Option<u64>,
+}
+
+impl AccessToken {
+    /// Check if the token may be used for starting a new request.
+    pub fn is_usable(&self, policy: &ExpiryPolicy) -> bool {
+        !policy.is_soft_expired(self.expires)
+    }
+
+    /// Check if the token may still be used for a request which is in flight, e.g. for retrying it.
+    pub fn is_usable_in_flight(&self, policy: &ExpiryPolicy) -> bool {
+        !policy.is_hard_expired(self.expires)
+    }
+}
+
+/// The key of a token in the cache.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct TokenKey {
+    pub scopes: Vec<String>,
+    pub audience: Option<String>,
+}
+
+impl TokenKey {
+    pub fn new(scopes: impl IntoIterator<Item = String>, audience: Option<String>) -> Self {
+        let mut scopes = scopes.into_iter().collect::<Vec<_>>();
+        // the order of scopes doesn't matter
+        scopes.sort_unstable();
+        scopes.dedup();
+        Self { scopes, audience }
+    }
+}
+
+/// Tokens acquired in addition to the one of the session.
+#[derive(Debug, Default)]
+pub(crate) struct TokenCache {
+    tokens: HashMap<TokenKey, (AccessToken, Option<Deadline>)>,
+    /// Refresh tokens bound to a key, see [`super::RefreshTokenPolicy::PerAudience`]
+    refresh_tokens: HashMap<TokenKey, String>,
+}
+
+impl TokenCache {
+    /// Get a token which is still valid for at least the grace period, and usable for new
+    /// requests.
+    pub fn get(
+        &self,
+        key: &TokenKey,
+        grace_period: Duration,
+        policy: &ExpiryPolicy,
+    ) -> Option<&AccessToken> {
+        self.tokens
+            .get(key)
+            .filter(|(_, deadline)| {
+                let remaining = deadline.map(|deadline| deadline.remaining());
+                remaining.map_or(true, |remaining| remaining > grace_period)
+                    && !policy.is_soft_expired_in(remaining)
+            })
+            .map(|(token, _)| token)
+    }
+
+    pub fn insert(&mut self, key: TokenKey, token: AccessToken) {
+        let deadline = token.expires.map(Deadline::from_expires);
+        self.tokens.insert(key, (token, deadline));
+    }
+
+    pub fn refresh_token(&self, key: &TokenKey) -> Option<&String> {
+        self.refresh_tokens.get(key)
+    }
+
+    pub fn insert_refresh_token(&mut self, key: TokenKey, refresh_token: String) {
+        self.refresh_tokens.insert(key, refresh_token);
+    }
+
+    pub fn remove_refresh_token(&mut self, key: &TokenKey) {
+        self.refresh_tokens.remove(key);
+    }
+
+    pub fn clear(&mut self) {
+        self.tokens.clear();
+        self.refresh_tokens.clear();
+    }
+}
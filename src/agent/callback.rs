@@ -0,0 +1,57 @@
+//! Callbacks of the agent.
+
+use std::{fmt::Formatter, rc::Rc};
+
+/// A callback, invoked by the agent.
+///
+/// This is independent of Yew, so that the agent can be used with other bindings. With the `yew`
+/// feature, it can be created from a [`yew::Callback`].
+pub struct AgentCallback<T>(Rc<dyn Fn(T)>);
+
+impl<T> AgentCallback<T> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(T) + 'static,
+    {
+        Self(Rc::new(f))
+    }
+
+    /// Invoke the callback.
+    pub fn emit(&self, value: T) {
+        (self.0)(value)
+    }
+}
+
+impl<T, F> From<F> for AgentCallback<T>
+where
+    F: Fn(T) + 'static,
+{
+    fn from(f: F) -> Self {
+        Self::new(f)
+    }
+}
+
+#[cfg(feature = "yew")]
+impl<T: 'static> From<yew::Callback<T>> for AgentCallback<T> {
+    fn from(callback: yew::Callback<T>) -> Self {
+        Self::new(move |value| callback.emit(value))
+    }
+}
+
+impl<T> Clone for AgentCallback<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> PartialEq for AgentCallback<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> std::fmt::Debug for AgentCallback<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AgentCallback")
+    }
+}
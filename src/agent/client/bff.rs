@@ -0,0 +1,399 @@
+use crate::{
+    agent::{
+        client::{expires, yield_now, Client, LoginContext},
+        InnerConfig, LoginOptions, LogoutOptions, OAuth2Error,
+    },
+    config::{bff, ConfigError},
+    context::{Authentication, OAuth2Context, Reason},
+};
+use ::oauth2::{
+    basic::{BasicClient, BasicTokenResponse},
+    AuthUrl, ClientId, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+};
+use async_trait::async_trait;
+use gloo_utils::window;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+#[cfg(feature = "openid")]
+use {
+    crate::context::{Claims, UserInfo},
+    std::rc::Rc,
+};
+
+/// Stands in for the refresh token, letting the agent ask the backend to refresh the session.
+const BACKEND_SESSION: &str = "backend-session";
+
+const POST_LOGOUT_REDIRECT_NAME: &str = "post_logout_redirect_uri";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BffLoginState {
+    pub pkce_verifier: String,
+    pub redirect_url: Url,
+}
+
+/// The session, as reported by the backend.
+#[derive(Debug, Deserialize)]
+struct BackendSession {
+    /// Seconds until the agent should refresh the session
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[cfg(feature = "openid")]
+    #[serde(default)]
+    claims: Option<Claims>,
+    #[cfg(feature = "openid")]
+    #[serde(default)]
+    user_info: Option<UserInfo>,
+}
+
+/// An error, reported by the backend.
+#[derive(Debug, Deserialize)]
+struct BackendError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+    #[serde(default)]
+    error_uri: Option<String>,
+}
+
+/// A client for a backend for frontend (BFF), which holds the tokens of the session.
+///
+/// The client only builds the authorization URL. The authorization code is handed to the
+/// backend, which exchanges it, keeps the tokens, and answers with a session cookie. The
+/// application then uses cookie based authentication for its API, and the agent never sees a
+/// token. The access token of an authenticated context is empty.
+///
+/// The backend must provide a session endpoint (`session_url`), accepting requests with
+/// credentials, and the CSRF header of the configuration:
+///
+/// * `POST`, with the form parameters `code`, `code_verifier`, and `redirect_uri`: exchange the
+///   code, and establish the session.
+/// * `GET`: report the current session, refreshing its tokens if required. Or `401`, if there is
+///   none.
+/// * `DELETE`: end the session.
+///
+/// The session is reported as JSON object, with the optional fields `expires_in` (seconds until
+/// the agent should check the session again), and with the feature `openid`, `claims` (the
+/// claims of the ID token) and `user_info`. An error of the code exchange may be reported like
+/// an error of a token endpoint.
+///
+/// On startup, the agent resumes a session the backend has. A logout navigates to the logout
+/// endpoint (`logout_url`), if there is one. So that the backend can end the session of the
+/// issuer too. Otherwise, only the session of the backend is ended.
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::{agent::client::BffClient, components::context::OAuth2, config::bff::Config};
+///
+/// #[function_component(App)]
+/// fn app() -> Html {
+///     let config = Config::new(
+///         "example",
+///         "https://issuer/auth",
+///         "https://app/api/session",
+///     );
+///     html!(
+///         <OAuth2<BffClient> {config}>
+///             // the application
+///         </OAuth2<BffClient>>
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BffClient {
+    client: BasicClient,
+    session_url: Url,
+    logout_url: Option<Url>,
+    additional_auth_params: Vec<(String, String)>,
+    csrf_header: String,
+    csrf_value: String,
+}
+
+impl BffClient {
+    fn request(&self, method: Method) -> RequestBuilder {
+        let request = reqwest::Client::new()
+            .request(method, self.session_url.clone())
+            .header(&self.csrf_header, &self.csrf_value);
+
+        // the backend keeps the session in a cookie
+        #[cfg(target_arch = "wasm32")]
+        let request = request.fetch_credentials_include();
+
+        request
+    }
+
+    /// Read the session from a response of the backend.
+    async fn session(response: Response) -> Result<OAuth2Context, OAuth2Error> {
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.bytes().await.unwrap_or_default();
+            return Err(match serde_json::from_slice::<BackendError>(&body) {
+                Ok(err) => OAuth2Error::Server {
+                    error: err.error,
+                    description: err.error_description,
+                    uri: err.error_uri,
+                },
+                Err(_) => OAuth2Error::Server {
+                    error: status.as_str().to_string(),
+                    description: status.canonical_reason().map(ToString::to_string),
+                    uri: None,
+                },
+            });
+        }
+
+        let body = response.bytes().await.map_err(OAuth2Error::network)?;
+        let session = serde_json::from_slice::<BackendSession>(&body).map_err(|err| {
+            OAuth2Error::Validation {
+                message: "failed to parse session response".to_string(),
+                source: Some(Arc::new(err)),
+            }
+        })?;
+
+        log::debug!("Backend session: {session:?}");
+
+        Ok(OAuth2Context::Authenticated(Authentication {
+            access_token: String::new(),
+            refresh_token: Some(BACKEND_SESSION.to_string()),
+            expires: expires(session.expires_in.map(Duration::from_secs)),
+            #[cfg(feature = "openid")]
+            claims: session.claims.map(Rc::new),
+            #[cfg(feature = "openid")]
+            user_info: session.user_info.map(Rc::new),
+        }))
+    }
+
+    /// Get the current session of the backend, if there is one.
+    async fn current_session(&self) -> Result<OAuth2Context, OAuth2Error> {
+        let response = self
+            .request(Method::GET)
+            .send()
+            .await
+            .map_err(OAuth2Error::network)?;
+
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Ok(OAuth2Context::NotAuthenticated {
+                reason: Reason::Expired,
+            });
+        }
+
+        Self::session(response).await
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for BffClient {
+    type TokenResponse = BasicTokenResponse;
+    type Configuration = bff::Config;
+    type LoginState = BffLoginState;
+    type SessionState = ();
+
+    fn validate_config(config: &Self::Configuration) -> Result<(), Vec<ConfigError>> {
+        config.validate()
+    }
+
+    async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error> {
+        let bff::Config {
+            client_id,
+            auth_url,
+            session_url,
+            logout_url,
+            additional_auth_params,
+            csrf_header,
+            csrf_value,
+        } = config;
+
+        let client = BasicClient::new(
+            ClientId::new(client_id),
+            None,
+            AuthUrl::new(auth_url)
+                .map_err(|err| OAuth2Error::configuration("invalid auth URL", err))?,
+            None,
+        );
+
+        Ok(Self {
+            client,
+            session_url: Url::parse(&session_url)
+                .map_err(|err| OAuth2Error::configuration("invalid session URL", err))?,
+            logout_url: logout_url
+                .map(|url| Url::parse(&url))
+                .transpose()
+                .map_err(|err| OAuth2Error::configuration("invalid logout URL", err))?,
+            additional_auth_params,
+            csrf_header,
+            csrf_value,
+        })
+    }
+
+    fn set_redirect_uri(mut self, url: Url) -> Self {
+        self.client = self.client.set_redirect_uri(RedirectUrl::from_url(url));
+        self
+    }
+
+    fn make_login_context(
+        &self,
+        config: &InnerConfig,
+        redirect_url: Url,
+        options: &LoginOptions,
+    ) -> Result<LoginContext<Self::LoginState>, OAuth2Error> {
+        let client = self
+            .client
+            .clone()
+            .set_redirect_uri(RedirectUrl::from_url(redirect_url.clone()));
+
+        let pkce_verifier = PkceCodeVerifier::new(config.random.token()?);
+        let pkce_challenge = PkceCodeChallenge::from_code_verifier_sha256(&pkce_verifier);
+        let csrf_token = CsrfToken::new(config.random.token()?);
+
+        // the scopes of the configuration already contain the ones of the options
+        let mut req = client
+            .authorize_url(move || csrf_token)
+            .add_scopes(config.scopes.iter().map(|s| Scope::new(s.to_string())))
+            .set_pkce_challenge(pkce_challenge);
+
+        if let Some(audience) = &config.audience {
+            req = req.add_extra_param("audience".to_string(), audience.clone())
+        }
+
+        for (key, value) in &self.additional_auth_params {
+            req = req.add_extra_param(key, value);
+        }
+
+        for (key, value) in options.authorization_params() {
+            req = req.add_extra_param(key, value);
+        }
+
+        let (url, state) = req.url();
+
+        Ok(LoginContext {
+            url,
+            csrf_token: state.secret().clone(),
+            state: BffLoginState {
+                pkce_verifier: pkce_verifier.secret().clone(),
+                redirect_url,
+            },
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        code: String,
+        BffLoginState {
+            pkce_verifier,
+            redirect_url,
+        }: BffLoginState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        let response = self
+            .request(Method::POST)
+            .form(&[
+                ("code", code.as_str()),
+                ("code_verifier", pkce_verifier.as_str()),
+                ("redirect_uri", redirect_url.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(OAuth2Error::network)?;
+
+        let state = Self::session(response).await?;
+
+        // parsing the response may have taken a while, let the browser render
+        yield_now().await;
+
+        Ok((state, ()))
+    }
+
+    async fn exchange_refresh_token(
+        &self,
+        _refresh_token: String,
+        session_state: Self::SessionState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        // the backend refreshes its tokens as required
+        Ok((self.current_session().await?, session_state))
+    }
+
+    async fn exchange_refresh_token_for(
+        &self,
+        _refresh_token: String,
+        _session_state: Self::SessionState,
+        _scopes: &[String],
+        _audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error> {
+        Err(OAuth2Error::Internal(
+            "the tokens of the session are held by the backend".to_string(),
+        ))
+    }
+
+    async fn resume_session(&self) -> Option<(OAuth2Context, Self::SessionState)> {
+        match self.current_session().await {
+            Ok(state @ OAuth2Context::Authenticated(_)) => Some((state, ())),
+            Ok(_) => None,
+            Err(err) => {
+                log::info!("Failed to check the backend session: {err}");
+                None
+            }
+        }
+    }
+
+    fn logout(&self, _session_state: Self::SessionState, options: LogoutOptions) {
+        if let Some(url) = &self.logout_url {
+            let mut url = url.clone();
+            if let Some(target) = &options.target {
+                url.query_pairs_mut()
+                    .append_pair(POST_LOGOUT_REDIRECT_NAME, target.as_str());
+            }
+
+            log::info!("Navigating to: {url}");
+
+            window().location().replace(url.as_str()).ok();
+        } else {
+            let request = self.request(Method::DELETE);
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(err) = request.send().await.and_then(|r| r.error_for_status()) {
+                    log::warn!("Failed to end the backend session: {err}");
+                }
+            });
+        }
+    }
+
+    fn health_check_url(&self) -> Option<Url> {
+        Some(self.session_url.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{agent::Random, test_util::Fixed};
+    use std::collections::HashMap;
+
+    #[test]
+    fn login_options() {
+        let client = futures::executor::block_on(BffClient::from_config(bff::Config::new(
+            "app",
+            "https://issuer/auth",
+            "https://app/api/session",
+        )))
+        .unwrap();
+        let config = InnerConfig {
+            scopes: vec!["openid".into(), "email".into()],
+            random: Random::new(Fixed),
+            ..Default::default()
+        };
+        let options = LoginOptions::default()
+            .with_login_hint("user@example.com")
+            .with_max_age(Duration::from_secs(60));
+
+        let context = client
+            .make_login_context(&config, Url::parse("https://app/").unwrap(), &options)
+            .unwrap();
+
+        let query = context
+            .url
+            .query_pairs()
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(query["client_id"], "app");
+        assert_eq!(query["scope"], "openid email");
+        assert_eq!(query["login_hint"], "user@example.com");
+        assert_eq!(query["max_age"], "60");
+    }
+}
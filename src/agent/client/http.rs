@@ -0,0 +1,91 @@
+//! Sending the HTTP requests of a client.
+
+use async_trait::async_trait;
+use oauth2::{HttpRequest, HttpResponse};
+use std::{fmt::Debug, rc::Rc};
+
+/// An error sending an HTTP request.
+///
+/// Custom clients can report their errors as [`HttpClientError::Other`].
+pub type HttpClientError = oauth2::reqwest::Error<reqwest::Error>;
+
+/// Sending the requests to the issuer, like discovery, keys, and token requests.
+///
+/// By default, requests are sent using `reqwest`. A custom client can add headers an issuer
+/// requires, change the credentials mode, use a different HTTP library, or answer requests
+/// itself, e.g. for testing.
+///
+/// ```rust
+/// use async_trait::async_trait;
+/// use yew_oauth2::agent::client::{HttpClient, HttpClientError, ReqwestHttpClient};
+///
+/// #[derive(Debug)]
+/// struct ApiKey(String);
+///
+/// #[async_trait(?Send)]
+/// impl HttpClient for ApiKey {
+///     async fn request(
+///         &self,
+///         mut request: oauth2::HttpRequest,
+///     ) -> Result<oauth2::HttpResponse, HttpClientError> {
+///         let value = self.0.parse().map_err(|_| HttpClientError::Other("invalid key".into()))?;
+///         request.headers.insert("x-api-key", value);
+///         ReqwestHttpClient.request(request).await
+///     }
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait HttpClient: Debug {
+    /// Send the request, returning the response.
+    ///
+    /// Responses with an error status are a response too, and not an error.
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, HttpClientError>;
+}
+
+/// The default HTTP client, using `reqwest`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReqwestHttpClient;
+
+#[async_trait(?Send)]
+impl HttpClient for ReqwestHttpClient {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, HttpClientError> {
+        oauth2::reqwest::async_http_client(request).await
+    }
+}
+
+/// An [`HttpClient`], shared by the configuration and the clients created from it.
+#[derive(Clone)]
+pub struct SharedHttpClient(Rc<dyn HttpClient>);
+
+impl SharedHttpClient {
+    pub fn new(client: impl HttpClient + 'static) -> Self {
+        Self(Rc::new(client))
+    }
+}
+
+impl Default for SharedHttpClient {
+    fn default() -> Self {
+        Self::new(ReqwestHttpClient)
+    }
+}
+
+impl PartialEq for SharedHttpClient {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for SharedHttpClient {}
+
+impl Debug for SharedHttpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[async_trait(?Send)]
+impl HttpClient for SharedHttpClient {
+    async fn request(&self, request: HttpRequest) -> Result<HttpResponse, HttpClientError> {
+        self.0.request(request).await
+    }
+}
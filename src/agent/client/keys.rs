@@ -0,0 +1,64 @@
+//! The verification keys of an issuer.
+
+use super::{HttpClient, SharedHttpClient};
+use openidconnect::{core::CoreJsonWebKeySet, JsonWebKey, JsonWebKeySet, JsonWebKeySetUrl};
+use std::{cell::RefCell, rc::Rc};
+
+/// The signing keys of the issuer.
+///
+/// The key set is shared between clones of the client, so that reloading it after the issuer
+/// rotated its keys applies to all of them.
+///
+/// **NOTE:** The verifier of `openidconnect` parses the key for each signature it verifies, so
+/// there is nothing to gain from caching anything beyond the key set.
+#[derive(Clone, Debug)]
+pub(crate) struct KeyCache {
+    jwks_uri: JsonWebKeySetUrl,
+    keys: Rc<RefCell<CoreJsonWebKeySet>>,
+    http: SharedHttpClient,
+}
+
+impl KeyCache {
+    pub fn new(
+        jwks_uri: JsonWebKeySetUrl,
+        keys: CoreJsonWebKeySet,
+        http: SharedHttpClient,
+    ) -> Self {
+        Self {
+            jwks_uri,
+            keys: Rc::new(RefCell::new(keys)),
+            http,
+        }
+    }
+
+    /// Check if the key set contains a key with the key ID.
+    pub fn contains(&self, kid: &str) -> bool {
+        self.keys
+            .borrow()
+            .keys()
+            .iter()
+            .any(|key| key.key_id().is_some_and(|id| id.as_str() == kid))
+    }
+
+    /// Get the keys for verifying a token.
+    pub fn get(&self) -> CoreJsonWebKeySet {
+        self.keys.borrow().clone()
+    }
+
+    /// Fetch the key set from the issuer again, replacing the cached keys.
+    ///
+    /// Returns `false` if the key set could not be fetched, keeping the current keys.
+    pub async fn reload(&self) -> bool {
+        match JsonWebKeySet::fetch_async(&self.jwks_uri, |request| self.http.request(request)).await
+        {
+            Ok(keys) => {
+                *self.keys.borrow_mut() = keys;
+                true
+            }
+            Err(err) => {
+                log::warn!("Failed to reload the signing keys: {err}");
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Cache of the discovered metadata of an issuer.
+
+use super::ExtendedProviderMetadata;
+use crate::agent::Clock;
+use gloo_storage::{LocalStorage, Storage};
+use openidconnect::core::CoreJsonWebKeySet;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Discovered metadata, stored in the local storage.
+#[derive(Serialize, Deserialize)]
+struct CachedMetadata {
+    /// Timestamp of storing the metadata, in milliseconds
+    stored: f64,
+    metadata: ExtendedProviderMetadata,
+    /// The key set, which isn't part of the serialized metadata
+    jwks: CoreJsonWebKeySet,
+}
+
+fn key(issuer: &str) -> String {
+    format!("ctron/oauth2/discovery/{issuer}")
+}
+
+/// Load the metadata of the issuer, if it was stored less than the TTL ago.
+pub(crate) fn load(issuer: &str, ttl: Duration) -> Option<ExtendedProviderMetadata> {
+    let cached = LocalStorage::get::<CachedMetadata>(key(issuer)).ok()?;
+    if Clock::current().now() - cached.stored > ttl.as_secs_f64() * 1000f64 {
+        log::debug!("Cached metadata of {issuer} is stale");
+        evict(issuer);
+        return None;
+    }
+
+    Some(cached.metadata.set_jwks(cached.jwks))
+}
+
+/// Store the discovered metadata of the issuer.
+pub(crate) fn store(issuer: &str, metadata: &ExtendedProviderMetadata) {
+    let cached = CachedMetadata {
+        stored: Clock::current().now(),
+        metadata: metadata.clone(),
+        jwks: metadata.jwks().clone(),
+    };
+    if let Err(err) = LocalStorage::set(key(issuer), cached) {
+        log::warn!("Failed to cache metadata: {err}");
+    }
+}
+
+/// Remove the metadata of the issuer, e.g. when it turned out to be outdated.
+pub(crate) fn evict(issuer: &str) {
+    LocalStorage::delete(key(issuer));
+}
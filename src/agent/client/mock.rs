@@ -0,0 +1,344 @@
+use crate::{
+    agent::{
+        client::{expires, Client, LoginContext},
+        InnerConfig, LoginOptions, OAuth2Error,
+    },
+    context::{Authentication, OAuth2Context, Operation},
+};
+use async_trait::async_trait;
+use reqwest::Url;
+use std::{rc::Rc, time::Duration};
+
+/// The configuration of a [`MockClient`].
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`crate::agent::LoginOptions`] for an example
+/// on how to work with this.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MockConfig {
+    /// Start with a session, instead of requiring a login.
+    pub signed_in: bool,
+    /// The access token of the session.
+    pub access_token: String,
+    /// Issue a refresh token, so that the session gets refreshed.
+    pub refresh_token: bool,
+    /// The lifetime of the access token, without an expiration if it is `None`.
+    pub expires_in: Option<Duration>,
+    /// The claims of the ID token, in their JSON form.
+    #[cfg(feature = "openid")]
+    pub claims: Option<serde_json::Value>,
+    /// The operations which fail, with their error.
+    pub failures: Vec<(Operation, OAuth2Error)>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            signed_in: false,
+            access_token: "mock-access-token".into(),
+            refresh_token: true,
+            expires_in: Some(Duration::from_secs(300)),
+            #[cfg(feature = "openid")]
+            claims: None,
+            failures: vec![],
+        }
+    }
+}
+
+impl MockConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set if the application starts with a session
+    pub fn with_signed_in(mut self, signed_in: bool) -> Self {
+        self.signed_in = signed_in;
+        self
+    }
+
+    /// Set the access token
+    pub fn with_access_token(mut self, access_token: impl Into<String>) -> Self {
+        self.access_token = access_token.into();
+        self
+    }
+
+    /// Set if a refresh token is issued
+    pub fn with_refresh_token(mut self, refresh_token: bool) -> Self {
+        self.refresh_token = refresh_token;
+        self
+    }
+
+    /// Set the lifetime of the access token
+    pub fn with_expires_in(mut self, expires_in: impl Into<Option<Duration>>) -> Self {
+        self.expires_in = expires_in.into();
+        self
+    }
+
+    /// Set the claims of the ID token, like `{ "sub": "…", "name": "…" }`
+    ///
+    /// The required claims `iss`, `aud`, `exp`, and `iat` are added, if missing.
+    #[cfg(feature = "openid")]
+    pub fn with_claims(mut self, claims: serde_json::Value) -> Self {
+        self.claims = Some(claims);
+        self
+    }
+
+    /// Let all attempts of the operation fail, with the error
+    pub fn with_failure(mut self, operation: Operation, error: OAuth2Error) -> Self {
+        self.failures.push((operation, error));
+        self
+    }
+}
+
+/// A client, which doesn't contact an issuer.
+///
+/// Each operation succeeds, unless configured to fail. Starting a login navigates directly back
+/// to the application. This allows testing components, and running demos, without an issuer.
+/// It requires the feature `testing`, which must not be enabled in production.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use yew::prelude::*;
+/// use yew_oauth2::agent::client::MockConfig;
+/// use yew_oauth2::components::context::MockOAuth2;
+///
+/// #[function_component(Demo)]
+/// fn demo() -> Html {
+///     let config = MockConfig::new()
+///         .with_signed_in(true)
+///         .with_expires_in(Duration::from_secs(60));
+///     html!(
+///         <MockOAuth2 {config}>
+///             // the application
+///         </MockOAuth2>
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MockClient {
+    config: Rc<MockConfig>,
+}
+
+impl MockClient {
+    /// Fail the operation, if configured to.
+    fn outcome(&self, operation: Operation) -> Result<(), OAuth2Error> {
+        match self.config.failures.iter().find(|(op, _)| *op == operation) {
+            Some((_, err)) => Err(err.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn authentication(&self) -> Result<Authentication, OAuth2Error> {
+        Ok(Authentication {
+            access_token: self.config.access_token.clone(),
+            refresh_token: self
+                .config
+                .refresh_token
+                .then(|| "mock-refresh-token".into()),
+            expires: expires(self.config.expires_in),
+            #[cfg(feature = "openid")]
+            claims: self.claims()?.map(Rc::new),
+            #[cfg(feature = "openid")]
+            user_info: None,
+        })
+    }
+
+    #[cfg(feature = "openid")]
+    fn claims(&self) -> Result<Option<crate::context::Claims>, OAuth2Error> {
+        let Some(serde_json::Value::Object(claims)) = &self.config.claims else {
+            return Ok(None);
+        };
+
+        let now = crate::agent::Clock::current().now_secs() as u64;
+        let exp = now + self.config.expires_in.unwrap_or_default().as_secs();
+        let mut claims = claims.clone();
+        for (name, value) in [
+            ("iss", "https://mock".into()),
+            ("aud", "mock".into()),
+            ("sub", "mock".into()),
+            ("exp", exp.into()),
+            ("iat", now.into()),
+        ] {
+            claims.entry(name).or_insert(value);
+        }
+
+        serde_json::from_value(claims.into())
+            .map(Some)
+            .map_err(|err| OAuth2Error::configuration("invalid mock claims", err))
+    }
+
+    fn authenticated(&self) -> Result<OAuth2Context, OAuth2Error> {
+        Ok(OAuth2Context::Authenticated(self.authentication()?))
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for MockClient {
+    type TokenResponse = ();
+    type Configuration = MockConfig;
+    type LoginState = ();
+    type SessionState = ();
+
+    async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error> {
+        let client = Self {
+            config: Rc::new(config),
+        };
+        client.outcome(Operation::Discovery)?;
+        Ok(client)
+    }
+
+    fn set_redirect_uri(self, _url: Url) -> Self {
+        self
+    }
+
+    fn make_login_context(
+        &self,
+        config: &InnerConfig,
+        mut redirect_url: Url,
+        _options: &LoginOptions,
+    ) -> Result<LoginContext<Self::LoginState>, OAuth2Error> {
+        self.outcome(Operation::Login)?;
+
+        let csrf_token = config.random.token()?;
+        redirect_url
+            .query_pairs_mut()
+            .append_pair("code", "mock-code")
+            .append_pair("state", &csrf_token);
+
+        Ok(LoginContext {
+            url: redirect_url,
+            csrf_token,
+            state: (),
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        _code: String,
+        _login_state: Self::LoginState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        self.outcome(Operation::CodeExchange)?;
+        Ok((self.authenticated()?, ()))
+    }
+
+    async fn exchange_refresh_token(
+        &self,
+        _refresh_token: String,
+        _session_state: Self::SessionState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        self.outcome(Operation::Refresh)?;
+        Ok((self.authenticated()?, ()))
+    }
+
+    async fn exchange_refresh_token_for(
+        &self,
+        _refresh_token: String,
+        _session_state: Self::SessionState,
+        scopes: &[String],
+        audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error> {
+        self.outcome(Operation::Refresh)?;
+        let mut authentication = self.authentication()?;
+        // allow telling the tokens apart
+        authentication.access_token = format!(
+            "{}:{}:{}",
+            authentication.access_token,
+            scopes.join(" "),
+            audience.unwrap_or_default()
+        );
+        Ok(authentication)
+    }
+
+    async fn resume_session(&self) -> Option<(OAuth2Context, Self::SessionState)> {
+        if !self.config.signed_in {
+            return None;
+        }
+        self.authenticated().ok().map(|state| (state, ()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        agent::{Clock, Random, VirtualTime},
+        test_util::{url, Fixed},
+    };
+    use futures::executor::block_on;
+
+    fn create(config: MockConfig) -> Result<MockClient, OAuth2Error> {
+        Clock::new(VirtualTime::starting_at(1_000_000f64)).install();
+        block_on(MockClient::from_config(config))
+    }
+
+    #[test]
+    fn login() {
+        let client = create(MockConfig::new()).unwrap();
+        let config = InnerConfig {
+            random: Random::new(Fixed),
+            ..Default::default()
+        };
+
+        // navigating back to the application right away
+        let context = client
+            .make_login_context(&config, url("https://app/"), &LoginOptions::new())
+            .unwrap();
+        let query = context.url.query_pairs().into_owned().collect::<Vec<_>>();
+        assert_eq!(context.url.path(), "/");
+        assert_eq!(
+            query,
+            vec![
+                ("code".to_string(), "mock-code".to_string()),
+                ("state".to_string(), context.csrf_token.clone()),
+            ]
+        );
+
+        let (context, _) = block_on(client.exchange_code("mock-code".into(), ())).unwrap();
+        let auth = context.authentication().unwrap();
+        assert_eq!(auth.access_token, "mock-access-token");
+        assert_eq!(auth.refresh_token.as_deref(), Some("mock-refresh-token"));
+        assert_eq!(auth.expires, Some(1_300));
+    }
+
+    #[test]
+    fn resume_session() {
+        let client = create(MockConfig::new()).unwrap();
+        assert!(block_on(client.resume_session()).is_none());
+
+        let client = create(
+            MockConfig::new()
+                .with_signed_in(true)
+                .with_access_token("token")
+                .with_refresh_token(false)
+                .with_expires_in(None),
+        )
+        .unwrap();
+        let (context, _) = block_on(client.resume_session()).unwrap();
+        let auth = context.authentication().unwrap();
+        assert_eq!(auth.access_token, "token");
+        assert_eq!(auth.refresh_token, None);
+        assert_eq!(auth.expires, None);
+    }
+
+    #[test]
+    fn failures() {
+        let err = OAuth2Error::Internal("offline".into());
+        assert!(create(MockConfig::new().with_failure(Operation::Discovery, err.clone())).is_err());
+
+        let client = create(MockConfig::new().with_failure(Operation::Refresh, err)).unwrap();
+        assert!(block_on(client.exchange_code("mock-code".into(), ())).is_ok());
+        assert!(block_on(client.exchange_refresh_token("mock-refresh-token".into(), ())).is_err());
+    }
+
+    #[test]
+    fn claims() {
+        let client = create(
+            MockConfig::new().with_claims(serde_json::json!({"sub": "alice", "name": "Alice"})),
+        )
+        .unwrap();
+        let (context, _) = block_on(client.exchange_code("mock-code".into(), ())).unwrap();
+        let claims = context.claims().unwrap();
+        assert_eq!(claims.subject().as_str(), "alice");
+        assert_eq!(claims.issuer().as_str(), "https://mock");
+    }
+}
@@ -1,23 +1,39 @@
 //! Client implementations
 
+mod bff;
+mod http;
+#[cfg(feature = "openid")]
+mod keys;
+#[cfg(feature = "openid")]
+mod metadata;
+#[cfg(feature = "testing")]
+mod mock;
 mod oauth2;
 #[cfg(feature = "openid")]
 mod openid;
+mod replay;
 
 pub use self::oauth2::*;
+pub use bff::*;
+pub use http::*;
+#[cfg(feature = "testing")]
+pub use mock::*;
 #[cfg(feature = "openid")]
 pub use openid::*;
+pub use replay::*;
 
 use crate::{
-    agent::{InnerConfig, LogoutOptions, OAuth2Error},
-    context::OAuth2Context,
+    agent::{Clock, InnerConfig, LoginOptions, LogoutOptions, OAuth2Error},
+    config::ConfigError,
+    context::{Authentication, OAuth2Context},
 };
+use ::oauth2::{basic::BasicErrorResponse, RequestTokenError};
 use async_trait::async_trait;
-use js_sys::Date;
 use num_traits::ToPrimitive;
 use reqwest::Url;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::fmt::Debug;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -35,16 +51,33 @@ pub trait Client: 'static + Sized + Clone + Debug {
     type TokenResponse;
     type Configuration: Clone + Debug + PartialEq;
     type LoginState: Debug + Serialize + DeserializeOwned;
-    type SessionState: Clone + Debug;
+    type SessionState: Clone + Debug + Serialize + DeserializeOwned;
+
+    /// Validate the configuration, before creating the client.
+    fn validate_config(_config: &Self::Configuration) -> Result<(), Vec<ConfigError>> {
+        Ok(())
+    }
 
     async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error>;
 
+    /// Create the client for an application with a base path.
+    ///
+    /// Clients keeping state in the browser storage must keep it apart for applications with
+    /// different base paths. By default, this is the same as [`Self::from_config`].
+    async fn from_scoped_config(
+        config: Self::Configuration,
+        _base_path: Option<&str>,
+    ) -> Result<Self, OAuth2Error> {
+        Self::from_config(config).await
+    }
+
     fn set_redirect_uri(self, url: Url) -> Self;
 
     fn make_login_context(
         &self,
         config: &InnerConfig,
         redirect_url: Url,
+        options: &LoginOptions,
     ) -> Result<LoginContext<Self::LoginState>, OAuth2Error>;
 
     async fn exchange_code(
@@ -59,16 +92,183 @@ pub trait Client: 'static + Sized + Clone + Debug {
         session_state: Self::SessionState,
     ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error>;
 
+    /// Exchange the refresh token of the session for an access token with different scopes
+    /// or audience.
+    ///
+    /// This must not change the session itself, however the returned authentication may contain
+    /// a rotated refresh token.
+    async fn exchange_refresh_token_for(
+        &self,
+        refresh_token: String,
+        session_state: Self::SessionState,
+        scopes: &[String],
+        audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error>;
+
+    /// Resume a session which already exists, without a login.
+    ///
+    /// This is called on startup, when there is no other session. Clients keeping the session
+    /// elsewhere, like a backend, can report it here.
+    async fn resume_session(&self) -> Option<(OAuth2Context, Self::SessionState)> {
+        None
+    }
+
+    /// Get the configured redirect URL, if there is one.
+    ///
+    /// This is used if the login options don't set a redirect URL.
+    fn redirect_url(&self) -> Option<Url> {
+        None
+    }
+
     /// Trigger the logout of the session
     ///
     /// Clients may choose to contact some back-channel or redirect to a logout URL.
     fn logout(&self, _session_state: Self::SessionState, _options: LogoutOptions) {}
+
+    /// Get the URL used for checking if the issuer is reachable.
+    ///
+    /// Any HTTP response to a request to this URL is considered a success.
+    fn health_check_url(&self) -> Option<Url> {
+        None
+    }
+
+    /// Get the URL of the issuer's account management console, if there is one.
+    ///
+    /// The `return_url` is the URL the console should link back to.
+    fn account_url(&self, _return_url: Url) -> Option<Url> {
+        None
+    }
+
+    /// Get the raw ID token of the session, if there is one.
+    fn id_token(_session_state: &Self::SessionState) -> Option<String> {
+        None
+    }
+
+    /// Get the worker, which requests to the token endpoint are sent through.
+    #[cfg(feature = "worker")]
+    fn token_vault(&self) -> Option<&crate::worker::TokenVault> {
+        None
+    }
+}
+
+/// Add the referrer information to an account management URL.
+///
+/// This uses the parameter names of the Keycloak account console, other issuers will ignore them.
+fn account_url(account_url: &Url, client_id: &str, return_url: Url) -> Url {
+    let mut url = account_url.clone();
+    url.query_pairs_mut()
+        .append_pair("referrer", client_id)
+        .append_pair("referrer_uri", return_url.as_str());
+    url
+}
+
+/// Parse the optional account management URL from the configuration.
+fn parse_account_url(account_url: Option<String>) -> Result<Option<Url>, OAuth2Error> {
+    account_url
+        .map(|url| Url::parse(&url))
+        .transpose()
+        .map_err(|err| OAuth2Error::configuration("invalid account URL", err))
+}
+
+/// Parse the optional redirect URL from the configuration, which may be a path.
+fn parse_redirect_url(redirect_url: Option<String>) -> Result<Option<Url>, OAuth2Error> {
+    redirect_url
+        .map(|url| page_url(&url))
+        .transpose()
+        .map_err(|err| OAuth2Error::configuration("invalid redirect URL", err))
+}
+
+/// Parse a URL, which may be a path relative to the origin of the page.
+fn page_url(url: &str) -> Result<Url, ::oauth2::url::ParseError> {
+    match Url::parse(url) {
+        Err(::oauth2::url::ParseError::RelativeUrlWithoutBase) if url.starts_with('/') => {
+            let base = gloo_utils::window()
+                .location()
+                .href()
+                .map_err(|_| ::oauth2::url::ParseError::RelativeUrlWithoutBase)?;
+            Url::parse(&base)?.join(url)
+        }
+        result => result,
+    }
+}
+
+/// Navigate the page to the URL, replacing the current entry of the history if requested.
+///
+/// Navigating to an installed fake issuer is handled by it, see [`crate::testing`].
+pub(crate) fn navigate(url: &str, replace: bool) -> Result<(), wasm_bindgen::JsValue> {
+    #[cfg(feature = "testing")]
+    if crate::testing::intercept(url) {
+        return Ok(());
+    }
+
+    let location = gloo_utils::window().location();
+    match replace {
+        true => location.replace(url),
+        false => location.set_href(url),
+    }
+}
+
+/// Perform a request to the token endpoint, through the token vault if there is one.
+async fn token_request(
+    http_client: &SharedHttpClient,
+    #[cfg(feature = "worker")] token_vault: Option<&crate::worker::TokenVault>,
+    request: ::oauth2::HttpRequest,
+) -> Result<::oauth2::HttpResponse, HttpClientError> {
+    #[cfg(feature = "worker")]
+    if let Some(token_vault) = token_vault {
+        return token_vault
+            .request(request)
+            .await
+            .map_err(::oauth2::reqwest::Error::Other);
+    }
+
+    http_client.request(request).await
+}
+
+/// The error code of an issuer, which is temporarily unable to handle the request.
+pub(crate) const TEMPORARILY_UNAVAILABLE: &str = "temporarily_unavailable";
+
+/// Map the error of a token request.
+///
+/// If the issuer reported being temporarily unavailable, this will be an
+/// [`OAuth2Error::TemporarilyUnavailable`].
+fn token_error<RE>(err: RequestTokenError<RE, BasicErrorResponse>) -> OAuth2Error
+where
+    RE: std::error::Error + Send + Sync + 'static,
+{
+    match err {
+        RequestTokenError::ServerResponse(response)
+            if response.error().as_ref() == TEMPORARILY_UNAVAILABLE =>
+        {
+            OAuth2Error::TemporarilyUnavailable(response.error_description().cloned())
+        }
+        RequestTokenError::ServerResponse(response) => OAuth2Error::Server {
+            error: response.error().as_ref().to_string(),
+            description: response.error_description().cloned(),
+            uri: response.error_uri().cloned(),
+        },
+        RequestTokenError::Request(err) => OAuth2Error::network(err),
+        RequestTokenError::Parse(err, _) => OAuth2Error::Validation {
+            message: "failed to parse token response".to_string(),
+            source: Some(Arc::new(err)),
+        },
+        RequestTokenError::Other(err) => OAuth2Error::validation(err),
+    }
+}
+
+/// Yield to the event loop of the browser.
+///
+/// The agent runs on the main thread, using a single threaded executor. So CPU heavy work, like
+/// parsing and verifying large tokens, blocks rendering until the next `.await` which actually
+/// suspends. Yielding between such steps lets the browser render, and process events.
+async fn yield_now() {
+    gloo_timers::future::TimeoutFuture::new(0).await;
 }
 
 /// Convert a duration to a timestamp, in seconds.
 fn expires(expires_in: Option<Duration>) -> Option<u64> {
     if let Some(expires_in) = expires_in {
-        let expires = ((Date::now() / 1000f64) + expires_in.as_secs_f64())
+        let expires = (Clock::current().now_secs() + expires_in.as_secs_f64())
             .to_u64()
             .unwrap_or(u64::MAX);
         Some(expires)
@@ -1,20 +1,24 @@
 use crate::{
     agent::{
-        client::{expires, Client, LoginContext},
-        InnerConfig, OAuth2Error,
+        client::{
+            account_url, expires, page_url, parse_account_url, parse_redirect_url, token_error,
+            token_request, yield_now, Client, HttpClientError, LoginContext, SharedHttpClient,
+        },
+        InnerConfig, LoginOptions, OAuth2Error,
     },
-    config::oauth2,
+    config::{oauth2, ConfigError},
     context::{Authentication, OAuth2Context},
 };
 use ::oauth2::{
     basic::{BasicClient, BasicTokenResponse},
-    reqwest::async_http_client,
-    url::Url,
-    AuthUrl, AuthorizationCode, ClientId, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
-    RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
+    http::{header::CONTENT_TYPE, HeaderValue, StatusCode},
+    url::{form_urlencoded, Url},
+    AuthUrl, AuthorizationCode, ClientId, CsrfToken, HttpRequest, HttpResponse, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
 };
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fmt::Debug;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -26,17 +30,51 @@ pub struct LoginState {
 #[derive(Clone, Debug)]
 pub struct OAuth2Client {
     client: BasicClient,
+    /// The URL of the account management console
+    account_url: Option<Url>,
+    /// Additional parameters of the authorization request
+    additional_auth_params: Vec<(String, String)>,
+    /// Correct token responses which don't follow the specification
+    lenient_token_response: bool,
+    /// The configured redirect URL
+    redirect_url: Option<Url>,
+    /// The client for requests to the issuer
+    http: SharedHttpClient,
+    /// The worker keeping the refresh tokens
+    #[cfg(feature = "worker")]
+    token_vault: Option<crate::worker::TokenVault>,
 }
 
 impl OAuth2Client {
+    /// Perform a request to the token endpoint.
+    async fn http_client(&self, request: HttpRequest) -> Result<HttpResponse, HttpClientError> {
+        let response = token_request(
+            &self.http,
+            #[cfg(feature = "worker")]
+            self.token_vault.as_ref(),
+            request,
+        )
+        .await?;
+        Ok(match self.lenient_token_response {
+            true => lenient_token_response(response),
+            false => response,
+        })
+    }
+
     fn make_authenticated(result: BasicTokenResponse) -> OAuth2Context {
-        OAuth2Context::Authenticated(Authentication {
+        OAuth2Context::Authenticated(Self::make_authentication(result))
+    }
+
+    fn make_authentication(result: BasicTokenResponse) -> Authentication {
+        Authentication {
             access_token: result.access_token().secret().to_string(),
             refresh_token: result.refresh_token().map(|t| t.secret().to_string()),
             expires: expires(result.expires_in()),
             #[cfg(feature = "openid")]
             claims: None,
-        })
+            #[cfg(feature = "openid")]
+            user_info: None,
+        }
     }
 }
 
@@ -47,26 +85,55 @@ impl Client for OAuth2Client {
     type LoginState = LoginState;
     type SessionState = ();
 
+    fn validate_config(config: &Self::Configuration) -> Result<(), Vec<ConfigError>> {
+        config.validate()
+    }
+
     async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error> {
         let oauth2::Config {
             client_id,
             auth_url,
             token_url,
+            account_url,
+            additional_auth_params,
+            lenient_token_response,
+            redirect_url,
+            http_client,
+            #[cfg(feature = "worker")]
+            token_vault,
         } = config;
 
         let client = BasicClient::new(
             ClientId::new(client_id),
             None,
             AuthUrl::new(auth_url)
-                .map_err(|err| OAuth2Error::Configuration(format!("invalid auth URL: {err}")))?,
+                .map_err(|err| OAuth2Error::configuration("invalid auth URL", err))?,
             Some(
-                TokenUrl::new(token_url).map_err(|err| {
-                    OAuth2Error::Configuration(format!("invalid token URL: {err}"))
-                })?,
+                page_url(&token_url)
+                    .map(TokenUrl::from_url)
+                    .map_err(|err| OAuth2Error::configuration("invalid token URL", err))?,
             ),
         );
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            account_url: parse_account_url(account_url)?,
+            additional_auth_params,
+            lenient_token_response,
+            redirect_url: parse_redirect_url(redirect_url)?,
+            http: http_client.unwrap_or_default(),
+            #[cfg(feature = "worker")]
+            token_vault,
+        })
+    }
+
+    fn redirect_url(&self) -> Option<Url> {
+        self.redirect_url.clone()
+    }
+
+    #[cfg(feature = "worker")]
+    fn token_vault(&self) -> Option<&crate::worker::TokenVault> {
+        self.token_vault.as_ref()
     }
 
     fn set_redirect_uri(mut self, url: Url) -> Self {
@@ -78,16 +145,19 @@ impl Client for OAuth2Client {
         &self,
         config: &InnerConfig,
         redirect_url: Url,
+        _options: &LoginOptions,
     ) -> Result<LoginContext<Self::LoginState>, OAuth2Error> {
         let client = self
             .client
             .clone()
             .set_redirect_uri(RedirectUrl::from_url(redirect_url));
 
-        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let pkce_verifier = PkceCodeVerifier::new(config.random.token()?);
+        let pkce_challenge = PkceCodeChallenge::from_code_verifier_sha256(&pkce_verifier);
+        let csrf_token = CsrfToken::new(config.random.token()?);
 
         let mut req = client
-            .authorize_url(CsrfToken::new_random)
+            .authorize_url(move || csrf_token)
             .add_scopes(
                 config
                     .scopes
@@ -101,6 +171,10 @@ impl Client for OAuth2Client {
             req = req.add_extra_param("audience".to_string(), audience.clone())
         }
 
+        for (key, value) in &self.additional_auth_params {
+            req = req.add_extra_param(key, value);
+        }
+
         let (url, state) = req.url();
 
         Ok(LoginContext {
@@ -123,12 +197,15 @@ impl Client for OAuth2Client {
             .client
             .exchange_code(AuthorizationCode::new(code))
             .set_pkce_verifier(pkce_verifier)
-            .request_async(async_http_client)
+            .request_async(|request| self.http_client(request))
             .await
-            .map_err(|err| OAuth2Error::LoginResult(format!("failed to exchange code: {err}")))?;
+            .map_err(token_error)?;
 
         log::debug!("Exchange code result: {:?}", result);
 
+        // parsing the response may have taken a while, let the browser render
+        yield_now().await;
+
         Ok((Self::make_authenticated(result), ()))
     }
 
@@ -140,12 +217,163 @@ impl Client for OAuth2Client {
         let result = self
             .client
             .exchange_refresh_token(&RefreshToken::new(refresh_token))
-            .request_async(async_http_client)
+            .request_async(|request| self.http_client(request))
             .await
-            .map_err(|err| {
-                OAuth2Error::Refresh(format!("failed to exchange refresh token: {err}"))
-            })?;
+            .map_err(token_error)?;
 
         Ok((Self::make_authenticated(result), session_state))
     }
+
+    async fn exchange_refresh_token_for(
+        &self,
+        refresh_token: String,
+        _session_state: Self::SessionState,
+        scopes: &[String],
+        audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error> {
+        let refresh_token = RefreshToken::new(refresh_token);
+        let mut req = self
+            .client
+            .exchange_refresh_token(&refresh_token)
+            .add_scopes(scopes.iter().map(|s| Scope::new(s.clone())));
+
+        if let Some(audience) = audience {
+            req = req.add_extra_param("audience", audience);
+        }
+
+        let result = req
+            .request_async(|request| self.http_client(request))
+            .await
+            .map_err(token_error)?;
+
+        Ok(Self::make_authentication(result))
+    }
+
+    fn health_check_url(&self) -> Option<Url> {
+        // this may fail, if the token endpoint does not respond with CORS headers
+        self.client.token_url().map(|url| url.url().clone())
+    }
+
+    fn account_url(&self, return_url: Url) -> Option<Url> {
+        self.account_url
+            .as_ref()
+            .map(|url| account_url(url, self.client.client_id(), return_url))
+    }
+}
+
+/// Correct a token response, which doesn't follow the specification.
+///
+/// A form-encoded body is converted to JSON, a missing token type defaults to `bearer`, and an
+/// error reported with a successful status gets the status `400`.
+fn lenient_token_response(mut response: HttpResponse) -> HttpResponse {
+    let mut body = match serde_json::from_slice::<Map<String, Value>>(&response.body) {
+        Ok(body) => body,
+        Err(_) => form_urlencoded::parse(&response.body)
+            .map(|(key, value)| {
+                let value = match key.as_ref() {
+                    "expires_in" | "refresh_token_expires_in" => value
+                        .parse::<u64>()
+                        .map(Value::from)
+                        .unwrap_or_else(|_| Value::from(value.as_ref())),
+                    _ => Value::from(value.as_ref()),
+                };
+                (key.into_owned(), value)
+            })
+            .collect(),
+    };
+
+    if body.contains_key("error") {
+        if response.status_code.is_success() {
+            response.status_code = StatusCode::BAD_REQUEST;
+        }
+    } else if body.contains_key("access_token") {
+        body.entry("token_type")
+            .or_insert_with(|| Value::from("bearer"));
+    }
+
+    response
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response.body = Value::Object(body).to_string().into_bytes();
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::oauth2::http::HeaderMap;
+
+    fn response(status: StatusCode, body: &str) -> HttpResponse {
+        HttpResponse {
+            status_code: status,
+            headers: HeaderMap::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn json(response: &HttpResponse) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    #[test]
+    fn lenient_form_response() {
+        // like GitHub, without an `Accept` header
+        let corrected = lenient_token_response(response(
+            StatusCode::OK,
+            "access_token=gho_abc&scope=repo&expires_in=28800&refresh_token=ghr_def",
+        ));
+        assert_eq!(corrected.status_code, StatusCode::OK);
+        assert_eq!(corrected.headers[CONTENT_TYPE], "application/json");
+        assert_eq!(
+            json(&corrected),
+            serde_json::json!({
+                "access_token": "gho_abc",
+                "scope": "repo",
+                "expires_in": 28800,
+                "refresh_token": "ghr_def",
+                "token_type": "bearer",
+            })
+        );
+
+        let parsed = serde_json::from_slice::<BasicTokenResponse>(&corrected.body).unwrap();
+        assert_eq!(parsed.access_token().secret(), "gho_abc");
+        assert_eq!(parsed.token_type().as_ref(), "bearer");
+    }
+
+    #[test]
+    fn lenient_json_response() {
+        // a token type of the issuer is kept
+        let corrected = lenient_token_response(response(
+            StatusCode::OK,
+            r#"{"access_token":"abc","token_type":"mac"}"#,
+        ));
+        assert_eq!(json(&corrected)["token_type"], "mac");
+    }
+
+    #[test]
+    fn lenient_error_response() {
+        // an error, reported with a successful status
+        let corrected = lenient_token_response(response(
+            StatusCode::OK,
+            "error=bad_verification_code&error_description=The+code+is+incorrect",
+        ));
+        assert_eq!(corrected.status_code, StatusCode::BAD_REQUEST);
+        assert_eq!(json(&corrected)["error"], "bad_verification_code");
+        assert!(json(&corrected).get("token_type").is_none());
+
+        // the status of an error is kept
+        let corrected = lenient_token_response(response(
+            StatusCode::UNAUTHORIZED,
+            r#"{"error":"invalid_client"}"#,
+        ));
+        assert_eq!(corrected.status_code, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn lenient_invalid_response() {
+        // neither a token nor an error, still failing to parse as token response
+        let corrected = lenient_token_response(response(StatusCode::OK, "scope=repo"));
+        assert_eq!(corrected.status_code, StatusCode::OK);
+        assert!(serde_json::from_slice::<BasicTokenResponse>(&corrected.body).is_err());
+    }
 }
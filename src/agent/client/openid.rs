@@ -1,10 +1,17 @@
 use crate::{
     agent::{
-        client::{expires, Client, LoginContext},
-        InnerConfig, LogoutOptions, OAuth2Error,
+        client::{
+            account_url, expires, keys::KeyCache, metadata as metadata_cache, navigate, page_url,
+            parse_account_url, parse_redirect_url, token_error, token_request, yield_now, Client,
+            HttpClient, HttpClientError, LoginContext, SharedHttpClient,
+        },
+        Clock, InnerConfig, LoginOptions, LogoutOptions, OAuth2Error,
+    },
+    config::{openid, ConfigError},
+    context::{
+        token::{jwt_claims, jwt_header},
+        Authentication, Claims, OAuth2Context, UserInfo,
     },
-    config::openid,
-    context::{Authentication, OAuth2Context},
 };
 use async_trait::async_trait;
 use gloo_utils::window;
@@ -12,32 +19,54 @@ use oauth2::TokenResponse;
 use openidconnect::{
     core::{
         CoreAuthDisplay, CoreAuthenticationFlow, CoreClaimName, CoreClaimType, CoreClient,
-        CoreClientAuthMethod, CoreGenderClaim, CoreGrantType, CoreJsonWebKey, CoreJsonWebKeyType,
-        CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm, CoreJweKeyManagementAlgorithm,
-        CoreJwsSigningAlgorithm, CoreResponseMode, CoreResponseType, CoreSubjectIdentifierType,
-        CoreTokenResponse,
+        CoreClientAuthMethod, CoreGenderClaim, CoreGrantType, CoreIdToken, CoreIdTokenVerifier,
+        CoreJsonWebKey, CoreJsonWebKeyType, CoreJsonWebKeyUse, CoreJweContentEncryptionAlgorithm,
+        CoreJweKeyManagementAlgorithm, CoreJwsSigningAlgorithm, CoreResponseMode, CoreResponseType,
+        CoreSubjectIdentifierType, CoreTokenResponse,
     },
-    reqwest::async_http_client,
-    AuthorizationCode, ClientId, CsrfToken, EmptyAdditionalClaims, IdTokenClaims, IssuerUrl, Nonce,
-    PkceCodeChallenge, PkceCodeVerifier, ProviderMetadata, RedirectUrl, RefreshToken, Scope,
+    AuthUrl, AuthenticationContextClass, AuthorizationCode, ClaimsVerificationError, ClientId,
+    CsrfToken, DiscoveryError, EmptyAdditionalClaims, IssuerUrl, JsonWebKeySet, JsonWebKeySetUrl,
+    Nonce, PkceCodeChallenge, PkceCodeVerifier, ProviderMetadata, RedirectUrl, RefreshToken,
+    ResponseTypes, Scope, TokenUrl, UserInfoClaims, UserInfoUrl,
 };
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, rc::Rc};
+use std::{fmt::Debug, rc::Rc, sync::Arc};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OpenIdLoginState {
     pub pkce_verifier: String,
     pub nonce: String,
+    /// The requested authentication context classes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub acr_values: Vec<String>,
+}
+
+/// Session state of an OpenID Connect session
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OpenIdSessionState {
+    /// The ID token
+    pub id_token: String,
+    /// The verified claims of the ID token
+    pub claims: Rc<Claims>,
+    /// The user info, if requested
+    pub user_info: Option<Rc<UserInfo>>,
 }
 
 const DEFAULT_POST_LOGOUT_DIRECT_NAME: &str = "post_logout_redirect_uri";
 
+/// The placeholder of multi-tenant issuers
+const TENANT_PLACEHOLDER: &str = "{tenantid}";
+
 /// An OpenID Connect based client implementation
 #[derive(Clone, Debug)]
 pub struct OpenIdClient {
     /// The client
     client: CoreClient,
+    /// The client ID
+    client_id: String,
+    /// The URL used for checking the health of the issuer
+    health_check_url: Url,
     /// An override for the URL to end the session (logout)
     end_session_url: Option<Url>,
     /// A URL to direct to after the logout was performed
@@ -46,6 +75,35 @@ pub struct OpenIdClient {
     post_logout_redirect_name: Option<String>,
     /// Additional audiences of the ID token which are considered trustworthy
     additional_trusted_audiences: Vec<String>,
+    /// The URL of the account management console
+    account_url: Option<Url>,
+    /// Fetch the user info after the login
+    fetch_user_info: bool,
+    /// Additional parameters of the authorization request
+    additional_auth_params: Vec<(String, String)>,
+    /// Additional parameters of the end session request
+    additional_logout_params: Vec<(String, String)>,
+    /// The issuer announced by a multi-tenant issuer, containing the tenant placeholder
+    issuer_template: Option<String>,
+    /// The issuers accepted in addition to the announced one, if any
+    expected_issuers: Vec<String>,
+    /// The issuer of ID tokens
+    issuer: IssuerUrl,
+    /// The algorithms allowed for signing ID tokens
+    signing_algs: Vec<CoreJwsSigningAlgorithm>,
+    /// The options for validating ID tokens
+    id_token_validation: openid::IdTokenValidation,
+    /// The signing keys of the issuer
+    keys: KeyCache,
+    /// The issuer URL, if its metadata is cached
+    cached_issuer: Option<String>,
+    /// The configured redirect URL
+    redirect_url: Option<Url>,
+    /// The client for requests to the issuer
+    http: SharedHttpClient,
+    /// The worker keeping the refresh tokens
+    #[cfg(feature = "worker")]
+    token_vault: Option<crate::worker::TokenVault>,
 }
 
 /// Additional metadata read from the discovery endpoint
@@ -80,10 +138,11 @@ impl Client for OpenIdClient {
     type TokenResponse = CoreTokenResponse;
     type Configuration = openid::Config;
     type LoginState = OpenIdLoginState;
-    type SessionState = (
-        String,
-        Rc<IdTokenClaims<EmptyAdditionalClaims, CoreGenderClaim>>,
-    );
+    type SessionState = OpenIdSessionState;
+
+    fn validate_config(config: &Self::Configuration) -> Result<(), Vec<ConfigError>> {
+        config.validate()
+    }
 
     async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error> {
         let openid::Config {
@@ -93,36 +152,160 @@ impl Client for OpenIdClient {
             after_logout_url,
             post_logout_redirect_name,
             additional_trusted_audiences,
+            account_url,
+            fetch_user_info,
+            additional_auth_params,
+            additional_logout_params,
+            multi_tenant,
+            metadata,
+            discovery_cache_ttl,
+            metadata_url,
+            expected_issuers,
+            id_token_validation,
+            token_url,
+            redirect_url,
+            http_client,
+            #[cfg(feature = "worker")]
+            token_vault,
         } = config;
+        let http = http_client.unwrap_or_default();
 
-        let issuer = IssuerUrl::new(issuer_url)
-            .map_err(|err| OAuth2Error::Configuration(format!("invalid issuer URL: {err}")))?;
+        let issuer = IssuerUrl::new(issuer_url.clone())
+            .map_err(|err| OAuth2Error::configuration("invalid issuer URL", err))?;
 
-        let metadata = ExtendedProviderMetadata::discover_async(issuer, async_http_client)
-            .await
-            .map_err(|err| {
-                OAuth2Error::Configuration(format!("Failed to discover client: {err}"))
-            })?;
+        let signing_algs = id_token_validation
+            .signing_algs
+            .iter()
+            .map(|alg| {
+                serde_json::from_value(serde_json::Value::String(alg.clone())).map_err(|err| {
+                    OAuth2Error::configuration(format!("unsupported signing algorithm: {alg}"), err)
+                })
+            })
+            .collect::<Result<Vec<CoreJwsSigningAlgorithm>, _>>()?;
+
+        let custom_metadata_url = metadata_url.is_some();
+        let metadata_url = match metadata_url {
+            Some(url) => Url::parse(&url)
+                .map_err(|err| OAuth2Error::configuration("invalid metadata URL", err))?,
+            None => default_metadata_url(&issuer),
+        };
+
+        let cached = match (&metadata, discovery_cache_ttl) {
+            (None, Some(ttl)) => metadata_cache::load(&issuer_url, ttl),
+            _ => None,
+        };
+        let discovered = metadata.is_none() && cached.is_none();
+
+        let (metadata, health_check_url) = match (metadata, cached) {
+            (_, Some(cached)) => (Ok(cached), metadata_url),
+            (Some(metadata), None) => {
+                // the discovery document may not be available, check the keys instead
+                let health_check_url = Url::parse(&metadata.jwks_uri)
+                    .map_err(|err| OAuth2Error::configuration("invalid JWKS URL", err))?;
+                (
+                    static_metadata(issuer, metadata, &signing_algs, &http).await,
+                    health_check_url,
+                )
+            }
+            (None, None) if multi_tenant || custom_metadata_url || !expected_issuers.is_empty() => {
+                (
+                    discover(
+                        &issuer,
+                        &metadata_url,
+                        multi_tenant,
+                        &expected_issuers,
+                        &http,
+                    )
+                    .await,
+                    metadata_url,
+                )
+            }
+            (None, None) => (
+                ExtendedProviderMetadata::discover_async(issuer, |request| http.request(request))
+                    .await,
+                metadata_url,
+            ),
+        };
+        let mut metadata = metadata.map_err(OAuth2Error::discovery)?;
+
+        if discovered && discovery_cache_ttl.is_some() {
+            metadata_cache::store(&issuer_url, &metadata);
+        }
+
+        // the metadata and key set can be large, let the browser render before continuing
+        yield_now().await;
+
+        let issuer_template = Some(metadata.issuer().to_string())
+            .filter(|issuer| issuer.contains(TENANT_PLACEHOLDER));
 
         let end_session_url = end_session_url
             .map(|url| Url::parse(&url))
             .transpose()
-            .map_err(|err| {
-                OAuth2Error::Configuration(format!("Unable to parse end_session_url: {err}"))
-            })?
+            .map_err(|err| OAuth2Error::configuration("invalid end session URL", err))?
             .or_else(|| metadata.additional_metadata().end_session_endpoint.clone());
 
-        let client = CoreClient::from_provider_metadata(metadata, ClientId::new(client_id), None);
+        if let Some(token_url) = token_url {
+            // e.g. a same-origin proxy, for an endpoint without CORS headers
+            let token_url = page_url(&token_url)
+                .map_err(|err| OAuth2Error::configuration("invalid token URL", err))?;
+            metadata = metadata.set_token_endpoint(Some(TokenUrl::from_url(token_url)));
+        }
+
+        let issuer = metadata.issuer().clone();
+        let signing_algs = match signing_algs.is_empty() {
+            true => metadata.id_token_signing_alg_values_supported().clone(),
+            false => signing_algs,
+        };
+        let keys = KeyCache::new(
+            metadata.jwks_uri().clone(),
+            metadata.jwks().clone(),
+            http.clone(),
+        );
+
+        let client =
+            CoreClient::from_provider_metadata(metadata, ClientId::new(client_id.clone()), None);
 
         Ok(Self {
             client,
+            client_id,
+            health_check_url,
             end_session_url,
             after_logout_url,
             post_logout_redirect_name,
             additional_trusted_audiences,
+            account_url: parse_account_url(account_url)?,
+            fetch_user_info,
+            additional_auth_params,
+            additional_logout_params,
+            issuer_template,
+            expected_issuers: match expected_issuers.is_empty() {
+                true => vec![],
+                false => [issuer_url.clone()]
+                    .into_iter()
+                    .chain(expected_issuers)
+                    .collect(),
+            },
+            issuer,
+            signing_algs,
+            id_token_validation,
+            keys,
+            cached_issuer: discovery_cache_ttl.map(|_| issuer_url),
+            redirect_url: parse_redirect_url(redirect_url)?,
+            http,
+            #[cfg(feature = "worker")]
+            token_vault,
         })
     }
 
+    fn redirect_url(&self) -> Option<Url> {
+        self.redirect_url.clone()
+    }
+
+    #[cfg(feature = "worker")]
+    fn token_vault(&self) -> Option<&crate::worker::TokenVault> {
+        self.token_vault.as_ref()
+    }
+
     fn set_redirect_uri(mut self, url: Url) -> Self {
         self.client = self.client.set_redirect_uri(RedirectUrl::from_url(url));
         self
@@ -132,18 +315,22 @@ impl Client for OpenIdClient {
         &self,
         config: &InnerConfig,
         redirect_url: Url,
+        options: &LoginOptions,
     ) -> Result<LoginContext<Self::LoginState>, OAuth2Error> {
         let client = self
             .client
             .clone()
             .set_redirect_uri(RedirectUrl::from_url(redirect_url));
 
-        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let pkce_verifier = PkceCodeVerifier::new(config.random.token()?);
+        let pkce_challenge = PkceCodeChallenge::from_code_verifier_sha256(&pkce_verifier);
+        let csrf_token = CsrfToken::new(config.random.token()?);
+        let nonce = Nonce::new(config.random.token()?);
 
         let mut req = client.authorize_url(
             CoreAuthenticationFlow::AuthorizationCode,
-            CsrfToken::new_random,
-            Nonce::new_random,
+            move || csrf_token,
+            move || nonce,
         );
 
         for scope in &config.scopes {
@@ -154,6 +341,14 @@ impl Client for OpenIdClient {
             req = req.add_extra_param("audience".to_string(), audience);
         }
 
+        for (key, value) in &self.additional_auth_params {
+            req = req.add_extra_param(key, value);
+        }
+
+        for acr in &options.acr_values {
+            req = req.add_auth_context_value(AuthenticationContextClass::new(acr.clone()));
+        }
+
         let (url, state, nonce) = req.set_pkce_challenge(pkce_challenge).url();
 
         Ok(LoginContext {
@@ -162,6 +357,7 @@ impl Client for OpenIdClient {
             state: OpenIdLoginState {
                 pkce_verifier: pkce_verifier.secret().clone(),
                 nonce: nonce.secret().clone(),
+                acr_values: options.acr_values.clone(),
             },
         })
     }
@@ -177,32 +373,87 @@ impl Client for OpenIdClient {
             .client
             .exchange_code(AuthorizationCode::new(code))
             .set_pkce_verifier(pkce_verifier)
-            .request_async(async_http_client)
+            .request_async(|request| self.http_client(request))
             .await
-            .map_err(|err| OAuth2Error::LoginResult(format!("failed to exchange code: {err}")))?;
+            .map_err(token_error)?;
 
         log::debug!("Exchange code result: {:?}", result);
 
-        let id_token = result.extra_fields().id_token().ok_or_else(|| {
-            OAuth2Error::LoginResult("Server did not return an ID token".to_string())
-        })?;
-
-        let claims = Rc::new(
-            id_token
-                .clone()
-                .into_claims(
-                    &self
-                        .client
-                        .id_token_verifier()
-                        .set_other_audience_verifier_fn(|aud| {
-                            self.additional_trusted_audiences.contains(aud)
-                        }),
-                    &Nonce::new(state.nonce),
-                )
-                .map_err(|err| {
-                    OAuth2Error::LoginResult(format!("failed to verify ID token: {err}"))
-                })?,
-        );
+        // parsing the response may have taken a while, let the browser render
+        yield_now().await;
+
+        let id_token = result
+            .extra_fields()
+            .id_token()
+            .ok_or_else(|| OAuth2Error::validation("server did not return an ID token"))?;
+
+        let nonce = Nonce::new(state.nonce);
+        let claims = match self.verify_id_token(id_token, &nonce) {
+            Err(err) if self.is_unknown_key(id_token) => {
+                // the issuer may have rotated its keys, fetch them once and try again
+                log::info!("ID token is signed with an unknown key, reloading the key set");
+                if let Some(issuer) = &self.cached_issuer {
+                    metadata_cache::evict(issuer);
+                }
+                match self.keys.reload().await {
+                    true => self.verify_id_token(id_token, &nonce),
+                    false => Err(err),
+                }
+            }
+            result => result,
+        };
+
+        let claims = Rc::new(claims.map_err(|err| {
+            if let Some(issuer) = &self.cached_issuer {
+                // the keys may have been rotated, discover again next time
+                metadata_cache::evict(issuer);
+            }
+            OAuth2Error::Validation {
+                message: "failed to verify ID token".to_string(),
+                source: Some(Arc::new(err)),
+            }
+        })?);
+
+        // verifying the signature may have taken a while as well
+        yield_now().await;
+
+        if let Some(template) = &self.issuer_template {
+            let tenant = jwt_claims(&id_token.to_string())
+                .and_then(|claims| claims.get("tid")?.as_str().map(ToString::to_string));
+            let expected = tenant.map(|tenant| template.replace(TENANT_PLACEHOLDER, &tenant));
+            if expected.as_deref() != Some(claims.issuer().as_str()) {
+                return Err(OAuth2Error::validation(format!(
+                    "issuer of the ID token doesn't match its tenant: {}",
+                    claims.issuer().as_str()
+                )));
+            }
+        } else if !self.expected_issuers.is_empty() {
+            let issuer = claims.issuer().as_str();
+            if issuer != self.issuer.as_str() && !self.expected_issuers.iter().any(|e| e == issuer)
+            {
+                return Err(OAuth2Error::validation(format!(
+                    "unexpected issuer of the ID token: {issuer}"
+                )));
+            }
+        }
+
+        if !state.acr_values.is_empty() {
+            let acr = claims.auth_context_ref().map(|acr| acr.to_string());
+            if !acr
+                .as_ref()
+                .is_some_and(|acr| state.acr_values.contains(acr))
+            {
+                return Err(OAuth2Error::AcrNotSatisfied {
+                    requested: state.acr_values,
+                    received: acr,
+                });
+            }
+        }
+
+        let user_info = match self.fetch_user_info {
+            true => self.user_info(result.access_token().clone(), &claims).await,
+            false => None,
+        };
 
         Ok((
             OAuth2Context::Authenticated(Authentication {
@@ -210,8 +461,13 @@ impl Client for OpenIdClient {
                 refresh_token: result.refresh_token().map(|t| t.secret().to_string()),
                 expires: expires(result.expires_in()),
                 claims: Some(claims.clone()),
+                user_info: user_info.clone(),
             }),
-            (id_token.to_string(), claims),
+            OpenIdSessionState {
+                id_token: id_token.to_string(),
+                claims,
+                user_info,
+            },
         ))
     }
 
@@ -223,23 +479,53 @@ impl Client for OpenIdClient {
         let result = self
             .client
             .exchange_refresh_token(&RefreshToken::new(refresh_token))
-            .request_async(async_http_client)
+            .request_async(|request| self.http_client(request))
             .await
-            .map_err(|err| {
-                OAuth2Error::Refresh(format!("failed to exchange refresh token: {err}"))
-            })?;
+            .map_err(token_error)?;
 
         Ok((
             OAuth2Context::Authenticated(Authentication {
                 access_token: result.access_token().secret().to_string(),
                 refresh_token: result.refresh_token().map(|t| t.secret().to_string()),
                 expires: expires(result.expires_in()),
-                claims: Some(session_state.1.clone()),
+                claims: Some(session_state.claims.clone()),
+                user_info: session_state.user_info.clone(),
             }),
             session_state,
         ))
     }
 
+    async fn exchange_refresh_token_for(
+        &self,
+        refresh_token: String,
+        session_state: Self::SessionState,
+        scopes: &[String],
+        audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error> {
+        let refresh_token = RefreshToken::new(refresh_token);
+        let mut req = self
+            .client
+            .exchange_refresh_token(&refresh_token)
+            .add_scopes(scopes.iter().map(|s| Scope::new(s.clone())));
+
+        if let Some(audience) = audience {
+            req = req.add_extra_param("audience", audience);
+        }
+
+        let result = req
+            .request_async(|request| self.http_client(request))
+            .await
+            .map_err(token_error)?;
+
+        Ok(Authentication {
+            access_token: result.access_token().secret().to_string(),
+            refresh_token: result.refresh_token().map(|t| t.secret().to_string()),
+            expires: expires(result.expires_in()),
+            claims: Some(session_state.claims),
+            user_info: session_state.user_info,
+        })
+    }
+
     fn logout(&self, session_state: Self::SessionState, options: LogoutOptions) {
         if let Some(url) = &self.end_session_url {
             let mut url = url.clone();
@@ -250,7 +536,14 @@ impl Client for OpenIdClient {
                 .unwrap_or(DEFAULT_POST_LOGOUT_DIRECT_NAME);
 
             url.query_pairs_mut()
-                .append_pair("id_token_hint", &session_state.0);
+                .append_pair("id_token_hint", &session_state.id_token);
+
+            if let Some(state) = &options.state {
+                url.query_pairs_mut().append_pair("state", state);
+            }
+
+            url.query_pairs_mut()
+                .extend_pairs(&self.additional_logout_params);
 
             if let Some(after) = options
                 .target
@@ -262,14 +555,318 @@ impl Client for OpenIdClient {
 
             log::info!("Navigating to: {url}");
 
-            window().location().replace(url.as_str()).ok();
+            navigate(url.as_str(), true).ok();
         } else {
             log::warn!("Found no session end URL");
         }
     }
+
+    fn health_check_url(&self) -> Option<Url> {
+        Some(self.health_check_url.clone())
+    }
+
+    fn account_url(&self, return_url: Url) -> Option<Url> {
+        self.account_url
+            .as_ref()
+            .map(|url| account_url(url, &self.client_id, return_url))
+    }
+
+    fn id_token(session_state: &Self::SessionState) -> Option<String> {
+        Some(session_state.id_token.clone())
+    }
+}
+
+/// The URL of the discovery endpoint, derived from the issuer URL.
+pub(crate) fn default_metadata_url(issuer: &IssuerUrl) -> Url {
+    let mut url = issuer.url().clone();
+    let path = format!(
+        "{}/.well-known/openid-configuration",
+        url.path().trim_end_matches('/')
+    );
+    url.set_path(&path);
+    url
+}
+
+type HttpDiscoveryError = DiscoveryError<HttpClientError>;
+
+/// Discover the metadata of an issuer, using the metadata URL.
+///
+/// For a multi-tenant issuer, the announced issuer may also be the issuer URL with the tenant
+/// replaced by the tenant placeholder. Otherwise, it may be one of the expected issuers.
+async fn discover(
+    issuer: &IssuerUrl,
+    metadata_url: &Url,
+    multi_tenant: bool,
+    expected_issuers: &[String],
+    http: &SharedHttpClient,
+) -> Result<ExtendedProviderMetadata, HttpDiscoveryError> {
+    let response = http
+        .request(openidconnect::HttpRequest {
+            url: metadata_url.clone(),
+            method: openidconnect::http::Method::GET,
+            headers: [(
+                openidconnect::http::header::ACCEPT,
+                openidconnect::http::HeaderValue::from_static("application/json"),
+            )]
+            .into_iter()
+            .collect(),
+            body: vec![],
+        })
+        .await
+        .map_err(DiscoveryError::Request)?;
+
+    if response.status_code != openidconnect::http::StatusCode::OK {
+        return Err(DiscoveryError::Response(
+            response.status_code,
+            response.body,
+            "unexpected status code".to_string(),
+        ));
+    }
+
+    yield_now().await;
+
+    let mut metadata: ExtendedProviderMetadata = serde_json::from_slice(&response.body)
+        .map_err(|err| DiscoveryError::Other(format!("failed to parse metadata: {err}")))?;
+
+    let announced = metadata.issuer().as_str();
+    if announced != issuer.as_str()
+        && !(multi_tenant && matches_tenant_template(announced, issuer.as_str()))
+        && !expected_issuers
+            .iter()
+            .any(|expected| expected == announced)
+    {
+        return Err(DiscoveryError::Validation(format!(
+            "unexpected issuer URI `{announced}` (expected `{}`)",
+            issuer.as_str()
+        )));
+    }
+
+    let jwks =
+        JsonWebKeySet::fetch_async(metadata.jwks_uri(), |request| http.request(request)).await?;
+    metadata = metadata.set_jwks(jwks);
+
+    Ok(metadata)
+}
+
+/// Create the metadata of the issuer from the configured endpoints, fetching only its keys.
+///
+/// The issuer is assumed to sign ID tokens using the configured algorithms, or `RS256` if there
+/// are none.
+async fn static_metadata(
+    issuer: IssuerUrl,
+    metadata: openid::StaticMetadata,
+    signing_algs: &[CoreJwsSigningAlgorithm],
+    http: &SharedHttpClient,
+) -> Result<ExtendedProviderMetadata, HttpDiscoveryError> {
+    let openid::StaticMetadata {
+        authorization_endpoint,
+        token_endpoint,
+        jwks_uri,
+        userinfo_endpoint,
+    } = metadata;
+
+    let jwks_uri = JsonWebKeySetUrl::new(jwks_uri).map_err(DiscoveryError::UrlParse)?;
+    let userinfo_endpoint = userinfo_endpoint
+        .map(UserInfoUrl::new)
+        .transpose()
+        .map_err(DiscoveryError::UrlParse)?;
+
+    let metadata = ExtendedProviderMetadata::new(
+        issuer,
+        AuthUrl::new(authorization_endpoint).map_err(DiscoveryError::UrlParse)?,
+        jwks_uri,
+        vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+        vec![CoreSubjectIdentifierType::Public],
+        match signing_algs.is_empty() {
+            true => vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+            false => signing_algs.to_vec(),
+        },
+        AdditionalProviderMetadata {
+            end_session_endpoint: None,
+        },
+    )
+    .set_token_endpoint(Some(
+        TokenUrl::new(token_endpoint).map_err(DiscoveryError::UrlParse)?,
+    ))
+    .set_userinfo_endpoint(userinfo_endpoint);
+
+    let jwks =
+        JsonWebKeySet::fetch_async(metadata.jwks_uri(), |request| http.request(request)).await?;
+    Ok(metadata.set_jwks(jwks))
+}
+
+/// Get the ID of the key (`kid`) a token was signed with.
+fn key_id(token: &str) -> Option<String> {
+    jwt_header(token).and_then(|header| header.get("kid")?.as_str().map(ToString::to_string))
+}
+
+/// Check if the template matches the issuer, with one path segment replaced by the placeholder.
+///
+/// The tenant must not be empty.
+fn matches_tenant_template(template: &str, issuer: &str) -> bool {
+    let template = template.split('/').collect::<Vec<_>>();
+    let issuer = issuer.split('/').collect::<Vec<_>>();
+
+    template.len() == issuer.len()
+        && template.contains(&TENANT_PLACEHOLDER)
+        && template.iter().zip(issuer).all(|(template, issuer)| {
+            match *template == TENANT_PLACEHOLDER {
+                true => !issuer.is_empty(),
+                false => *template == issuer,
+            }
+        })
 }
 
 impl OpenIdClient {
+    /// Perform a request to the token endpoint.
+    async fn http_client(
+        &self,
+        request: openidconnect::HttpRequest,
+    ) -> Result<openidconnect::HttpResponse, HttpClientError> {
+        token_request(
+            &self.http,
+            #[cfg(feature = "worker")]
+            self.token_vault.as_ref(),
+            request,
+        )
+        .await
+    }
+
+    /// Create a verifier for ID tokens, using the signing keys of the issuer.
+    pub(crate) fn id_token_verifier(&self) -> CoreIdTokenVerifier<'_> {
+        let validation = &self.id_token_validation;
+        let leeway = chrono::Duration::from_std(validation.clock_leeway).unwrap_or_default();
+        let require_auth_time = validation.require_auth_time;
+        let max_auth_age = validation.max_auth_age.map(|max| {
+            chrono::Duration::from_std(max.saturating_add(validation.clock_leeway))
+                .unwrap_or(chrono::Duration::MAX)
+        });
+
+        CoreIdTokenVerifier::new_public_client(
+            ClientId::new(self.client_id.clone()),
+            self.issuer.clone(),
+            self.keys.get(),
+        )
+        .set_allowed_algs(self.signing_algs.clone())
+        .require_audience_match(self.id_token_validation.require_audience)
+        .set_time_fn(move || Clock::current().utc() - leeway)
+        .set_issue_time_verifier_fn(
+            move |issued| match issued > Clock::current().utc() + leeway {
+                true => Err(format!("ID token is issued in the future: {issued}")),
+                false => Ok(()),
+            },
+        )
+        .set_auth_time_verifier_fn(move |auth_time| match auth_time {
+            None if require_auth_time => Err("missing time of the authentication".to_string()),
+            Some(auth_time)
+                if max_auth_age.is_some_and(|max| Clock::current().utc() - auth_time > max) =>
+            {
+                Err(format!("authentication is too old: {auth_time}"))
+            }
+            _ => Ok(()),
+        })
+    }
+
+    /// Verify the ID token, returning its claims.
+    fn verify_id_token(
+        &self,
+        id_token: &CoreIdToken,
+        nonce: &Nonce,
+    ) -> Result<Claims, ClaimsVerificationError> {
+        let trusted_audiences = &self.additional_trusted_audiences;
+        id_token
+            .claims(
+                &self
+                    .id_token_verifier()
+                    .set_other_audience_verifier_fn(|aud| trusted_audiences.contains(aud))
+                    // verified by the caller, using the tenant of the token
+                    .require_issuer_match(
+                        self.issuer_template.is_none() && self.expected_issuers.is_empty(),
+                    ),
+                nonce,
+            )
+            .cloned()
+            .and_then(|claims| self.verify_authorized_party(claims))
+            .and_then(|claims| self.verify_not_before(&id_token.to_string(), claims))
+    }
+
+    /// Verify the ID token is valid already, if it has a `nbf` claim.
+    ///
+    /// This isn't covered by the verifier, as the claim is not part of OpenID Connect.
+    fn verify_not_before(
+        &self,
+        id_token: &str,
+        claims: Claims,
+    ) -> Result<Claims, ClaimsVerificationError> {
+        let not_before = jwt_claims(id_token).and_then(|claims| claims.get("nbf")?.as_i64());
+        let now = Clock::current().utc().timestamp();
+        let leeway = self.id_token_validation.clock_leeway.as_secs() as i64;
+
+        match not_before {
+            Some(not_before) if not_before > now.saturating_add(leeway) => {
+                Err(ClaimsVerificationError::Expired(format!(
+                    "ID token is not valid before {not_before} (current time is {now})"
+                )))
+            }
+            _ => Ok(claims),
+        }
+    }
+
+    /// Verify the authorized party of the claims, if enabled.
+    fn verify_authorized_party(&self, claims: Claims) -> Result<Claims, ClaimsVerificationError> {
+        if !self.id_token_validation.verify_authorized_party {
+            return Ok(claims);
+        }
+
+        match claims.authorized_party() {
+            Some(azp) if azp.as_str() != self.client_id => {
+                Err(ClaimsVerificationError::InvalidAudience(format!(
+                    "authorized party is not the client: {}",
+                    azp.as_str()
+                )))
+            }
+            None if claims.audiences().len() > 1 => Err(ClaimsVerificationError::InvalidAudience(
+                "missing authorized party, with multiple audiences".to_string(),
+            )),
+            _ => Ok(claims),
+        }
+    }
+
+    /// Check if the ID token is signed with a key which is not in the key set.
+    fn is_unknown_key(&self, id_token: &CoreIdToken) -> bool {
+        key_id(&id_token.to_string()).is_some_and(|kid| !self.keys.contains(&kid))
+    }
+
+    /// Fetch the user info, a failure will not fail the login.
+    async fn user_info(
+        &self,
+        access_token: openidconnect::AccessToken,
+        claims: &Claims,
+    ) -> Option<Rc<UserInfo>> {
+        let req = match self
+            .client
+            .user_info(access_token, Some(claims.subject().clone()))
+        {
+            Ok(req) => req,
+            Err(err) => {
+                log::warn!("Unable to request user info: {err}");
+                return None;
+            }
+        };
+
+        let result: Result<UserInfoClaims<EmptyAdditionalClaims, CoreGenderClaim>, _> = req
+            .request_async(|request| self.http.request(request))
+            .await;
+
+        match result {
+            Ok(user_info) => Some(Rc::new(user_info.standard_claims().clone())),
+            Err(err) => {
+                log::warn!("Failed to fetch user info: {err}");
+                None
+            }
+        }
+    }
+
     fn after_logout_url(&self) -> Option<String> {
         if let Some(after) = &self.after_logout_url {
             if Url::parse(after).is_ok() {
@@ -292,3 +889,96 @@ impl OpenIdClient {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::executor::block_on;
+    use oauth2::{http::HeaderMap, HttpRequest, HttpResponse};
+
+    const TEMPLATE: &str = "https://login.microsoftonline.com/{tenantid}/v2.0";
+
+    /// Serving an empty key set.
+    #[derive(Debug)]
+    struct Jwks;
+
+    #[async_trait(?Send)]
+    impl HttpClient for Jwks {
+        async fn request(&self, _: HttpRequest) -> Result<HttpResponse, HttpClientError> {
+            Ok(HttpResponse {
+                status_code: oauth2::http::StatusCode::OK,
+                headers: HeaderMap::new(),
+                body: br#"{"keys":[]}"#.to_vec(),
+            })
+        }
+    }
+
+    fn static_signing_algs(
+        signing_algs: &[CoreJwsSigningAlgorithm],
+    ) -> Vec<CoreJwsSigningAlgorithm> {
+        let metadata = block_on(static_metadata(
+            IssuerUrl::new("https://issuer".into()).unwrap(),
+            openid::StaticMetadata::new(
+                "https://issuer/auth",
+                "https://issuer/token",
+                "https://issuer/jwks",
+            ),
+            signing_algs,
+            &SharedHttpClient::new(Jwks),
+        ))
+        .expect("metadata");
+        metadata.id_token_signing_alg_values_supported().clone()
+    }
+
+    #[test]
+    fn static_metadata_signing_algs() {
+        assert_eq!(
+            static_signing_algs(&[]),
+            vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256]
+        );
+        assert_eq!(
+            static_signing_algs(&[
+                CoreJwsSigningAlgorithm::EcdsaP256Sha256,
+                CoreJwsSigningAlgorithm::RsaSsaPssSha256
+            ]),
+            vec![
+                CoreJwsSigningAlgorithm::EcdsaP256Sha256,
+                CoreJwsSigningAlgorithm::RsaSsaPssSha256
+            ]
+        );
+    }
+
+    #[test]
+    fn tenant_template_accepts() {
+        assert!(matches_tenant_template(
+            TEMPLATE,
+            "https://login.microsoftonline.com/9188040d-6c67-4c5b-b112-36a304b66dad/v2.0"
+        ));
+        assert!(matches_tenant_template(
+            TEMPLATE,
+            "https://login.microsoftonline.com/contoso/v2.0"
+        ));
+    }
+
+    #[test]
+    fn tenant_template_rejects() {
+        for issuer in [
+            // a different host
+            "https://login.attacker.com/contoso/v2.0",
+            // a different version
+            "https://login.microsoftonline.com/contoso/v1.0",
+            // the tenant spanning multiple segments
+            "https://login.microsoftonline.com/contoso/other/v2.0",
+            // no tenant at all
+            "https://login.microsoftonline.com//v2.0",
+            "https://login.microsoftonline.com/v2.0",
+        ] {
+            assert!(!matches_tenant_template(TEMPLATE, issuer), "{issuer}");
+        }
+        // a template without a placeholder matches nothing
+        assert!(!matches_tenant_template(
+            "https://login.microsoftonline.com/common/v2.0",
+            "https://login.microsoftonline.com/common/v2.0"
+        ));
+    }
+}
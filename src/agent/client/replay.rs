@@ -0,0 +1,231 @@
+use crate::{
+    agent::{
+        client::{expires, Client, LoginContext},
+        state::{AppScope, STORAGE_KEY_REPLAY},
+        FlowTrace, InnerConfig, LoginOptions, OAuth2Error, TraceEvent, TracedState,
+    },
+    context::{Authentication, OAuth2Context, Operation},
+};
+use async_trait::async_trait;
+use gloo_storage::{SessionStorage, Storage};
+use reqwest::Url;
+use std::{rc::Rc, time::Duration};
+
+/// A client replaying a recorded [`FlowTrace`].
+///
+/// Instead of contacting an issuer, each operation has the outcome recorded in the trace, in the
+/// order it was recorded. Starting a login navigates directly back to the application. This
+/// allows reproducing issues from bug reports locally.
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::agent::{client::ReplayClient, FlowTrace};
+/// use yew_oauth2::components::context::OAuth2;
+///
+/// #[derive(PartialEq, Properties)]
+/// struct Props {
+///     trace: FlowTrace,
+/// }
+///
+/// #[function_component(Reproducer)]
+/// fn reproducer(props: &Props) -> Html {
+///     html!(
+///         <OAuth2<ReplayClient> config={props.trace.clone()}>
+///             // the application
+///         </OAuth2<ReplayClient>>
+///     )
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReplayClient {
+    trace: Rc<FlowTrace>,
+    redirect_url: Option<Url>,
+    scope: AppScope,
+}
+
+impl ReplayClient {
+    /// Take the next recorded outcome of an operation.
+    ///
+    /// As a login navigates away, the positions are kept in the session storage, until the
+    /// logout.
+    fn next_outcome(&self, operation: Operation) -> Result<(), OAuth2Error> {
+        let key = self.scope.key(STORAGE_KEY_REPLAY);
+        let mut positions =
+            SessionStorage::get::<Vec<(Operation, usize)>>(&key).unwrap_or_default();
+        let position = match positions.iter_mut().find(|(op, _)| *op == operation) {
+            Some((_, position)) => {
+                *position += 1;
+                *position - 1
+            }
+            None => {
+                positions.push((operation, 1));
+                0
+            }
+        };
+        let _ = SessionStorage::set(&key, positions);
+
+        match self.trace.outcomes(operation).nth(position) {
+            Some(Some(err)) => Err(err.to_error()),
+            // succeed, if the trace has no more outcomes
+            Some(None) | None => Ok(()),
+        }
+    }
+
+    /// Create an authentication, resembling the recorded one.
+    fn authenticated(&self) -> OAuth2Context {
+        let recorded = self
+            .trace
+            .entries
+            .iter()
+            .find_map(|entry| match &entry.event {
+                TraceEvent::State(TracedState::Authenticated {
+                    expires_in,
+                    refresh_token,
+                }) => Some((*expires_in, *refresh_token)),
+                _ => None,
+            });
+        let (expires_in, refresh_token) = recorded.unwrap_or((None, true));
+
+        OAuth2Context::Authenticated(Authentication {
+            access_token: "replayed-access-token".into(),
+            refresh_token: refresh_token.then(|| "replayed-refresh-token".into()),
+            expires: expires(expires_in.map(Duration::from_secs)),
+            #[cfg(feature = "openid")]
+            claims: None,
+            #[cfg(feature = "openid")]
+            user_info: None,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl Client for ReplayClient {
+    type TokenResponse = ();
+    type Configuration = FlowTrace;
+    type LoginState = ();
+    type SessionState = ();
+
+    async fn from_config(config: Self::Configuration) -> Result<Self, OAuth2Error> {
+        Self::from_scoped_config(config, None).await
+    }
+
+    async fn from_scoped_config(
+        config: Self::Configuration,
+        base_path: Option<&str>,
+    ) -> Result<Self, OAuth2Error> {
+        let client = Self {
+            trace: Rc::new(config),
+            redirect_url: None,
+            scope: AppScope::new(base_path),
+        };
+        client.next_outcome(Operation::Discovery)?;
+        Ok(client)
+    }
+
+    fn set_redirect_uri(mut self, url: Url) -> Self {
+        self.redirect_url = Some(url);
+        self
+    }
+
+    fn make_login_context(
+        &self,
+        config: &InnerConfig,
+        mut redirect_url: Url,
+        _options: &LoginOptions,
+    ) -> Result<LoginContext<Self::LoginState>, OAuth2Error> {
+        self.next_outcome(Operation::Login)?;
+
+        let csrf_token = config.random.token()?;
+        redirect_url
+            .query_pairs_mut()
+            .append_pair("code", "replayed-code")
+            .append_pair("state", &csrf_token);
+
+        Ok(LoginContext {
+            url: redirect_url,
+            csrf_token,
+            state: (),
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        _code: String,
+        _login_state: Self::LoginState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        self.next_outcome(Operation::CodeExchange)?;
+        Ok((self.authenticated(), ()))
+    }
+
+    async fn exchange_refresh_token(
+        &self,
+        _refresh_token: String,
+        _session_state: Self::SessionState,
+    ) -> Result<(OAuth2Context, Self::SessionState), OAuth2Error> {
+        self.next_outcome(Operation::Refresh)?;
+        Ok((self.authenticated(), ()))
+    }
+
+    async fn exchange_refresh_token_for(
+        &self,
+        _refresh_token: String,
+        _session_state: Self::SessionState,
+        _scopes: &[String],
+        _audience: Option<&str>,
+    ) -> Result<Authentication, OAuth2Error> {
+        Err(OAuth2Error::Internal(
+            "Acquiring tokens is not supported when replaying".into(),
+        ))
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use super::*;
+    use crate::agent::{TraceEntry, TracedError};
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    /// A trace with a failed, and then a successful login.
+    fn trace() -> FlowTrace {
+        let outcome = |error| TraceEntry {
+            elapsed: 0,
+            event: TraceEvent::Outcome {
+                operation: Operation::Login,
+                error,
+            },
+        };
+        FlowTrace {
+            entries: vec![
+                outcome(Some(TracedError {
+                    kind: "Network".into(),
+                    code: None,
+                })),
+                outcome(None),
+            ],
+        }
+    }
+
+    #[wasm_bindgen_test]
+    async fn scoped_positions() {
+        SessionStorage::clear();
+
+        let first = ReplayClient::from_scoped_config(trace(), Some("/first"))
+            .await
+            .unwrap();
+        let second = ReplayClient::from_scoped_config(trace(), Some("/second"))
+            .await
+            .unwrap();
+
+        assert!(first.next_outcome(Operation::Login).is_err());
+        assert!(first.next_outcome(Operation::Login).is_ok());
+        // the position of the other application is kept apart
+        assert!(second.next_outcome(Operation::Login).is_err());
+
+        assert!(
+            SessionStorage::get::<Vec<(Operation, usize)>>("ctron/oauth2/first/replay").is_ok()
+        );
+        assert!(SessionStorage::get::<Vec<(Operation, usize)>>("ctron/oauth2/replay").is_err());
+    }
+}
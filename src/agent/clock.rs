@@ -0,0 +1,334 @@
+//! Sources of time, used for the expiration of tokens, and for scheduling refreshes.
+
+use gloo_timers::callback::Timeout;
+use js_sys::Date;
+use num_traits::ToPrimitive;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{Debug, Formatter},
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use web_time::Instant;
+
+/// A source of the current time, and of timers.
+///
+/// The default is [`BrowserTime`]. Providing a different source, like [`VirtualTime`], allows
+/// testing the refresh and expiration of sessions, without waiting.
+pub trait TimeSource: Debug {
+    /// The time of the wall clock, in milliseconds since the Unix epoch.
+    fn now(&self) -> f64;
+
+    /// The time of the monotonic clock, since an arbitrary point in time.
+    fn monotonic(&self) -> Duration;
+
+    /// Call the callback once the delay elapsed, unless the timer gets dropped before.
+    fn timeout(&self, delay: Duration, callback: Box<dyn FnOnce()>) -> Timer;
+}
+
+/// A timer, which gets cancelled when dropped.
+pub struct Timer {
+    _inner: Box<dyn Any>,
+}
+
+impl Timer {
+    /// Create a timer, from a value which cancels it when dropped.
+    pub fn new(inner: impl Any) -> Self {
+        Self {
+            _inner: Box::new(inner),
+        }
+    }
+}
+
+impl Debug for Timer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Timer")
+    }
+}
+
+/// The clocks and timers of the browser.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BrowserTime;
+
+thread_local! {
+    static ORIGIN: Instant = Instant::now();
+}
+
+impl TimeSource for BrowserTime {
+    fn now(&self) -> f64 {
+        Date::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        ORIGIN.with(|origin| origin.elapsed())
+    }
+
+    fn timeout(&self, delay: Duration, callback: Box<dyn FnOnce()>) -> Timer {
+        // while the API says millis is u32, internally it is i32
+        let millis = delay.as_millis().to_i32().unwrap_or(i32::MAX);
+        Timer::new(Timeout::new(millis as u32, callback))
+    }
+}
+
+/// A time, which only passes when advancing it.
+///
+/// Timers fire while advancing the time, in the order they are due. Clones share the same time.
+///
+/// ```rust
+/// use std::{cell::Cell, rc::Rc, time::Duration};
+/// use yew_oauth2::agent::{TimeSource, VirtualTime};
+///
+/// let time = VirtualTime::starting_at(0f64);
+/// let fired = Rc::new(Cell::new(false));
+/// let _timer = time.timeout(Duration::from_secs(60), Box::new({
+///     let fired = fired.clone();
+///     move || fired.set(true)
+/// }));
+///
+/// time.advance(Duration::from_secs(59));
+/// assert!(!fired.get());
+/// time.advance(Duration::from_secs(1));
+/// assert!(fired.get());
+/// assert_eq!(time.now(), 60_000f64);
+/// ```
+#[derive(Clone, Debug)]
+pub struct VirtualTime(Rc<RefCell<VirtualState>>);
+
+#[derive(Default)]
+struct VirtualState {
+    /// The wall clock time at the start, in milliseconds
+    start: f64,
+    /// The time passed since the start
+    elapsed: Duration,
+    /// The pending timers, by the time they are due, and the order they were created in
+    timers: BTreeMap<(Duration, u64), Box<dyn FnOnce()>>,
+    next: u64,
+}
+
+impl Debug for VirtualState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VirtualState")
+            .field("start", &self.start)
+            .field("elapsed", &self.elapsed)
+            .field("timers", &self.timers.len())
+            .finish()
+    }
+}
+
+impl VirtualTime {
+    /// Start at the current time of the wall clock.
+    pub fn new() -> Self {
+        Self::starting_at(Date::now())
+    }
+
+    /// Start at the time of the wall clock, in milliseconds since the Unix epoch.
+    pub fn starting_at(millis: f64) -> Self {
+        Self(Rc::new(RefCell::new(VirtualState {
+            start: millis,
+            ..Default::default()
+        })))
+    }
+
+    /// Let the time pass, firing the timers which are due.
+    pub fn advance(&self, duration: Duration) {
+        let target = self.0.borrow().elapsed + duration;
+        loop {
+            let callback = {
+                let mut state = self.0.borrow_mut();
+                match state.timers.first_key_value() {
+                    Some((&(due, _), _)) if due <= target => {
+                        state.elapsed = state.elapsed.max(due);
+                        state.timers.pop_first().map(|(_, callback)| callback)
+                    }
+                    _ => None,
+                }
+            };
+            match callback {
+                // the callback may create timers of its own
+                Some(callback) => callback(),
+                None => break,
+            }
+        }
+        self.0.borrow_mut().elapsed = target;
+    }
+}
+
+impl Default for VirtualTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cancels a timer of the virtual time, when dropped.
+struct VirtualTimer {
+    state: Weak<RefCell<VirtualState>>,
+    key: (Duration, u64),
+}
+
+impl Drop for VirtualTimer {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            // the timer may be dropped by its own callback
+            if let Ok(mut state) = state.try_borrow_mut() {
+                state.timers.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl TimeSource for VirtualTime {
+    fn now(&self) -> f64 {
+        let state = self.0.borrow();
+        state.start + state.elapsed.as_secs_f64() * 1000f64
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.0.borrow().elapsed
+    }
+
+    fn timeout(&self, delay: Duration, callback: Box<dyn FnOnce()>) -> Timer {
+        let mut state = self.0.borrow_mut();
+        let key = (state.elapsed + delay, state.next);
+        state.next += 1;
+        state.timers.insert(key, callback);
+        Timer::new(VirtualTimer {
+            state: Rc::downgrade(&self.0),
+            key,
+        })
+    }
+}
+
+/// A handle to a [`TimeSource`].
+///
+/// The time source applies to the whole application. It is installed when the agent gets
+/// configured, and used for all decisions about the expiration of tokens.
+#[derive(Clone, Debug)]
+pub struct Clock(Rc<dyn TimeSource>);
+
+thread_local! {
+    static BROWSER: Clock = Clock::new(BrowserTime);
+    static CURRENT: RefCell<Clock> = RefCell::new(Clock::default());
+}
+
+impl Clock {
+    pub fn new<T>(source: T) -> Self
+    where
+        T: TimeSource + 'static,
+    {
+        Self(Rc::new(source))
+    }
+
+    /// Use this clock for the application.
+    pub(crate) fn install(&self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.clone());
+    }
+
+    /// The clock of the application.
+    pub(crate) fn current() -> Self {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// The time of the wall clock, in milliseconds since the Unix epoch.
+    pub(crate) fn now(&self) -> f64 {
+        self.0.now()
+    }
+
+    /// The time of the wall clock, in seconds since the Unix epoch.
+    pub(crate) fn now_secs(&self) -> f64 {
+        self.0.now() / 1000f64
+    }
+
+    /// The time of the wall clock, as a date.
+    #[cfg(feature = "openid")]
+    pub(crate) fn utc(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_millis(self.0.now() as i64).unwrap_or_default()
+    }
+
+    pub(crate) fn monotonic(&self) -> Duration {
+        self.0.monotonic()
+    }
+
+    pub(crate) fn timeout(&self, delay: Duration, callback: impl FnOnce() + 'static) -> Timer {
+        self.0.timeout(delay, Box::new(callback))
+    }
+
+    /// Call the callback each time the period elapsed, until the timer gets dropped.
+    pub(crate) fn interval(&self, period: Duration, callback: impl Fn() + 'static) -> Timer {
+        let next = Rc::new(RefCell::new(None));
+        // a period of zero would never let the virtual time advance
+        let period = period.max(Duration::from_millis(1));
+        self.arm(period, Rc::new(callback), Rc::downgrade(&next));
+        Timer::new(next)
+    }
+
+    /// Start the next timer of an interval, unless the interval was dropped.
+    fn arm(&self, period: Duration, callback: Rc<dyn Fn()>, next: Weak<RefCell<Option<Timer>>>) {
+        let Some(slot) = next.upgrade() else {
+            return;
+        };
+        let clock = self.clone();
+        let timer = self.timeout(period, move || {
+            callback();
+            clock.arm(period, callback, next);
+        });
+        *slot.borrow_mut() = Some(timer);
+    }
+}
+
+impl Default for Clock {
+    /// The time of the Browser.
+    ///
+    /// All default instances share the same source, so that they compare equal.
+    fn default() -> Self {
+        BROWSER.with(Clone::clone)
+    }
+}
+
+impl PartialEq for Clock {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn default_is_shared() {
+        assert_eq!(Clock::default(), Clock::default());
+        assert_ne!(Clock::default(), Clock::new(BrowserTime));
+    }
+
+    #[test]
+    fn interval() {
+        let time = VirtualTime::starting_at(0f64);
+        let clock = Clock::new(time.clone());
+        let fired = Rc::new(Cell::new(0));
+        let timer = clock.interval(Duration::from_secs(10), {
+            let fired = fired.clone();
+            move || fired.set(fired.get() + 1)
+        });
+
+        time.advance(Duration::from_secs(9));
+        assert_eq!(fired.get(), 0);
+        time.advance(Duration::from_secs(1));
+        assert_eq!(fired.get(), 1);
+        time.advance(Duration::from_secs(25));
+        assert_eq!(fired.get(), 3);
+
+        drop(timer);
+        time.advance(Duration::from_secs(60));
+        assert_eq!(fired.get(), 3);
+    }
+
+    #[cfg(feature = "openid")]
+    #[test]
+    fn utc() {
+        let clock = Clock::new(VirtualTime::starting_at(1_700_000_000_500f64));
+        assert_eq!(clock.utc().timestamp_millis(), 1_700_000_000_500);
+    }
+}
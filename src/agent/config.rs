@@ -1,4 +1,8 @@
-use super::{LoginOptions, LogoutOptions};
+use super::{
+    BackendSession, Bootstrap, Clock, ExpiryPolicy, FlowRecorder, HiddenRefreshPolicy, KeepAlive,
+    LoginOptions, LogoutOptions, Metrics, Random, RefreshAhead, RefreshTokenPolicy, RetryPolicy,
+    SessionPersistence, UnauthorizedPolicy, UnknownStatePolicy, UrlCleanupPolicy,
+};
 use crate::agent::Client;
 use std::time::Duration;
 
@@ -13,6 +17,29 @@ pub struct AgentConfiguration<C: Client> {
 
     pub default_login_options: Option<LoginOptions>,
     pub default_logout_options: Option<LogoutOptions>,
+
+    pub unauthorized_policy: UnauthorizedPolicy,
+    pub unknown_state_policy: UnknownStatePolicy,
+    pub health_check: Option<Duration>,
+    pub retry_policy: RetryPolicy,
+    pub random: Random,
+    pub metrics: Option<Metrics>,
+    pub base_path: Option<String>,
+    pub backend_session: Option<BackendSession>,
+    pub keep_alive: Option<KeepAlive>,
+    pub recorder: Option<FlowRecorder>,
+    pub session_persistence: SessionPersistence,
+    pub expiry: ExpiryPolicy,
+    pub nonce_window: Duration,
+    pub refresh_tokens: RefreshTokenPolicy,
+    pub refresh_ahead: Option<RefreshAhead>,
+    pub hidden_refresh: HiddenRefreshPolicy,
+    pub refresh_jitter: Duration,
+    pub dom_events: bool,
+    pub bootstrap: Option<Bootstrap>,
+    pub clock: Clock,
+    pub url_cleanup: UrlCleanupPolicy,
+    pub public_path: Option<String>,
 }
 
 impl<C: Client> PartialEq for AgentConfiguration<C> {
@@ -21,6 +48,28 @@ impl<C: Client> PartialEq for AgentConfiguration<C> {
             && self.scopes == other.scopes
             && self.grace_period == other.grace_period
             && self.audience == other.audience
+            && self.unauthorized_policy == other.unauthorized_policy
+            && self.unknown_state_policy == other.unknown_state_policy
+            && self.health_check == other.health_check
+            && self.retry_policy == other.retry_policy
+            && self.random == other.random
+            && self.metrics == other.metrics
+            && self.base_path == other.base_path
+            && self.backend_session == other.backend_session
+            && self.keep_alive == other.keep_alive
+            && self.recorder == other.recorder
+            && self.session_persistence == other.session_persistence
+            && self.expiry == other.expiry
+            && self.nonce_window == other.nonce_window
+            && self.refresh_tokens == other.refresh_tokens
+            && self.refresh_ahead == other.refresh_ahead
+            && self.hidden_refresh == other.hidden_refresh
+            && self.refresh_jitter == other.refresh_jitter
+            && self.dom_events == other.dom_events
+            && self.bootstrap == other.bootstrap
+            && self.clock == other.clock
+            && self.url_cleanup == other.url_cleanup
+            && self.public_path == other.public_path
     }
 }
 
@@ -0,0 +1,42 @@
+//! Expiration of tokens, on the monotonic clock.
+
+use super::Clock;
+use std::time::Duration;
+
+/// The point in time a token expires.
+///
+/// The expiration of a token is reported as a timestamp of the wall clock (see
+/// [`crate::context::Authentication::expires`]). The wall clock may jump, e.g. when it gets
+/// synchronized, which would let the token appear to expire early, or late. So the timestamp is
+/// converted to a point in time of the monotonic clock, once the token was received, and all
+/// further decisions are based on that. The timestamp is only kept for display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Deadline {
+    expires: Duration,
+    lifetime: Duration,
+}
+
+impl Deadline {
+    /// Create a deadline from the timestamp of the expiration, in seconds.
+    ///
+    /// This must be called right after computing the timestamp, before the wall clock could jump.
+    pub fn from_expires(expires: u64) -> Self {
+        let clock = Clock::current();
+        let remaining = expires as f64 - clock.now_secs();
+        let lifetime = Duration::from_secs_f64(remaining.max(0f64));
+        Self {
+            expires: clock.monotonic() + lifetime,
+            lifetime,
+        }
+    }
+
+    /// The time remaining until the expiration.
+    pub fn remaining(&self) -> Duration {
+        self.expires.saturating_sub(Clock::current().monotonic())
+    }
+
+    /// The lifetime of the token, from when it was received until the expiration.
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+}
@@ -0,0 +1,86 @@
+//! Hooks for end-to-end tests.
+//!
+//! When the feature `e2e` is enabled, the agent registers an object `window.__yewOAuth2`, which
+//! allows browser automation (like Playwright or Selenium) to put the application into a state,
+//! without interacting with the issuer:
+//!
+//! * `injectSession({ accessToken, refreshToken, expires })`: Switch to an authenticated session,
+//!   using the provided tokens. `refreshToken` and `expires` (in seconds since the epoch) are
+//!   optional.
+//! * `forceExpire()`: Expire the current session.
+//!
+//! **NOTE**: This allows anyone able to run scripts in the page to inject a session. Never enable
+//! this feature in production builds.
+
+use super::{
+    js::{hooks, HOOKS_NAME},
+    Client, Msg,
+};
+use crate::context::Authentication;
+use serde::Deserialize;
+use tokio::sync::mpsc::Sender;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InjectedSession {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires: Option<u64>,
+}
+
+impl From<InjectedSession> for Authentication {
+    fn from(session: InjectedSession) -> Self {
+        Self {
+            access_token: session.access_token,
+            refresh_token: session.refresh_token,
+            expires: session.expires,
+            #[cfg(feature = "openid")]
+            claims: None,
+            #[cfg(feature = "openid")]
+            user_info: None,
+        }
+    }
+}
+
+/// Register the hooks on the window, replacing hooks of a previous agent.
+pub(crate) fn install<C: Client>(tx: Sender<Msg<C>>) {
+    let hooks = hooks();
+
+    let inject_tx = tx.clone();
+    let inject = Closure::<dyn Fn(JsValue)>::new(move |session: JsValue| {
+        let session = js_sys::JSON::stringify(&session)
+            .ok()
+            .and_then(|session| session.as_string())
+            .and_then(|session| serde_json::from_str::<InjectedSession>(&session).ok());
+        match session {
+            Some(session) => {
+                let _ = inject_tx.try_send(Msg::InjectSession(session.into()));
+            }
+            None => log::warn!("Invalid session to inject"),
+        }
+    });
+
+    let expire = Closure::<dyn Fn()>::new(move || {
+        let _ = tx.try_send(Msg::ForceExpire);
+    });
+
+    let _ = js_sys::Reflect::set(
+        &hooks,
+        &"injectSession".into(),
+        inject.as_ref().unchecked_ref(),
+    );
+    let _ = js_sys::Reflect::set(
+        &hooks,
+        &"forceExpire".into(),
+        expire.as_ref().unchecked_ref(),
+    );
+
+    // the hooks live as long as the page
+    inject.forget();
+    expire.forget();
+
+    log::warn!("Registered end-to-end test hooks as window.{HOOKS_NAME}");
+}
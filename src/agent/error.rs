@@ -1,19 +1,70 @@
-use crate::context::OAuth2Context;
+use crate::config::ConfigError;
 use core::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+/// The underlying cause of an [`OAuth2Error`].
+pub type ErrorSource = Arc<dyn std::error::Error + Send + Sync>;
 
 /// An error with the OAuth2 agent
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum OAuth2Error {
     /// Not initialized
     NotInitialized,
-    /// Configuration error
-    Configuration(String),
+    /// Configuration error, like a malformed URL
+    Configuration {
+        /// What is wrong with the configuration
+        message: String,
+        /// The underlying cause
+        source: Option<ErrorSource>,
+    },
+    /// The configuration is invalid, found before starting the agent
+    InvalidConfiguration(Vec<ConfigError>),
+    /// Failed to discover the metadata of the issuer
+    Discovery(ErrorSource),
+    /// Failed to communicate with the issuer
+    Network(ErrorSource),
+    /// A request was likely blocked by the browser, as the endpoint doesn't allow cross-origin
+    /// requests (CORS) of the application
+    ///
+    /// Browsers don't report why a request failed. A request to a different origin, failing
+    /// while the browser is online, is reported as this instead of [`Self::Network`].
+    Cors {
+        /// The URL of the request
+        endpoint: String,
+        /// The origin of the application, which the endpoint must allow
+        origin: String,
+        /// The underlying cause
+        source: ErrorSource,
+    },
+    /// The issuer responded with an error
+    Server {
+        /// The error code, e.g. `access_denied`
+        error: String,
+        /// A human readable description of the error
+        description: Option<String>,
+        /// The URI of a page describing the error
+        uri: Option<String>,
+    },
+    /// A response of the issuer is invalid, like an ID token failing verification
+    Validation {
+        /// What is wrong with the response
+        message: String,
+        /// The underlying cause
+        source: Option<ErrorSource>,
+    },
+    /// The authentication context class of the ID token doesn't satisfy the requested ones
+    AcrNotSatisfied {
+        /// The requested classes
+        requested: Vec<String>,
+        /// The class of the ID token
+        received: Option<String>,
+    },
+    /// The state of the authorization response doesn't match the pending login
+    UnknownState,
+    /// The issuer is temporarily unavailable, with an optional description
+    TemporarilyUnavailable(Option<String>),
     /// Failed to start login
     StartLogin(String),
-    /// Failed to handle login result
-    LoginResult(String),
-    /// Failed to handle token refresh
-    Refresh(String),
     /// Failing storing information
     Storage(String),
     /// Internal error
@@ -24,26 +75,238 @@ impl Display for OAuth2Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NotInitialized => f.write_str("not initialized"),
-            Self::Configuration(err) => write!(f, "configuration error: {err}"),
+            Self::Configuration { message, .. } => write!(f, "configuration error: {message}"),
+            Self::InvalidConfiguration(errors) => {
+                f.write_str("invalid configuration")?;
+                for (i, err) in errors.iter().enumerate() {
+                    f.write_str(if i == 0 { ": " } else { ", " })?;
+                    write!(f, "{err}")?;
+                }
+                Ok(())
+            }
+            Self::Discovery(err) => write!(f, "discovery error: {err}"),
+            Self::Network(err) => write!(f, "network error: {err}"),
+            Self::Cors {
+                endpoint, origin, ..
+            } => write!(
+                f,
+                "request to {endpoint} failed, it may be blocked by CORS: the endpoint must allow \
+                requests from the origin {origin}"
+            ),
+            Self::Server {
+                error,
+                description: None,
+                ..
+            } => write!(f, "server error: {error}"),
+            Self::Server {
+                error,
+                description: Some(description),
+                ..
+            } => write!(f, "server error: {error}: {description}"),
+            Self::Validation { message, .. } => write!(f, "validation error: {message}"),
+            Self::AcrNotSatisfied {
+                requested,
+                received,
+            } => write!(
+                f,
+                "requested authentication context not satisfied: requested: {}, received: {}",
+                requested.join(" "),
+                received.as_deref().unwrap_or("<none>")
+            ),
+            Self::UnknownState => f.write_str("login result: unknown state"),
+            Self::TemporarilyUnavailable(None) => f.write_str("issuer temporarily unavailable"),
+            Self::TemporarilyUnavailable(Some(description)) => {
+                write!(f, "issuer temporarily unavailable: {description}")
+            }
             Self::StartLogin(err) => write!(f, "start login error: {err}"),
-            Self::LoginResult(err) => write!(f, "login result: {err}"),
-            Self::Refresh(err) => write!(f, "refresh error: {err}"),
             Self::Storage(err) => write!(f, "storage error: {err}"),
             Self::Internal(err) => write!(f, "internal error: {err}"),
         }
     }
 }
 
-impl std::error::Error for OAuth2Error {}
+impl std::error::Error for OAuth2Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Configuration { source, .. } | Self::Validation { source, .. } => source
+                .as_deref()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+            Self::Discovery(err) | Self::Network(err) | Self::Cors { source: err, .. } => {
+                Some(err.as_ref())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Errors are equal if they are of the same kind, with the same details. Sources are only equal
+/// if they are the same instance.
+impl PartialEq for OAuth2Error {
+    fn eq(&self, other: &Self) -> bool {
+        fn same_source(a: &Option<ErrorSource>, b: &Option<ErrorSource>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (None, None) => true,
+                _ => false,
+            }
+        }
 
-impl From<OAuth2Error> for OAuth2Context {
-    fn from(err: OAuth2Error) -> Self {
-        OAuth2Context::Failed(err.to_string())
+        match (self, other) {
+            (Self::NotInitialized, Self::NotInitialized) => true,
+            (
+                Self::Configuration { message, source },
+                Self::Configuration {
+                    message: other_message,
+                    source: other_source,
+                },
+            )
+            | (
+                Self::Validation { message, source },
+                Self::Validation {
+                    message: other_message,
+                    source: other_source,
+                },
+            ) => message == other_message && same_source(source, other_source),
+            (Self::Discovery(a), Self::Discovery(b)) | (Self::Network(a), Self::Network(b)) => {
+                Arc::ptr_eq(a, b)
+            }
+            (
+                Self::Cors {
+                    endpoint,
+                    origin,
+                    source,
+                },
+                Self::Cors {
+                    endpoint: other_endpoint,
+                    origin: other_origin,
+                    source: other_source,
+                },
+            ) => {
+                endpoint == other_endpoint
+                    && origin == other_origin
+                    && Arc::ptr_eq(source, other_source)
+            }
+            (
+                Self::Server {
+                    error,
+                    description,
+                    uri,
+                },
+                Self::Server {
+                    error: other_error,
+                    description: other_description,
+                    uri: other_uri,
+                },
+            ) => error == other_error && description == other_description && uri == other_uri,
+            (
+                Self::AcrNotSatisfied {
+                    requested,
+                    received,
+                },
+                Self::AcrNotSatisfied {
+                    requested: other_requested,
+                    received: other_received,
+                },
+            ) => requested == other_requested && received == other_received,
+            (Self::InvalidConfiguration(a), Self::InvalidConfiguration(b)) => a == b,
+            (Self::UnknownState, Self::UnknownState) => true,
+            (Self::TemporarilyUnavailable(a), Self::TemporarilyUnavailable(b)) => a == b,
+            (Self::StartLogin(a), Self::StartLogin(b))
+            | (Self::Storage(a), Self::Storage(b))
+            | (Self::Internal(a), Self::Internal(b)) => a == b,
+            _ => false,
+        }
     }
 }
 
+impl Eq for OAuth2Error {}
+
 impl OAuth2Error {
     pub(crate) fn storage_key_empty(key: impl Display) -> Self {
         Self::Storage(format!("Missing value for key: {key}"))
     }
+
+    pub(crate) fn configuration<E>(message: impl Into<String>, source: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Self::Configuration {
+            message: message.into(),
+            source: Some(Arc::new(source)),
+        }
+    }
+
+    /// Check if the error is transient, so that retrying the operation later may succeed.
+    ///
+    /// This is the case for failing to reach the issuer, and the issuer being temporarily
+    /// unavailable. As a request blocked by CORS can't be told apart from an unreachable issuer
+    /// for sure, it is considered transient too.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Network(_) | Self::Cors { .. } | Self::TemporarilyUnavailable(_)
+        )
+    }
+
+    /// Create an error for a request which failed to reach the issuer.
+    pub(crate) fn network<E>(err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        match blocked_by_cors(&err) {
+            Some((endpoint, origin)) => Self::Cors {
+                endpoint,
+                origin,
+                source: Arc::new(err),
+            },
+            None => Self::Network(Arc::new(err)),
+        }
+    }
+
+    /// Create an error for a failed discovery.
+    #[cfg(feature = "openid")]
+    pub(crate) fn discovery<E>(err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        match blocked_by_cors(&err) {
+            Some((endpoint, origin)) => Self::Cors {
+                endpoint,
+                origin,
+                source: Arc::new(err),
+            },
+            None => Self::Discovery(Arc::new(err)),
+        }
+    }
+
+    pub(crate) fn validation(message: impl Into<String>) -> Self {
+        Self::Validation {
+            message: message.into(),
+            source: None,
+        }
+    }
+}
+
+/// Check if a request likely failed because of CORS, returning its endpoint and the origin.
+///
+/// This looks for a request error of `reqwest`, to a different origin than the one of the
+/// application, while the browser is online.
+fn blocked_by_cors(err: &(dyn std::error::Error + 'static)) -> Option<(String, String)> {
+    let mut current = Some(err);
+    let request = loop {
+        let err = current?;
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            break err;
+        }
+        current = err.source();
+    };
+
+    let endpoint = request.url().filter(|_| request.is_request())?;
+    let window = gloo_utils::window();
+    let origin = window.location().origin().ok()?;
+    if endpoint.origin().ascii_serialization() == origin || !window.navigator().on_line() {
+        return None;
+    }
+
+    Some((endpoint.to_string(), origin))
 }
@@ -0,0 +1,178 @@
+//! Events of the lifecycle of the session.
+
+use super::{AgentCallback, OAuth2Error};
+use crate::context::{Authentication, LogoutReason, OAuth2Context, Operation, Reason};
+use gloo_utils::window;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fmt::Formatter,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use web_sys::{CustomEvent, CustomEventInit};
+
+/// An event of the lifecycle of the session.
+///
+/// Unlike the [`OAuth2Context`], which is the current state, events report what happened. So that
+/// application code outside of components, like analytics or a WebSocket connection, can react to
+/// it. Subscribe using [`super::OAuth2Operations::subscribe`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(feature = "openid"), derive(Eq))]
+#[non_exhaustive]
+pub enum AgentEvent {
+    /// A new session was established, e.g. by logging in, or restoring a persisted session.
+    Authenticated(Authentication),
+    /// The tokens of the session were refreshed.
+    TokenRefreshed(Authentication),
+    /// Refreshing the session failed.
+    ///
+    /// The agent may still retry, see [`super::RetryPolicy`]. If the session is lost, this is
+    /// followed by another event.
+    RefreshFailed(OAuth2Error),
+    /// The session expired.
+    Expired,
+    /// The session was logged out.
+    LoggedOut(LogoutReason),
+    /// The login completed, the code returned by the issuer was exchanged for tokens.
+    ///
+    /// This follows the [`Self::Authenticated`] event of the new session.
+    LoginCompleted(Authentication),
+    /// The login failed, returning from the issuer.
+    ///
+    /// The agent may still start over, see [`super::RetryPolicy`] and
+    /// [`super::UnknownStatePolicy`].
+    LoginFailed(OAuth2Error),
+    /// Configuring the agent failed, e.g. because the configuration is invalid, or the issuer
+    /// could not be discovered.
+    ConfigurationError(OAuth2Error),
+}
+
+impl AgentEvent {
+    /// The event of a change of the state, if any.
+    pub(crate) fn from_change(current: &OAuth2Context, new: &OAuth2Context) -> Option<Self> {
+        match (current, new) {
+            (OAuth2Context::Authenticated(current), OAuth2Context::Authenticated(new)) => {
+                (current.access_token != new.access_token)
+                    .then(|| Self::TokenRefreshed(new.clone()))
+            }
+            (_, OAuth2Context::Authenticated(new)) => Some(Self::Authenticated(new.clone())),
+            (
+                _,
+                OAuth2Context::NotAuthenticated {
+                    reason: Reason::Expired,
+                },
+            ) => Some(Self::Expired),
+            (
+                _,
+                OAuth2Context::NotAuthenticated {
+                    reason: Reason::Logout(reason),
+                },
+            ) => Some(Self::LoggedOut(*reason)),
+            (
+                _,
+                OAuth2Context::Failed {
+                    operation: Operation::Discovery,
+                    error,
+                },
+            ) if current != new => Some(Self::ConfigurationError(error.clone())),
+            _ => None,
+        }
+    }
+
+    /// Dispatch the event as DOM event on the window, if it has one.
+    ///
+    /// The events are `CustomEvent`s, with minimal details, not including any token:
+    ///
+    /// * `yew-oauth2:authenticated`: A new session was established, with `{ expires }`.
+    /// * `yew-oauth2:refreshed`: The session was refreshed, with `{ expires }`.
+    /// * `yew-oauth2:logout`: The session ended, with `{ reason }`, which is `expired`, or the
+    ///   [`LogoutReason`].
+    ///
+    /// The expiration is in seconds since the epoch, or `null`.
+    pub fn dispatch(&self) {
+        let (name, detail) = match self {
+            Self::Authenticated(auth) => ("authenticated", json!({ "expires": auth.expires })),
+            Self::TokenRefreshed(auth) => ("refreshed", json!({ "expires": auth.expires })),
+            Self::Expired => ("logout", json!({ "reason": "expired" })),
+            Self::LoggedOut(reason) => ("logout", json!({ "reason": reason })),
+            Self::RefreshFailed(_)
+            | Self::ConfigurationError(_)
+            | Self::LoginCompleted(_)
+            | Self::LoginFailed(_) => return,
+        };
+
+        let init = CustomEventInit::new();
+        if let Ok(detail) = js_sys::JSON::parse(&detail.to_string()) {
+            init.set_detail(&detail);
+        }
+        let result = CustomEvent::new_with_event_init_dict(&format!("yew-oauth2:{name}"), &init)
+            .and_then(|event| window().dispatch_event(&event));
+        if let Err(err) = result {
+            log::warn!("Failed to dispatch DOM event: {err:?}");
+        }
+    }
+}
+
+/// A subscription to the events of the agent.
+///
+/// Dropping it ends the subscription.
+#[must_use = "dropping the subscription ends it"]
+pub struct EventSubscription(Option<Box<dyn FnOnce()>>);
+
+impl EventSubscription {
+    pub(crate) fn new<F>(unsubscribe: F) -> Self
+    where
+        F: FnOnce() + 'static,
+    {
+        Self(Some(Box::new(unsubscribe)))
+    }
+
+    /// Keep the subscription for the lifetime of the agent.
+    pub fn forget(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for EventSubscription {
+    fn drop(&mut self) {
+        if let Some(unsubscribe) = self.0.take() {
+            unsubscribe();
+        }
+    }
+}
+
+impl std::fmt::Debug for EventSubscription {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSubscription")
+    }
+}
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The subscribers of the agent.
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    callbacks: HashMap<usize, AgentCallback<AgentEvent>>,
+}
+
+impl Subscribers {
+    /// A new ID, for a subscriber.
+    pub fn next_id() -> usize {
+        NEXT_ID.fetch_add(1, Ordering::AcqRel)
+    }
+
+    pub fn insert(&mut self, id: usize, callback: AgentCallback<AgentEvent>) {
+        self.callbacks.insert(id, callback);
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.callbacks.remove(&id);
+    }
+
+    pub fn emit(&self, event: AgentEvent) {
+        log::debug!("Event: {event:?}");
+        for callback in self.callbacks.values() {
+            callback.emit(event.clone());
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! A handle to the agent, independent of its client.
+
+use super::{
+    AccessToken, Agent, AgentCallback, AgentEvent, Client, ContextStream, Error, EventSubscription,
+    LoginFunnel, LoginOptions, LogoutOptions, OAuth2Operations, TokenRequest,
+};
+use async_trait::async_trait;
+use reqwest::Url;
+use std::{cell::RefCell, rc::Rc};
+
+/// The operations of [`OAuth2Operations`], without the configuration.
+#[async_trait(?Send)]
+trait Operations {
+    fn start_login(&self) -> Result<(), Error>;
+    fn start_login_opts(&self, options: LoginOptions) -> Result<(), Error>;
+    fn logout(&self) -> Result<(), Error>;
+    fn logout_opts(&self, options: LogoutOptions) -> Result<(), Error>;
+    fn report_unauthorized(&self) -> Result<(), Error>;
+    async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error>;
+    async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error>;
+    async fn login_funnel(&self) -> Result<LoginFunnel, Error>;
+    fn retry(&self) -> Result<(), Error>;
+    fn refresh(&self) -> Result<(), Error>;
+    fn subscribe(&self, callback: AgentCallback<AgentEvent>) -> Result<EventSubscription, Error>;
+    fn stream(&self) -> ContextStream;
+}
+
+#[async_trait(?Send)]
+impl<C: Client> Operations for Agent<C> {
+    fn start_login(&self) -> Result<(), Error> {
+        OAuth2Operations::start_login(self)
+    }
+
+    fn start_login_opts(&self, options: LoginOptions) -> Result<(), Error> {
+        OAuth2Operations::start_login_opts(self, options)
+    }
+
+    fn logout(&self) -> Result<(), Error> {
+        OAuth2Operations::logout(self)
+    }
+
+    fn logout_opts(&self, options: LogoutOptions) -> Result<(), Error> {
+        OAuth2Operations::logout_opts(self, options)
+    }
+
+    fn report_unauthorized(&self) -> Result<(), Error> {
+        OAuth2Operations::report_unauthorized(self)
+    }
+
+    async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error> {
+        OAuth2Operations::account_url(self, return_url).await
+    }
+
+    async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error> {
+        OAuth2Operations::acquire_token(self, request).await
+    }
+
+    async fn login_funnel(&self) -> Result<LoginFunnel, Error> {
+        OAuth2Operations::login_funnel(self).await
+    }
+
+    fn retry(&self) -> Result<(), Error> {
+        OAuth2Operations::retry(self)
+    }
+
+    fn refresh(&self) -> Result<(), Error> {
+        OAuth2Operations::refresh(self)
+    }
+
+    fn subscribe(&self, callback: AgentCallback<AgentEvent>) -> Result<EventSubscription, Error> {
+        OAuth2Operations::subscribe(self, callback)
+    }
+
+    fn stream(&self) -> ContextStream {
+        OAuth2Operations::stream(self)
+    }
+}
+
+thread_local! {
+    static GLOBAL: RefCell<Option<OAuth2Agent>> = const { RefCell::new(None) };
+}
+
+/// A handle to the agent, independent of its client.
+///
+/// Unlike [`Agent`], this doesn't require knowing the type of the client. It can also be obtained
+/// outside of the component tree, using [`Self::global`]. So that service layers, or interceptors
+/// of HTTP clients, can use the session without being components.
+///
+/// ```rust
+/// use yew_oauth2::agent::{Error, OAuth2Agent};
+///
+/// async fn authorization_header() -> Result<String, Error> {
+///     let agent = OAuth2Agent::global().ok_or(Error::NoAgent)?;
+///     let token = agent.access_token().await?;
+///     Ok(format!("Bearer {}", token.access_token))
+/// }
+/// ```
+#[derive(Clone)]
+pub struct OAuth2Agent(Rc<dyn Operations>);
+
+impl OAuth2Agent {
+    pub fn new<C: Client>(agent: Agent<C>) -> Self {
+        Self(Rc::new(agent))
+    }
+
+    /// Get the global handle, if there is one.
+    ///
+    /// The [`crate::components::context::OAuth2`] component registers its agent, while it
+    /// exists. With multiple components, this is the one created last.
+    pub fn global() -> Option<Self> {
+        GLOBAL.with(|global| global.borrow().clone())
+    }
+
+    /// Register the handle as the global one, replacing the current one.
+    pub fn set_global(&self) {
+        GLOBAL.with(|global| *global.borrow_mut() = Some(self.clone()));
+    }
+
+    /// Remove the handle as the global one, if it is.
+    pub fn clear_global(&self) {
+        GLOBAL.with(|global| {
+            let mut global = global.borrow_mut();
+            if global.as_ref().is_some_and(|current| current == self) {
+                *global = None;
+            }
+        });
+    }
+
+    /// See [`OAuth2Operations::start_login`]
+    pub fn start_login(&self) -> Result<(), Error> {
+        self.0.start_login()
+    }
+
+    /// See [`OAuth2Operations::start_login_opts`]
+    pub fn start_login_opts(&self, options: LoginOptions) -> Result<(), Error> {
+        self.0.start_login_opts(options)
+    }
+
+    /// See [`OAuth2Operations::logout`]
+    pub fn logout(&self) -> Result<(), Error> {
+        self.0.logout()
+    }
+
+    /// See [`OAuth2Operations::logout_opts`]
+    pub fn logout_opts(&self, options: LogoutOptions) -> Result<(), Error> {
+        self.0.logout_opts(options)
+    }
+
+    /// See [`OAuth2Operations::report_unauthorized`]
+    pub fn report_unauthorized(&self) -> Result<(), Error> {
+        self.0.report_unauthorized()
+    }
+
+    /// See [`OAuth2Operations::account_url`]
+    pub async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error> {
+        self.0.account_url(return_url).await
+    }
+
+    /// Get the access token of the session, refreshing it first if required.
+    ///
+    /// This is [`Self::acquire_token`] with the scopes and the audience of the session.
+    pub async fn access_token(&self) -> Result<AccessToken, Error> {
+        self.0.acquire_token(TokenRequest::new()).await
+    }
+
+    /// See [`OAuth2Operations::acquire_token`]
+    pub async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error> {
+        self.0.acquire_token(request).await
+    }
+
+    /// See [`OAuth2Operations::login_funnel`]
+    pub async fn login_funnel(&self) -> Result<LoginFunnel, Error> {
+        self.0.login_funnel().await
+    }
+
+    /// See [`OAuth2Operations::retry`]
+    pub fn retry(&self) -> Result<(), Error> {
+        self.0.retry()
+    }
+
+    /// Refresh the session now, see [`OAuth2Operations::refresh`]
+    pub fn refresh_now(&self) -> Result<(), Error> {
+        self.0.refresh()
+    }
+
+    /// Get a new token, after the token was rejected by a server as unauthorized.
+    ///
+    /// This refreshes the session, and acquires the token again. Returns `None` if that didn't
+    /// result in a different token, so that retrying the request won't succeed.
+    pub async fn replace_token(
+        &self,
+        rejected: &AccessToken,
+        request: TokenRequest,
+    ) -> Result<Option<AccessToken>, Error> {
+        self.refresh_now()?;
+        let token = self.acquire_token(request).await?;
+        Ok((token.access_token != rejected.access_token).then_some(token))
+    }
+
+    /// See [`OAuth2Operations::subscribe`]
+    pub fn subscribe(
+        &self,
+        callback: impl Into<AgentCallback<AgentEvent>>,
+    ) -> Result<EventSubscription, Error> {
+        self.0.subscribe(callback.into())
+    }
+
+    /// See [`OAuth2Operations::stream`]
+    pub fn stream(&self) -> ContextStream {
+        self.0.stream()
+    }
+}
+
+impl PartialEq for OAuth2Agent {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for OAuth2Agent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OAuth2Agent")
+    }
+}
@@ -0,0 +1,99 @@
+//! An API for scripts of the page, which are not part of the application.
+//!
+//! When the feature `js-api` is enabled, the agent registers functions on the object
+//! `window.__yewOAuth2`, which allow other scripts embedded in the page (like chat widgets, or
+//! legacy scripts) to use the session, instead of logging in on their own:
+//!
+//! * `getAccessToken(request?)`: A promise of the access token, refreshing the session first if
+//!   required. The optional request (`{ scopes, audience }`) acquires a token of different
+//!   scopes, or a different audience, see [`super::TokenRequest`]. The promise is rejected if
+//!   there is no session.
+//!
+//! The functions use the [global agent](super::OAuth2Agent::global).
+//!
+//! **NOTE**: This hands the access token to any script running in the page.
+
+#[cfg(feature = "js-api")]
+use super::{Error, OAuth2Agent, TokenRequest};
+use gloo_utils::window;
+#[cfg(feature = "js-api")]
+use serde::Deserialize;
+use wasm_bindgen::JsCast;
+#[cfg(feature = "js-api")]
+use wasm_bindgen::{closure::Closure, JsValue};
+#[cfg(feature = "js-api")]
+use wasm_bindgen_futures::future_to_promise;
+
+/// The name of the object on the window, holding the functions.
+///
+/// The object is shared with the hooks for end-to-end tests.
+pub(crate) const HOOKS_NAME: &str = "__yewOAuth2";
+
+/// The object holding the functions, creating it if required.
+pub(crate) fn hooks() -> js_sys::Object {
+    js_sys::Reflect::get(&window(), &HOOKS_NAME.into())
+        .ok()
+        .and_then(|hooks| hooks.dyn_into::<js_sys::Object>().ok())
+        .unwrap_or_else(|| {
+            let hooks = js_sys::Object::new();
+            let _ = js_sys::Reflect::set(&window(), &HOOKS_NAME.into(), &hooks);
+            hooks
+        })
+}
+
+#[cfg(feature = "js-api")]
+#[derive(Default, Deserialize)]
+struct JsTokenRequest {
+    #[serde(default)]
+    scopes: Vec<String>,
+    #[serde(default)]
+    audience: Option<String>,
+}
+
+#[cfg(feature = "js-api")]
+impl From<JsTokenRequest> for TokenRequest {
+    fn from(request: JsTokenRequest) -> Self {
+        let token_request = TokenRequest::new().with_scopes(request.scopes);
+        match request.audience {
+            Some(audience) => token_request.with_audience(audience),
+            None => token_request,
+        }
+    }
+}
+
+/// Register the functions on the window.
+#[cfg(feature = "js-api")]
+pub(crate) fn install() {
+    let get_access_token =
+        Closure::<dyn Fn(JsValue) -> js_sys::Promise>::new(move |request: JsValue| {
+            let request = if request.is_undefined() || request.is_null() {
+                Ok(JsTokenRequest::default())
+            } else {
+                js_sys::JSON::stringify(&request)
+                    .ok()
+                    .and_then(|request| request.as_string())
+                    .and_then(|request| serde_json::from_str::<JsTokenRequest>(&request).ok())
+                    .ok_or_else(|| JsValue::from_str("invalid token request"))
+            };
+
+            future_to_promise(async move {
+                let request = request?;
+                let agent = OAuth2Agent::global()
+                    .ok_or_else(|| JsValue::from_str(&Error::NoAgent.to_string()))?;
+                agent
+                    .acquire_token(request.into())
+                    .await
+                    .map(|token| token.access_token.into())
+                    .map_err(|err| JsValue::from_str(&err.to_string()))
+            })
+        });
+
+    let _ = js_sys::Reflect::set(
+        &hooks(),
+        &"getAccessToken".into(),
+        get_access_token.as_ref().unchecked_ref(),
+    );
+
+    // the functions live as long as the page
+    get_access_token.forget();
+}
@@ -0,0 +1,130 @@
+//! Metrics of the login funnel.
+
+use super::{state::AppScope, Clock};
+use gloo_storage::{LocalStorage, SessionStorage, Storage};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, rc::Rc, time::Duration};
+
+const STORAGE_KEY_LOGIN_FUNNEL: &str = "loginFunnel";
+const STORAGE_KEY_LOGIN_STARTED: &str = "loginStarted";
+
+/// A stage of the login funnel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunnelStage {
+    /// The login was started.
+    Started,
+    /// The browser was redirected to the issuer.
+    Redirected,
+    /// The browser returned from the issuer, with a code.
+    Returned,
+    /// The code was exchanged with the issuer.
+    Exchanged,
+    /// Exchanging the code with the issuer failed.
+    ExchangeFailed,
+    /// The session was authenticated, using the exchanged tokens.
+    Authenticated,
+}
+
+/// Counters of the login funnel.
+///
+/// The counters are persisted in the local storage of the browser, and can be retrieved using
+/// [`super::OAuth2Operations::login_funnel`]. They are only recorded when a [`Metrics`] reporter
+/// is configured.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginFunnel {
+    /// Number of logins started
+    pub started: u64,
+    /// Number of redirects to the issuer
+    pub redirected: u64,
+    /// Number of returns from the issuer
+    pub returned: u64,
+    /// Number of code exchanges
+    pub exchanged: u64,
+    /// Number of failed code exchanges
+    #[serde(default)]
+    pub exchange_failed: u64,
+    /// Number of authenticated sessions
+    pub authenticated: u64,
+    /// The time it took the last login, from starting to being authenticated, in milliseconds
+    pub last_duration: Option<u64>,
+}
+
+/// Reporting login funnel events, e.g. to a product analytics service.
+pub trait MetricsReporter: Debug {
+    /// A stage of the login funnel was reached.
+    ///
+    /// The `elapsed` time is the time since starting the login, if known.
+    fn login_funnel(&self, stage: FunnelStage, elapsed: Option<Duration>);
+}
+
+/// A handle to a [`MetricsReporter`].
+#[derive(Clone, Debug)]
+pub struct Metrics(Rc<dyn MetricsReporter>);
+
+impl Metrics {
+    pub fn new<R>(reporter: R) -> Self
+    where
+        R: MetricsReporter + 'static,
+    {
+        Self(Rc::new(reporter))
+    }
+}
+
+impl PartialEq for Metrics {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Load the persisted counters.
+pub(crate) fn login_funnel(scope: &AppScope) -> LoginFunnel {
+    LocalStorage::get(scope.key(STORAGE_KEY_LOGIN_FUNNEL)).unwrap_or_default()
+}
+
+/// Record reaching a stage of the login funnel.
+///
+/// As the login navigates away, the time the login was started is kept in the session storage.
+pub(crate) fn record(stage: FunnelStage, scope: &AppScope, reporter: Option<&Metrics>) {
+    // without a reporter, the application never opted in to storing anything
+    let Some(reporter) = reporter else {
+        return;
+    };
+
+    let now = Clock::current().now();
+    let mut funnel = login_funnel(scope);
+    let started = scope.key(STORAGE_KEY_LOGIN_STARTED);
+
+    let elapsed = match stage {
+        FunnelStage::Started => {
+            let _ = SessionStorage::set(&started, now);
+            None
+        }
+        _ => SessionStorage::get::<f64>(&started)
+            .ok()
+            .and_then(|started| (now - started).max(0f64).to_u64())
+            .map(Duration::from_millis),
+    };
+
+    match stage {
+        FunnelStage::Started => funnel.started += 1,
+        FunnelStage::Redirected => funnel.redirected += 1,
+        FunnelStage::Returned => funnel.returned += 1,
+        FunnelStage::Exchanged => funnel.exchanged += 1,
+        FunnelStage::ExchangeFailed => {
+            funnel.exchange_failed += 1;
+            SessionStorage::delete(&started);
+        }
+        FunnelStage::Authenticated => {
+            funnel.authenticated += 1;
+            funnel.last_duration = elapsed.and_then(|elapsed| elapsed.as_millis().to_u64());
+            SessionStorage::delete(&started);
+        }
+    }
+
+    if let Err(err) = LocalStorage::set(scope.key(STORAGE_KEY_LOGIN_FUNNEL), &funnel) {
+        log::warn!("Failed to store login funnel: {err}");
+    }
+
+    reporter.0.login_funnel(stage, elapsed);
+}
@@ -1,32 +1,72 @@
 //! The agent, working in the background to manage the session and refresh tokens.
 pub mod client;
 
+mod backend;
+mod bootstrap;
+mod cache;
+mod callback;
+mod clock;
 mod config;
+mod deadline;
+#[cfg(feature = "e2e")]
+mod e2e;
 mod error;
+mod events;
+mod handle;
+#[cfg(any(feature = "e2e", feature = "js-api"))]
+mod js;
+mod metrics;
 mod ops;
-mod state;
-
+mod policy;
+mod provider;
+mod random;
+mod recorder;
+pub(crate) mod state;
+mod stream;
+
+pub use backend::{BackendSession, KeepAlive};
+pub use bootstrap::Bootstrap;
+pub use cache::{AccessToken, TokenRequest};
+pub use callback::AgentCallback;
 pub use client::*;
+pub use clock::{BrowserTime, Clock, TimeSource, Timer, VirtualTime};
 pub use error::*;
+pub use events::{AgentEvent, EventSubscription};
+pub use handle::OAuth2Agent;
+pub use metrics::{FunnelStage, LoginFunnel, Metrics, MetricsReporter};
 pub use ops::*;
+pub use policy::*;
+pub use provider::AccessTokenProvider;
+pub use random::*;
+pub use recorder::*;
 pub use state::LoginState;
+pub use stream::ContextStream;
 
 pub(crate) use config::*;
 
-use crate::context::{Authentication, OAuth2Context, Reason};
-use gloo_storage::{SessionStorage, Storage};
-use gloo_timers::callback::Timeout;
-use gloo_utils::{history, window};
-use js_sys::Date;
+use crate::context::{
+    token::{jwt_claims, matches_audience},
+    Authentication, IssuerHealth, LogoutReason, OAuth2Context, Operation, Reason,
+};
+use async_trait::async_trait;
+use cache::{TokenCache, TokenKey};
+use deadline::Deadline;
+use events::Subscribers;
+use gloo_events::EventListener;
+use gloo_storage::{LocalStorage, SessionStorage, Storage};
+use gloo_utils::{document, history, window};
 use log::error;
-use num_traits::cast::ToPrimitive;
 use reqwest::Url;
+use serde::Serialize;
 use state::*;
 use std::{cmp::min, collections::HashMap, fmt::Debug, time::Duration};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use wasm_bindgen::JsValue;
+use tokio::sync::{
+    mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedSender},
+    oneshot,
+};
+use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::spawn_local;
-use yew::Callback;
+use web_sys::StorageEvent;
 
 /// Options for the login process
 ///
@@ -48,31 +88,28 @@ use yew::Callback;
 /// By default, the login process will ask the issuer to redirect back the page that was active when starting the login
 /// process. In some cases, the issuer might require a more strict set of redirect URLs, and so can only redirect back
 /// to a single page. This can be enabled set setting a specific URL as `redirect_url`.
+/// Alternatively, the agent can be configured with the public path of the application (e.g. `/app/`), which then is
+/// the default redirect URL, on the current origin.
 ///
 /// Once the user comes back from the login flow, which might actually be without any user interaction if the session
 /// was still valid, users might find themselves on the redirect page. Therefore, it is advisable to forward/redirect
 /// back to the original page, the one where the user left off.
 ///
-/// While this crate does provide some assistance, the actual implementation on how to redirect is left to the user
-/// of this crate. If, while starting the login process, the currently active URL differs from the `redirect_url`,
-/// the agent will store the "current" URL and pass it to the provided "post login redirect callback" once the
-/// login process has completed.
-///
-/// It could be argued, that the crate should just perform the redirect automatically, if no call back was provided.
-/// However, there can be different ways to redirect, and there is no common one. One might think just setting a new
-/// location in the browser should work, but that would actually cause a page reload, and would then start the login
-/// process again, since the tokens are only held in memory for security reasons. Also using the browser's History API
-/// won't work, as it does not notify listeners when pushing a new state.
+/// While starting the login process, the agent stores the full "current" URL, including its query and fragment. Once
+/// the login process has completed, it restores that URL in place, using the browser's History API and dispatching a
+/// `popstate` event for routers to pick up the change. The fragment is never part of the default redirect URL, as
+/// issuers reject redirect URLs with one, so deep links using it are restored this way as well.
 ///
-/// Therefore, it is necessary to set a "post login redirect callback", which will be triggered to handle the redirect,
-/// in order to allow the user of the crate to implement the needed logic. Having the `yew-nested-router`
-/// feature enabled, it is possible to just call [`LoginOptions::with_nested_router_redirect`] and let the
-/// router take care of this.
+/// If the "current" URL differs from the `redirect_url`, and a "post login redirect callback" is provided, the URL is
+/// passed to the callback instead, leaving the actual implementation on how to redirect to the user of this crate.
 ///
-/// **NOTE:** As a summary, setting only the `redirect_url` will not be sufficient. The "post login redirect callback" must
-/// also be implemented or the `yew-nested-router`feature used. Otherwise, the user would simply end up on the page defined by
-/// `redirect_url`, which in most cases is not what one would expect.
-#[derive(Debug, Clone, Default)]
+/// Setting a new location in the browser is never an option, as that would actually cause a page reload, and would
+/// then start the login process again, since the tokens are only held in memory for security reasons. Restoring the
+/// URL in place works with routers listening for `popstate`, for others a "post login redirect callback" can implement
+/// the needed logic. Having the `yew-nested-router` feature enabled, it is possible to just call
+/// [`LoginOptions::with_nested_router_redirect`] and let the router take care of this. The same
+/// works for `yew-router`, using `LoginOptions::with_yew_router_redirect`.
+#[derive(Debug, Clone, Default, PartialEq)]
 #[non_exhaustive]
 pub struct LoginOptions {
     /// Additional query parameters sent to the issuer.
@@ -80,7 +117,8 @@ pub struct LoginOptions {
 
     /// Defines the redirect URL. See ["Redirect & Post login redirect"](#redirect--post-login-redirect) for more information.
     ///
-    /// If this field is empty, the current URL is used as a redirect URL.
+    /// If this field is empty, the redirect URL of the client configuration is used, or else the
+    /// public path of the agent, or the current URL.
     pub redirect_url: Option<Url>,
 
     /// Defines callback used for post-login redirect.
@@ -90,7 +128,152 @@ pub struct LoginOptions {
     /// is complete.
     ///
     /// If `None`, disables post-login redirect.
-    pub post_login_redirect_callback: Option<Callback<String>>,
+    pub post_login_redirect_callback: Option<AgentCallback<String>>,
+
+    /// Defines the URL to return to after the login, restored or passed to the post-login redirect
+    /// callback.
+    ///
+    /// If this field is empty, the current URL, when starting the login, is used.
+    pub post_login_url: Option<Url>,
+
+    /// Scopes to request, replacing the scopes configured for the session.
+    ///
+    /// If this field is empty, the configured scopes are used.
+    pub scopes: Option<Vec<String>>,
+
+    /// Values of the `prompt` parameter, e.g. to force a re-authentication.
+    pub prompt: Vec<Prompt>,
+
+    /// The maximum authentication age (`max_age`).
+    ///
+    /// If the user authenticated longer ago, the issuer must re-authenticate the user.
+    pub max_age: Option<Duration>,
+
+    /// A hint to the issuer about the login identifier of the user (`login_hint`).
+    pub login_hint: Option<String>,
+
+    /// The preferred languages of the user interface (`ui_locales`).
+    pub ui_locales: Vec<String>,
+
+    /// How the issuer should display the user interface (`display`), e.g. `page` or `popup`.
+    pub display: Option<String>,
+
+    /// The requested authentication context classes (`acr_values`), e.g. for requiring MFA.
+    ///
+    /// If present, the class of the returned ID token must be one of the requested ones, or the
+    /// login fails with [`OAuth2Error::AcrNotSatisfied`]. This is only supported with OpenID
+    /// Connect.
+    pub acr_values: Vec<String>,
+
+    /// A hint to a brokering issuer, which upstream identity provider to use.
+    ///
+    /// This allows skipping the provider selection page of the issuer.
+    pub idp_hint: Option<IdpHint>,
+
+    /// How the issuer should return the authorization response (`response_mode`).
+    ///
+    /// If this field is empty, the default of the issuer is used, which is the query for the
+    /// code flow. Responses are accepted in the query or the fragment in any case.
+    pub response_mode: Option<ResponseMode>,
+
+    /// A payload of the application, handed back to the app state callback after the login.
+    ///
+    /// The payload is kept in the session storage, alongside the CSRF token, and never sent to
+    /// the issuer. So it should be small, and must not contain secrets.
+    pub app_state: Option<serde_json::Value>,
+
+    /// Defines callback receiving the payload of the application, once the login is complete.
+    ///
+    /// As the login loads the page again, only the callback of the default login options is
+    /// still known when it completes. Starting a login with a different callback fails.
+    pub app_state_callback: Option<AgentCallback<serde_json::Value>>,
+}
+
+/// A hint to a brokering issuer, which upstream identity provider to use.
+///
+/// There is no standard parameter for this, so the name of the parameter depends on the issuer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdpHint {
+    /// Keycloak's `kc_idp_hint`, the alias of the identity provider.
+    Keycloak(String),
+    /// Azure's `domain_hint`, the domain of the user's organization.
+    Azure(String),
+    /// A custom parameter.
+    Custom {
+        /// The name of the parameter
+        parameter: String,
+        /// The value of the parameter
+        value: String,
+    },
+}
+
+impl IdpHint {
+    /// The name of the authorization request parameter.
+    pub fn parameter(&self) -> &str {
+        match self {
+            Self::Keycloak(_) => "kc_idp_hint",
+            Self::Azure(_) => "domain_hint",
+            Self::Custom { parameter, .. } => parameter,
+        }
+    }
+
+    /// The value of the authorization request parameter.
+    pub fn value(&self) -> &str {
+        match self {
+            Self::Keycloak(value) | Self::Azure(value) | Self::Custom { value, .. } => value,
+        }
+    }
+}
+
+/// Values of the `response_mode` parameter of the authorization request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResponseMode {
+    /// The response parameters are added to the query of the redirect URL.
+    Query,
+    /// The response parameters are added to the fragment of the redirect URL.
+    ///
+    /// This keeps the code out of the logs of servers and proxies, as the fragment is never sent
+    /// to the server.
+    Fragment,
+    /// A non-standard value.
+    Other(String),
+}
+
+impl ResponseMode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Query => "query",
+            Self::Fragment => "fragment",
+            Self::Other(value) => value,
+        }
+    }
+}
+
+/// Values of the `prompt` parameter of the authorization request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Prompt {
+    /// Do not display any user interface.
+    None,
+    /// Force the user to re-authenticate.
+    Login,
+    /// Ask the user for consent.
+    Consent,
+    /// Let the user select an account.
+    SelectAccount,
+    /// A non-standard value.
+    Other(String),
+}
+
+impl Prompt {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Login => "login",
+            Self::Consent => "consent",
+            Self::SelectAccount => "select_account",
+            Self::Other(value) => value,
+        }
+    }
 }
 
 impl LoginOptions {
@@ -122,16 +305,142 @@ impl LoginOptions {
         self
     }
 
+    /// Set the URL passed to the post-login redirect callback
+    pub fn with_post_login_url(mut self, post_login_url: impl Into<Url>) -> Self {
+        self.post_login_url = Some(post_login_url.into());
+        self
+    }
+
+    /// Set the scopes, replacing the configured ones
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scopes = Some(scopes.into_iter().map(|s| s.into()).collect());
+        self
+    }
+
+    /// Add a value to the `prompt` parameter
+    pub fn add_prompt(mut self, prompt: Prompt) -> Self {
+        self.prompt.push(prompt);
+        self
+    }
+
+    /// Set the maximum authentication age
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set the login hint
+    pub fn with_login_hint(mut self, login_hint: impl Into<String>) -> Self {
+        self.login_hint = Some(login_hint.into());
+        self
+    }
+
+    /// Set the preferred languages of the user interface
+    pub fn with_ui_locales(
+        mut self,
+        ui_locales: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.ui_locales = ui_locales.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Set how the issuer should display the user interface
+    pub fn with_display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+        self
+    }
+
+    /// Set the requested authentication context classes
+    pub fn with_acr_values(
+        mut self,
+        acr_values: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.acr_values = acr_values.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Set the identity provider hint
+    pub fn with_idp_hint(mut self, idp_hint: IdpHint) -> Self {
+        self.idp_hint = Some(idp_hint);
+        self
+    }
+
+    /// Set how the issuer should return the authorization response
+    pub fn with_response_mode(mut self, response_mode: ResponseMode) -> Self {
+        self.response_mode = Some(response_mode);
+        self
+    }
+
+    /// Set the payload of the application, handed back after the login
+    ///
+    /// E.g. the item the user was trying to add to the cart. A payload which fails to serialize
+    /// is dropped, with a warning.
+    pub fn with_app_state<S: Serialize>(mut self, app_state: &S) -> Self {
+        self.app_state = serde_json::to_value(app_state)
+            .map_err(|err| log::warn!("Failed to serialize app state: {err}"))
+            .ok();
+        self
+    }
+
+    /// Set a callback receiving the payload of the application, after the login
+    ///
+    /// This must be set on the default login options, see [`Self::app_state_callback`].
+    pub fn with_app_state_callback(
+        mut self,
+        app_state_callback: impl Into<AgentCallback<serde_json::Value>>,
+    ) -> Self {
+        self.app_state_callback = Some(app_state_callback.into());
+        self
+    }
+
+    /// The standard authorization request parameters, set by these options.
+    pub(crate) fn authorization_params(&self) -> Vec<(String, String)> {
+        let mut params = vec![];
+
+        if !self.prompt.is_empty() {
+            let prompt = self
+                .prompt
+                .iter()
+                .map(Prompt::as_str)
+                .collect::<Vec<_>>()
+                .join(" ");
+            params.push(("prompt".into(), prompt));
+        }
+        if let Some(max_age) = self.max_age {
+            params.push(("max_age".into(), max_age.as_secs().to_string()));
+        }
+        if let Some(login_hint) = &self.login_hint {
+            params.push(("login_hint".into(), login_hint.clone()));
+        }
+        if !self.ui_locales.is_empty() {
+            params.push(("ui_locales".into(), self.ui_locales.join(" ")));
+        }
+        if let Some(display) = &self.display {
+            params.push(("display".into(), display.clone()));
+        }
+        if let Some(idp_hint) = &self.idp_hint {
+            params.push((idp_hint.parameter().into(), idp_hint.value().into()));
+        }
+        if let Some(response_mode) = &self.response_mode {
+            params.push(("response_mode".into(), response_mode.as_str().into()));
+        }
+
+        params
+    }
+
     /// Set a callback for post-login redirect
-    pub fn with_redirect_callback(mut self, redirect_callback: Callback<String>) -> Self {
-        self.post_login_redirect_callback = Some(redirect_callback);
+    pub fn with_redirect_callback(
+        mut self,
+        redirect_callback: impl Into<AgentCallback<String>>,
+    ) -> Self {
+        self.post_login_redirect_callback = Some(redirect_callback.into());
         self
     }
 
     /// Use `yew-nested-router` History API for post-login redirect callback
     #[cfg(feature = "yew-nested-router")]
     pub fn with_nested_router_redirect(mut self) -> Self {
-        let callback = Callback::from(|url: String| {
+        let callback = AgentCallback::new(|url: String| {
             if yew_nested_router::History::push_state(JsValue::null(), &url).is_err() {
                 error!("Unable to redirect");
             }
@@ -140,6 +449,45 @@ impl LoginOptions {
         self.post_login_redirect_callback = Some(callback);
         self
     }
+
+    /// Use `yew-router` History API for post-login redirect callback
+    ///
+    /// The router picks up the change, and renders the route which was active when starting
+    /// the login. The URL replaces the one of the response, like restoring it in place does, so
+    /// that going back doesn't return to the response of the issuer.
+    #[cfg(feature = "yew-router")]
+    pub fn with_yew_router_redirect(mut self) -> Self {
+        use yew_router::history::{BrowserHistory, History};
+
+        let callback = AgentCallback::new(|url: String| BrowserHistory::new().replace(url));
+
+        self.post_login_redirect_callback = Some(callback);
+        self
+    }
+
+    /// Return to the route after the login, instead of the current URL
+    ///
+    /// The path of the route is prefixed with the base of the router, the `href` of the page's
+    /// `<base>` element.
+    #[cfg(feature = "yew-router")]
+    pub fn with_post_login_route<R: yew_router::Routable>(mut self, route: &R) -> Self {
+        let path = format!(
+            "{}{}",
+            yew_router::utils::base_url().unwrap_or_default(),
+            route.to_path()
+        );
+        match window()
+            .location()
+            .href()
+            .ok()
+            .and_then(|href| Url::parse(&href).ok())
+            .and_then(|current| current.join(&path).ok())
+        {
+            Some(url) => self.post_login_url = Some(url),
+            None => error!("Unable to build the URL of the route: {path}"),
+        }
+        self
+    }
 }
 
 /// Options for the logout process
@@ -152,6 +500,17 @@ pub struct LogoutOptions {
     ///
     /// This would override any settings from the client configuration.
     pub target: Option<Url>,
+
+    /// An optional `state`, which the issuer passes back when navigating to the target.
+    pub state: Option<String>,
+
+    /// Only end the local session, keeping the session of the issuer.
+    ///
+    /// By default, the client will end the session of the issuer too, if it is able to.
+    pub local_only: bool,
+
+    /// The reason for the logout, see [`Reason::Logout`].
+    pub reason: LogoutReason,
 }
 
 impl LogoutOptions {
@@ -163,9 +522,28 @@ impl LogoutOptions {
         self.target = Some(target.into());
         self
     }
+
+    /// Set the state, passed back by the issuer
+    pub fn with_state(mut self, state: impl Into<String>) -> Self {
+        self.state = Some(state.into());
+        self
+    }
+
+    /// Set if only the local session should be ended
+    pub fn with_local_only(mut self, local_only: bool) -> Self {
+        self.local_only = local_only;
+        self
+    }
+
+    /// Set the reason for the logout
+    pub fn with_reason(mut self, reason: LogoutReason) -> Self {
+        self.reason = reason;
+        self
+    }
 }
 
 #[doc(hidden)]
+#[allow(clippy::large_enum_variant)]
 pub enum Msg<C>
 where
     C: Client,
@@ -173,9 +551,39 @@ where
     Configure(AgentConfiguration<C>),
     StartLogin(Option<LoginOptions>),
     Logout(Option<LogoutOptions>),
+    RemoteLogout(LogoutReason),
     Refresh,
+    RefreshSession,
+    AccountUrl(Option<Url>, oneshot::Sender<Option<Url>>),
+    AcquireToken(TokenRequest, TokenSender),
+    Unauthorized,
+    HealthCallback(AgentCallback<IssuerHealth>),
+    Subscribe(usize, AgentCallback<AgentEvent>),
+    Unsubscribe(usize),
+    Watch(UnboundedSender<OAuth2Context>),
+    HealthCheck,
+    Health(IssuerHealth),
+    KeepAlive,
+    LoginFunnel(oneshot::Sender<LoginFunnel>),
+    Retry,
+    Refreshed {
+        generation: u64,
+        refresh_token: String,
+        session_state: C::SessionState,
+        result: Result<(OAuth2Context, C::SessionState), OAuth2Error>,
+    },
+    VisibilityChange,
+    Online,
+    #[cfg(feature = "worker")]
+    VaultRefreshed(String),
+    #[cfg(feature = "e2e")]
+    InjectSession(Authentication),
+    #[cfg(feature = "e2e")]
+    ForceExpire,
 }
 
+type TokenSender = oneshot::Sender<Result<AccessToken, Error>>;
+
 /// The agent handling the OAuth2/OIDC state
 #[derive(Clone, Debug)]
 pub struct Agent<C>
@@ -198,8 +606,24 @@ where
         let inner = InnerAgent::new(tx.clone(), state_callback);
         inner.spawn(rx);
 
+        #[cfg(feature = "e2e")]
+        e2e::install(tx.clone());
+        #[cfg(feature = "js-api")]
+        js::install();
+
         Self { tx }
     }
+
+    /// Set a callback, receiving the outcome of the issuer health checks.
+    pub fn with_health_callback<F>(self, health_callback: F) -> Self
+    where
+        F: Fn(IssuerHealth) + 'static,
+    {
+        let _ = self
+            .tx
+            .try_send(Msg::HealthCallback(AgentCallback::new(health_callback)));
+        self
+    }
 }
 
 #[doc(hidden)]
@@ -208,16 +632,45 @@ where
     C: Client,
 {
     tx: Sender<Msg<C>>,
-    state_callback: Callback<OAuth2Context>,
+    state_callback: AgentCallback<OAuth2Context>,
+    configuration: Option<AgentConfiguration<C>>,
     config: Option<InnerConfig>,
     client: Option<C>,
     state: OAuth2Context,
     session_state: Option<C::SessionState>,
-    timeout: Option<Timeout>,
+    timeout: Option<Timer>,
+    tokens: TokenCache,
+    unauthorized: u32,
+    health_callback: Option<AgentCallback<IssuerHealth>>,
+    subscribers: Subscribers,
+    watchers: Vec<UnboundedSender<OAuth2Context>>,
+    health_check: Option<Timer>,
+    health: IssuerHealth,
+    keep_alive: Option<Timer>,
+    logout_listener: Option<EventListener>,
+    visibility_listener: Option<EventListener>,
+    online_listener: Option<EventListener>,
+    /// A scheduled refresh was deferred, as the page was hidden, or the browser offline
+    refresh_deferred: bool,
+    logout_pending: bool,
+    recorder: Option<FlowRecorder>,
+    retries: u32,
+    failed_refresh: Option<(String, C::SessionState)>,
+    /// Requests for tokens, waiting for the refresh in flight
+    refreshing: Option<Vec<(TokenRequest, TokenSender)>>,
+    /// Incremented with each change of the state
+    generation: u64,
+    /// The session was restored from the storage, and still needs to be revalidated
+    restored: bool,
+    /// The expiration of the session token
+    deadline: Option<Deadline>,
+    /// The cleaned up URL, navigated to after handling the response of the issuer
+    pending_navigation: Option<String>,
 }
 
 #[doc(hidden)]
 #[derive(Clone, Debug)]
+#[cfg_attr(test, derive(Default))]
 pub struct InnerConfig {
     scopes: Vec<String>,
     grace_period: Duration,
@@ -225,6 +678,25 @@ pub struct InnerConfig {
     audience: Option<String>,
     default_login_options: Option<LoginOptions>,
     default_logout_options: Option<LogoutOptions>,
+    unauthorized_policy: UnauthorizedPolicy,
+    unknown_state_policy: UnknownStatePolicy,
+    health_check: Option<Duration>,
+    retry_policy: RetryPolicy,
+    random: Random,
+    metrics: Option<Metrics>,
+    scope: AppScope,
+    backend_session: Option<BackendSession>,
+    keep_alive: Option<KeepAlive>,
+    session_persistence: SessionPersistence,
+    expiry: ExpiryPolicy,
+    nonce_window: Duration,
+    refresh_tokens: RefreshTokenPolicy,
+    refresh_ahead: Option<RefreshAhead>,
+    hidden_refresh: HiddenRefreshPolicy,
+    refresh_jitter: Duration,
+    dom_events: bool,
+    url_cleanup: UrlCleanupPolicy,
+    public_path: Option<String>,
 }
 
 impl<C> InnerAgent<C>
@@ -237,12 +709,59 @@ where
     {
         Self {
             tx,
-            state_callback: Callback::from(state_callback),
+            state_callback: AgentCallback::new(state_callback),
             client: None,
+            configuration: None,
             config: None,
             state: OAuth2Context::NotInitialized,
             session_state: None,
             timeout: None,
+            tokens: Default::default(),
+            unauthorized: 0,
+            health_callback: None,
+            subscribers: Subscribers::default(),
+            watchers: Vec::new(),
+            health_check: None,
+            health: IssuerHealth::Unknown,
+            keep_alive: None,
+            logout_listener: None,
+            visibility_listener: None,
+            online_listener: None,
+            refresh_deferred: false,
+            logout_pending: false,
+            recorder: None,
+            retries: 0,
+            failed_refresh: None,
+            refreshing: None,
+            generation: 0,
+            restored: false,
+            deadline: None,
+            pending_navigation: None,
+        }
+    }
+
+    /// Record an event, if a recorder is configured.
+    fn record(&self, event: TraceEvent) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(event);
+        }
+    }
+
+    /// Record the outcome of an operation.
+    fn record_outcome<T>(&self, operation: Operation, result: &Result<T, OAuth2Error>) {
+        self.record(TraceEvent::Outcome {
+            operation,
+            error: result.as_ref().err().map(Into::into),
+        });
+    }
+
+    /// The scope of the application, for the stored state.
+    fn scope(&self) -> AppScope {
+        match (&self.config, &self.configuration) {
+            (Some(config), _) => config.scope.clone(),
+            // still creating the client
+            (None, Some(configuration)) => AppScope::new(configuration.base_path.as_deref()),
+            (None, None) => AppScope::default(),
         }
     }
 
@@ -268,64 +787,213 @@ where
         match msg {
             Msg::Configure(config) => self.configure(config).await,
             Msg::StartLogin(login) => {
-                if let Err(err) = self.start_login(login) {
+                if let Err(err) = self.start_login(login, None) {
                     // FIXME: need to report this somehow
                     log::info!("Failed to start login: {err}");
                 }
             }
             Msg::Logout(logout) => self.logout_opts(logout),
-            Msg::Refresh => self.refresh().await,
+            Msg::RemoteLogout(reason) => self.remote_logout(reason),
+            Msg::Refresh => self.scheduled_refresh(),
+            Msg::RefreshSession => {
+                if matches!(self.state, OAuth2Context::Authenticated(_)) {
+                    self.refresh();
+                }
+            }
+            Msg::AccountUrl(return_url, tx) => {
+                let _ = tx.send(self.account_url(return_url));
+            }
+            Msg::AcquireToken(request, tx) => self.request_token(request, tx).await,
+            Msg::Unauthorized => self.unauthorized(),
+            Msg::HealthCallback(callback) => self.health_callback = Some(callback),
+            Msg::Subscribe(id, callback) => self.subscribers.insert(id, callback),
+            Msg::Unsubscribe(id) => self.subscribers.remove(id),
+            Msg::Watch(tx) => {
+                if tx.send(self.state.clone()).is_ok() {
+                    self.watchers.push(tx);
+                }
+            }
+            Msg::HealthCheck => self.health_check(),
+            Msg::Health(health) => self.update_health(health),
+            Msg::KeepAlive => self.keep_alive(),
+            Msg::LoginFunnel(tx) => {
+                let _ = tx.send(metrics::login_funnel(&self.scope()));
+            }
+            Msg::Retry => self.retry().await,
+            Msg::Refreshed {
+                generation,
+                refresh_token,
+                session_state,
+                result,
+            } => {
+                self.refreshed(generation, refresh_token, session_state, result)
+                    .await
+            }
+            Msg::VisibilityChange => self.visibility_change(),
+            Msg::Online => self.online(),
+            #[cfg(feature = "worker")]
+            Msg::VaultRefreshed(handle) => self.vault_refreshed(handle),
+            #[cfg(feature = "e2e")]
+            Msg::InjectSession(auth) => {
+                log::info!("Injecting session");
+                self.update_state(OAuth2Context::Authenticated(auth), None);
+            }
+            #[cfg(feature = "e2e")]
+            Msg::ForceExpire => {
+                log::info!("Forcing the session to expire");
+                self.update_state(
+                    OAuth2Context::NotAuthenticated {
+                        reason: Reason::Expired,
+                    },
+                    None,
+                );
+            }
         }
     }
 
     fn update_state(&mut self, state: OAuth2Context, session_state: Option<C::SessionState>) {
         log::debug!("update state: {state:?}");
 
-        if let OAuth2Context::Authenticated(Authentication {
-            expires: Some(expires),
-            ..
-        }) = &state
-        {
-            let grace = self
-                .config
-                .as_ref()
-                .map(|c| c.grace_period)
-                .unwrap_or_default();
+        // an outcome of a refresh in flight no longer applies
+        self.generation = self.generation.wrapping_add(1);
+
+        self.record(TraceEvent::State((&state).into()));
 
-            let mut expires = *expires;
-            if let Some(max) = self.config.as_ref().and_then(|cfg| cfg.max_expiration) {
-                // cap time the token expires by "max"
-                expires = min(expires, max.as_secs());
+        self.deadline = match (&self.state, &state) {
+            // the same token, keep its deadline
+            (OAuth2Context::Authenticated(current), OAuth2Context::Authenticated(new))
+                if current.access_token == new.access_token && current.expires == new.expires =>
+            {
+                self.deadline
             }
+            (_, OAuth2Context::Authenticated(auth)) => auth.expires.map(Deadline::from_expires),
+            _ => None,
+        };
 
-            // get now as seconds
-            let now = Date::now() / 1000f64;
-            // get delta from now to expiration minus the grace period
-            let diff = expires as f64 - now - grace.as_secs_f64();
+        self.schedule_refresh(state.authentication().and_then(|auth| auth.expires));
 
-            let tx = self.tx.clone();
-            if diff > 0f64 {
-                // while the API says millis is u32, internally it is i32
-                let millis = (diff * 1000f64).to_i32().unwrap_or(i32::MAX);
-                log::debug!("Starting timeout for: {}ms", millis);
-                self.timeout = Some(Timeout::new(millis as u32, move || {
-                    let _ = tx.try_send(Msg::Refresh);
-                }));
-            } else {
-                // token already expired
-                let _ = tx.try_send(Msg::Refresh);
+        if !matches!(state, OAuth2Context::Authenticated(..)) {
+            // tokens acquired for the previous session are no longer valid
+            self.tokens.clear();
+        }
+
+        if matches!(state, OAuth2Context::Authenticated(..)) {
+            // a new session can be logged out again
+            self.logout_pending = false;
+        }
+
+        let scope = self.scope();
+        match (&state, &session_state) {
+            (OAuth2Context::Authenticated(authentication), session_state) => {
+                state::set_session_hint(&scope, true);
+                if let (Some(config), Some(session_state)) = (&self.config, session_state) {
+                    state::persist_session(
+                        config.session_persistence,
+                        &scope,
+                        &PersistedSession {
+                            authentication: authentication.clone(),
+                            session_state: session_state.clone(),
+                        },
+                    );
+                }
             }
-        } else {
-            self.timeout = None;
+            // failing to reach the issuer doesn't tell if the session is gone
+            (
+                OAuth2Context::NotInitialized
+                | OAuth2Context::Failed {
+                    operation: Operation::Discovery,
+                    ..
+                },
+                _,
+            ) => {}
+            (OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed { .. }, _) => {
+                state::set_session_hint(&scope, false);
+                state::forget_session(&scope);
+            }
+        }
+
+        if state.access_token() != self.state.access_token() {
+            // a new token, start counting again
+            self.unauthorized = 0;
         }
 
+        // any new state ends retrying
+        self.retries = 0;
+        self.failed_refresh = None;
+        self.refresh_deferred = false;
+
         self.notify_state(state.clone());
+        if let Some(event) = AgentEvent::from_change(&self.state, &state) {
+            if self.config.as_ref().is_some_and(|config| config.dom_events) {
+                event.dispatch();
+            }
+            self.subscribers.emit(event);
+        }
 
         self.state = state;
         self.session_state = session_state;
     }
 
-    fn notify_state(&self, state: OAuth2Context) {
+    /// Schedule refreshing the session, before the token with the expiration expires.
+    fn schedule_refresh(&mut self, expires: Option<u64>) {
+        let (Some(expires), Some(deadline)) = (expires, self.deadline) else {
+            self.timeout = None;
+            return;
+        };
+
+        // refresh before the token can no longer be used for new requests
+        let grace = self
+            .config
+            .as_ref()
+            .map(|c| {
+                c.refresh_ahead
+                    .map_or(c.grace_period, |ahead| ahead.before(deadline.lifetime()))
+                    .max(c.expiry.soft)
+                    // also for a grace period, or threshold, longer than the lifetime
+                    .min(max_refresh_ahead(deadline.lifetime()))
+            })
+            .unwrap_or_default();
+
+        let mut remaining = deadline.remaining();
+        if let Some(max) = self.config.as_ref().and_then(|cfg| cfg.max_expiration) {
+            // cap time the token expires by "max"
+            let capped = min(expires, max.as_secs());
+            remaining = remaining.saturating_sub(Duration::from_secs(expires - capped));
+        }
+
+        // get delta from now to expiration minus the grace period
+        let mut diff = remaining.as_secs_f64() - grace.as_secs_f64();
+        if diff > 0f64 {
+            // spread the refreshes of multiple tabs, or clients
+            if let Some(config) = &self.config {
+                let jitter = config.random.duration(config.refresh_jitter);
+                diff = (diff - jitter.as_secs_f64()).max(0f64);
+            }
+        }
+
+        let tx = self.tx.clone();
+        if diff > 0f64 {
+            let delay = Duration::from_secs_f64(diff);
+
+            #[cfg(feature = "worker")]
+            if self.delegate_refresh(delay) {
+                self.timeout = None;
+                return;
+            }
+
+            log::debug!("Starting timeout for: {delay:?}");
+            self.timeout = Some(Clock::current().timeout(delay, move || {
+                let _ = tx.try_send(Msg::Refresh);
+            }));
+        } else {
+            // token already expired
+            let _ = tx.try_send(Msg::Refresh);
+        }
+    }
+
+    fn notify_state(&mut self, state: OAuth2Context) {
+        // streams which were dropped are gone for good
+        self.watchers.retain(|tx| tx.send(state.clone()).is_ok());
         self.state_callback.emit(state);
     }
 
@@ -335,41 +1003,197 @@ where
             Ok((client, config)) => {
                 log::debug!("Client created");
 
+                self.health_check = config.health_check.map(|interval| {
+                    let tx = self.tx.clone();
+                    let _ = tx.try_send(Msg::HealthCheck);
+                    Clock::current().interval(interval, move || {
+                        let _ = tx.try_send(Msg::HealthCheck);
+                    })
+                });
+
+                self.keep_alive = config.keep_alive.as_ref().map(|keep_alive| {
+                    let tx = self.tx.clone();
+                    Clock::current().interval(keep_alive.interval, move || {
+                        let _ = tx.try_send(Msg::KeepAlive);
+                    })
+                });
+
+                self.logout_listener = Some(self.listen_logout(&config.scope));
+                self.visibility_listener = Some(self.listen_visibility());
+                self.online_listener = Some(self.listen_online());
+
+                #[cfg(feature = "worker")]
+                if let Some(vault) = client.token_vault() {
+                    let tx = self.tx.clone();
+                    vault.on_refreshed(move |handle| {
+                        let _ = tx.try_send(Msg::VaultRefreshed(handle.to_string()));
+                    });
+                }
+
                 self.client = Some(client);
                 self.config = Some(config);
 
-                if matches!(self.state, OAuth2Context::NotInitialized) {
+                if Self::is_initializing(&self.state) || self.restored {
+                    let restored = std::mem::take(&mut self.restored);
                     let detected = self.detect_state().await;
                     log::debug!("Detected state: {detected:?}");
+                    if detected.is_ok() {
+                        SessionStorage::delete(self.scope().key(STORAGE_KEY_LOGIN_RETRIES));
+                    }
                     match detected {
                         Ok(true) => {
                             if let Err(e) = self.post_login_redirect() {
                                 error!("Post-login redirect failed: {e}");
                             }
+                            self.return_app_state();
+                            match &self.state {
+                                OAuth2Context::Authenticated(auth) => self
+                                    .subscribers
+                                    .emit(AgentEvent::LoginCompleted(auth.clone())),
+                                OAuth2Context::Failed { error, .. } => self
+                                    .subscribers
+                                    .emit(AgentEvent::LoginFailed(error.clone())),
+                                _ => {}
+                            }
+                        }
+                        Ok(false) if restored => {
+                            // the restored session is only downgraded if refreshing fails
+                            self.refresh();
                         }
+                        Ok(false) if self.resume_session().await => {}
                         Ok(false) => {
-                            self.update_state(
-                                OAuth2Context::NotAuthenticated {
-                                    reason: Reason::NewSession,
-                                },
-                                None,
-                            );
+                            // e.g. returning from the issuer, after ending the session
+                            let key = self.scope().key(STORAGE_KEY_LOGOUT_REASON);
+                            let reason = match SessionStorage::get::<LogoutReason>(&key) {
+                                Ok(reason) => {
+                                    SessionStorage::delete(&key);
+                                    Reason::Logout(reason)
+                                }
+                                Err(_) => Reason::NewSession,
+                            };
+                            self.update_state(OAuth2Context::NotAuthenticated { reason }, None);
                         }
                         Err(err) => {
-                            self.update_state(err.into(), None);
+                            self.subscribers.emit(AgentEvent::LoginFailed(err.clone()));
+                            match err {
+                                OAuth2Error::TemporarilyUnavailable(_) => self.retry_login(err),
+                                OAuth2Error::UnknownState => self.unknown_state(),
+                                err => self.update_state(
+                                    OAuth2Context::failed(Operation::CodeExchange, err),
+                                    None,
+                                ),
+                            }
                         }
                     }
+                    self.navigate_after_cleanup();
                 }
             }
+            Err(err @ OAuth2Error::InvalidConfiguration(_)) => {
+                // don't keep running with the previous configuration
+                log::info!("Stopping, due to an invalid configuration: {err}");
+                self.update_state(OAuth2Context::failed(Operation::Discovery, err), None);
+                self.stop();
+            }
             Err(err) => {
                 log::debug!("Failed to configure client: {err}");
-                if matches!(self.state, OAuth2Context::NotInitialized) {
-                    self.update_state(err.into(), None);
+                if Self::is_initializing(&self.state) {
+                    self.update_state(OAuth2Context::failed(Operation::Discovery, err), None);
                 }
             }
         }
     }
 
+    /// Drop the client and its configuration, until the agent is configured again.
+    fn stop(&mut self) {
+        self.client = None;
+        self.config = None;
+        self.session_state = None;
+        self.health_check = None;
+        self.keep_alive = None;
+        self.logout_listener = None;
+        self.visibility_listener = None;
+        self.online_listener = None;
+    }
+
+    /// Resume the session the client already has, returning `true` if there was one.
+    async fn resume_session(&mut self) -> bool {
+        let Some(client) = self.client.clone() else {
+            return false;
+        };
+        let Some((state, session_state)) = client.resume_session().await else {
+            return false;
+        };
+
+        log::debug!("Resuming the session of the client");
+        self.update_state(state, Some(session_state));
+        true
+    }
+
+    /// Start with the session handed over by the server, in case it didn't expire yet.
+    fn bootstrap_session(&mut self, config: &AgentConfiguration<C>) -> bool {
+        let Some((authentication, session_state)) = config
+            .bootstrap
+            .as_ref()
+            .and_then(|bootstrap| bootstrap.take::<C::SessionState>())
+        else {
+            return false;
+        };
+
+        let now = config.clock.now_secs();
+        let valid = authentication.expires.map_or(true, |expires| {
+            expires as f64 - now > config.grace_period.as_secs_f64()
+        });
+        if !valid {
+            log::debug!("Bootstrap session expired");
+            return false;
+        }
+
+        log::debug!("Starting with the bootstrap session");
+        self.update_state(OAuth2Context::Authenticated(authentication), session_state);
+        true
+    }
+
+    /// Restore a persisted session, in case it didn't expire yet.
+    fn restore_session(&mut self, config: &AgentConfiguration<C>) {
+        let scope = AppScope::new(config.base_path.as_deref());
+        let Some(PersistedSession {
+            authentication,
+            session_state,
+        }) = state::persisted_session::<C::SessionState>(config.session_persistence, &scope)
+        else {
+            return;
+        };
+
+        let now = config.clock.now_secs();
+        let valid = authentication
+            .expires
+            .is_some_and(|expires| expires as f64 - now > config.grace_period.as_secs_f64());
+        if !valid {
+            log::debug!("Persisted session expired");
+            state::forget_session(&scope);
+            return;
+        }
+
+        log::debug!("Restoring persisted session");
+        self.restored = true;
+        self.update_state(
+            OAuth2Context::Authenticated(authentication),
+            Some(session_state),
+        );
+    }
+
+    /// Check if the state still needs to be detected, after creating the client.
+    fn is_initializing(state: &OAuth2Context) -> bool {
+        matches!(
+            state,
+            OAuth2Context::NotInitialized
+                | OAuth2Context::Failed {
+                    operation: Operation::Discovery,
+                    ..
+                }
+        )
+    }
+
     async fn make_client(config: AgentConfiguration<C>) -> Result<(C, InnerConfig), OAuth2Error> {
         let AgentConfiguration {
             config,
@@ -379,9 +1203,32 @@ where
             default_login_options,
             default_logout_options,
             max_expiration,
+            unauthorized_policy,
+            unknown_state_policy,
+            health_check,
+            retry_policy,
+            random,
+            metrics,
+            base_path,
+            backend_session,
+            keep_alive,
+            recorder: _,
+            session_persistence,
+            expiry,
+            nonce_window,
+            refresh_tokens,
+            refresh_ahead,
+            hidden_refresh,
+            refresh_jitter,
+            dom_events,
+            bootstrap: _,
+            clock: _,
+            url_cleanup,
+            public_path,
         } = config;
 
-        let client = C::from_config(config).await?;
+        C::validate_config(&config).map_err(OAuth2Error::InvalidConfiguration)?;
+        let client = C::from_scoped_config(config, base_path.as_deref()).await?;
 
         let inner = InnerConfig {
             scopes,
@@ -390,6 +1237,25 @@ where
             default_login_options,
             default_logout_options,
             max_expiration,
+            unauthorized_policy,
+            unknown_state_policy,
+            health_check,
+            retry_policy,
+            random,
+            metrics,
+            scope: AppScope::new(base_path.as_deref()),
+            backend_session,
+            keep_alive,
+            session_persistence,
+            expiry,
+            nonce_window,
+            refresh_tokens,
+            refresh_ahead,
+            hidden_refresh,
+            refresh_jitter,
+            dom_events,
+            url_cleanup,
+            public_path,
         };
 
         Ok((client, inner))
@@ -400,7 +1266,17 @@ where
     /// Returns `false` if there is no authentication state found and the result is final.
     /// Otherwise, it returns `true` and spawns a request for e.g. a code exchange.
     async fn detect_state(&mut self) -> Result<bool, OAuth2Error> {
-        let client = self.client.as_ref().ok_or(OAuth2Error::NotInitialized)?;
+        let client = self.client.clone().ok_or(OAuth2Error::NotInitialized)?;
+        let metrics = self
+            .config
+            .as_ref()
+            .and_then(|config| config.metrics.clone());
+        let scope = self.scope();
+
+        if !Self::current_url().is_ok_and(|url| scope.contains(&url)) {
+            // not for this application
+            return Ok(false);
+        }
 
         let state = if let Some(state) = Self::find_query_state() {
             state
@@ -415,48 +1291,84 @@ where
             log::info!("Login error from server: {error}");
 
             // cleanup URL
-            Self::cleanup_url();
+            self.cleanup_url();
 
             // error from the OAuth2 server
-            return Err(OAuth2Error::LoginResult(error));
+            if error == TEMPORARILY_UNAVAILABLE {
+                return Err(OAuth2Error::TemporarilyUnavailable(state.error_description));
+            }
+            return Err(OAuth2Error::Server {
+                error,
+                description: state.error_description,
+                uri: state.error_uri,
+            });
         }
 
         if let Some(code) = state.code {
+            if state
+                .state
+                .as_deref()
+                .is_some_and(|state| is_consumed(&scope, state))
+            {
+                // e.g. reloading the page, the exchange would fail with the code already used
+                log::info!("Authorization response was already handled, ignoring");
+                self.cleanup_url();
+                return Ok(false);
+            }
+
+            metrics::record(FunnelStage::Returned, &scope, metrics.as_ref());
+
             // cleanup URL
-            Self::cleanup_url();
+            self.cleanup_url();
 
             match state.state {
-                None => {
-                    return Err(OAuth2Error::LoginResult(
-                        "Missing state from server".to_string(),
-                    ))
-                }
+                None => return Err(OAuth2Error::validation("missing state from server")),
                 Some(state) => {
-                    let stored_state = get_from_store(STORAGE_KEY_CSRF_TOKEN)?;
+                    let stored_state =
+                        get_from_store_optional(self.scope().key(STORAGE_KEY_CSRF_TOKEN))?;
 
-                    if state != stored_state {
-                        return Err(OAuth2Error::LoginResult("State mismatch".to_string()));
+                    if stored_state.as_ref() != Some(&state) {
+                        return Err(OAuth2Error::UnknownState);
                     }
+
+                    consume(&scope, state);
                 }
             }
 
             let state: C::LoginState =
-                SessionStorage::get(STORAGE_KEY_LOGIN_STATE).map_err(|err| {
+                SessionStorage::get(self.scope().key(STORAGE_KEY_LOGIN_STATE)).map_err(|err| {
                     OAuth2Error::Storage(format!("Failed to load login state: {err}"))
                 })?;
 
             log::debug!("Login state: {state:?}");
 
-            let redirect_url = get_from_store(STORAGE_KEY_REDIRECT_URL)?;
+            let redirect_url = get_from_store(self.scope().key(STORAGE_KEY_REDIRECT_URL))?;
             log::debug!("Redirect URL: {redirect_url}");
             let redirect_url = Url::parse(&redirect_url).map_err(|err| {
-                OAuth2Error::LoginResult(format!("Failed to parse redirect URL: {err}"))
+                OAuth2Error::Storage(format!("Failed to parse redirect URL: {err}"))
             })?;
 
-            let client = client.clone().set_redirect_uri(redirect_url);
+            let client = client.set_redirect_uri(redirect_url);
 
-            let result = client.exchange_code(code, state).await;
-            self.update_state_from_result(result);
+            let result = client
+                .exchange_code(code, state)
+                .await
+                .and_then(|result| self.check_nonce(result));
+            self.record_outcome(Operation::CodeExchange, &result);
+            let stage = match &result {
+                Ok(_) => FunnelStage::Exchanged,
+                Err(_) => FunnelStage::ExchangeFailed,
+            };
+            metrics::record(stage, &scope, metrics.as_ref());
+            if let Ok((OAuth2Context::Authenticated(auth), session_state)) = &result {
+                metrics::record(FunnelStage::Authenticated, &scope, metrics.as_ref());
+                self.establish_backend_session(auth, session_state);
+            }
+            if let Err(err @ OAuth2Error::TemporarilyUnavailable(_)) = result {
+                // the code exchange must be retried with a new login
+                return Err(err);
+            }
+            self.update_state_from_result(result, Operation::CodeExchange);
 
             Ok(true)
         } else {
@@ -465,39 +1377,315 @@ where
         }
     }
 
-    fn post_login_redirect(&self) -> Result<(), OAuth2Error> {
-        let config = self.config.as_ref().ok_or(OAuth2Error::NotInitialized)?;
-        let Some(redirect_callback) = config
-            .default_login_options
+    /// Reject ID tokens, whose nonce was already used within the nonce window.
+    fn check_nonce(
+        &self,
+        result: (OAuth2Context, C::SessionState),
+    ) -> Result<(OAuth2Context, C::SessionState), OAuth2Error> {
+        let window = self
+            .config
             .as_ref()
-            .and_then(|opts| opts.post_login_redirect_callback.clone())
-        else {
-            return Ok(());
+            .map(|config| config.nonce_window)
+            .unwrap_or_default();
+        if window.is_zero() {
+            return Ok(result);
+        }
+
+        let nonce = C::id_token(&result.1)
+            .and_then(|id_token| jwt_claims(&id_token))
+            .and_then(|claims| claims.get("nonce")?.as_str().map(ToString::to_string));
+
+        match nonce {
+            Some(nonce) if !state::consume_nonce(&self.scope(), &nonce, window) => {
+                log::warn!("Rejecting ID token with a nonce which was already used");
+                Err(OAuth2Error::validation(
+                    "nonce of the ID token was already used",
+                ))
+            }
+            _ => Ok(result),
+        }
+    }
+
+    /// Establish the backend session after a login, if configured.
+    fn establish_backend_session(&self, auth: &Authentication, session_state: &C::SessionState) {
+        let Some(backend_session) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.backend_session.clone())
+        else {
+            return;
+        };
+
+        let id_token = C::id_token(session_state);
+        let access_token = auth.access_token.clone();
+        spawn_local(async move {
+            backend_session.establish(id_token, access_token).await;
+        });
+    }
+
+    /// An authorization response doesn't match the pending login.
+    fn unknown_state(&mut self) {
+        let policy = self
+            .config
+            .as_ref()
+            .map(|config| config.unknown_state_policy)
+            .unwrap_or_default();
+
+        log::info!("Authorization response with unknown state, applying policy: {policy:?}");
+
+        match policy {
+            UnknownStatePolicy::Fail => {
+                self.update_state(
+                    OAuth2Context::failed(Operation::CodeExchange, OAuth2Error::UnknownState),
+                    None,
+                );
+            }
+            UnknownStatePolicy::Ignore => {
+                self.update_state(
+                    OAuth2Context::NotAuthenticated {
+                        reason: Reason::NewSession,
+                    },
+                    None,
+                );
+            }
+            UnknownStatePolicy::Login => {
+                if let Err(err) = self.start_login(None, None) {
+                    self.update_state(OAuth2Context::failed(Operation::Login, err), None);
+                }
+            }
+        }
+    }
+
+    /// Retry the login later, if the issuer is temporarily unavailable and the policy allows it.
+    ///
+    /// As the login navigates away, the number of attempts is kept in the session storage.
+    fn retry_login(&mut self, err: OAuth2Error) {
+        // loading the page again would abort the retry
+        self.pending_navigation = None;
+        let (policy, random) = self
+            .config
+            .as_ref()
+            .map(|config| (config.retry_policy, config.random.clone()))
+            .unwrap_or_default();
+        let attempt = SessionStorage::get::<u32>(self.scope().key(STORAGE_KEY_LOGIN_RETRIES))
+            .unwrap_or_default();
+
+        let Some(delay) = policy.delay(attempt) else {
+            log::info!("Issuer still temporarily unavailable, giving up: {err}");
+            SessionStorage::delete(self.scope().key(STORAGE_KEY_LOGIN_RETRIES));
+            self.update_state(OAuth2Context::failed(Operation::Login, err), None);
+            return;
         };
-        let Some(url) = get_from_store_optional(STORAGE_KEY_POST_LOGIN_URL)? else {
+
+        let delay = delay + random.duration(policy.jitter);
+        log::info!("Issuer temporarily unavailable, retrying login in {delay:?}");
+        if let Err(err) =
+            SessionStorage::set(self.scope().key(STORAGE_KEY_LOGIN_RETRIES), attempt + 1)
+        {
+            log::warn!("Failed to store login attempts: {err}");
+        }
+
+        self.update_state(
+            OAuth2Context::NotAuthenticated {
+                reason: Reason::TemporarilyUnavailable,
+            },
+            None,
+        );
+
+        let tx = self.tx.clone();
+        self.timeout = Some(Clock::current().timeout(delay, move || {
+            let _ = tx.try_send(Msg::StartLogin(None));
+        }));
+    }
+
+    /// Return to the URL which was active when starting the login.
+    ///
+    /// The redirect callback handles returning to a different page, otherwise the URL is restored
+    /// in place.
+    fn post_login_redirect(&mut self) -> Result<(), OAuth2Error> {
+        let config = self.config.as_ref().ok_or(OAuth2Error::NotInitialized)?;
+        let Some(url) = get_from_store_optional(self.scope().key(STORAGE_KEY_POST_LOGIN_URL))?
+        else {
             return Ok(());
         };
-        SessionStorage::delete(STORAGE_KEY_POST_LOGIN_URL);
-        redirect_callback.emit(url);
+        SessionStorage::delete(self.scope().key(STORAGE_KEY_POST_LOGIN_URL));
+        let redirect_url = get_from_store_optional(self.scope().key(STORAGE_KEY_REDIRECT_URL))?;
+
+        let redirect_callback = config
+            .default_login_options
+            .as_ref()
+            .and_then(|opts| opts.post_login_redirect_callback.clone());
+        match redirect_callback {
+            Some(redirect_callback) if redirect_url.as_ref() != Some(&url) => {
+                // the callback navigates on its own
+                self.pending_navigation = None;
+                redirect_callback.emit(url)
+            }
+            _ => self.restore_url(url),
+        }
 
         Ok(())
     }
 
+    /// Hand the payload of the application, stored when starting the login, back to the app.
+    fn return_app_state(&self) {
+        let key = self.scope().key(STORAGE_KEY_APP_STATE);
+        let Ok(app_state) = SessionStorage::get::<serde_json::Value>(&key) else {
+            return;
+        };
+        SessionStorage::delete(&key);
+
+        if let Some(app_state_callback) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.default_login_options.as_ref())
+            .and_then(|opts| opts.app_state_callback.clone())
+        {
+            app_state_callback.emit(app_state);
+        }
+    }
+
+    /// Restore the URL, without loading the page again.
+    ///
+    /// Routers listen for `popstate`, but not for changes of the history, so the event is
+    /// dispatched after replacing the URL.
+    fn restore_url(&mut self, url: String) {
+        let policy = self
+            .config
+            .as_ref()
+            .map(|config| config.url_cleanup.clone())
+            .unwrap_or_default();
+        if matches!(policy.cleanup, UrlCleanup::Nothing | UrlCleanup::Path(_)) {
+            // the application chose where to end up
+            return;
+        }
+        if policy.navigation == CleanupNavigation::Navigate {
+            self.pending_navigation = Some(url);
+            return;
+        }
+
+        if Self::current_url().is_ok_and(|current| current.as_str() == url) {
+            return;
+        }
+        let url = url.as_str();
+        log::debug!("Restoring URL: {url}");
+        let state = history().state().unwrap_or(JsValue::NULL);
+        if let Err(err) = history().replace_state_with_url(&state, "", Some(url)) {
+            log::warn!("Failed to restore URL: {err:?}");
+            return;
+        }
+        if let Ok(event) = web_sys::Event::new("popstate") {
+            let _ = window().dispatch_event(&event);
+        }
+    }
+
     fn update_state_from_result(
         &mut self,
         result: Result<(OAuth2Context, C::SessionState), OAuth2Error>,
+        operation: Operation,
     ) {
         match result {
             Ok((state, session_state)) => {
                 self.update_state(state, Some(session_state));
             }
             Err(err) => {
-                self.update_state(err.into(), None);
+                self.update_state(OAuth2Context::failed(operation, err), None);
+            }
+        }
+    }
+
+    /// The scheduled refresh is due.
+    fn scheduled_refresh(&mut self) {
+        let defer = self
+            .config
+            .as_ref()
+            .is_some_and(|config| config.hidden_refresh == HiddenRefreshPolicy::Defer);
+        if defer && document().hidden() {
+            log::debug!("Page is hidden, deferring refresh");
+            self.refresh_deferred = true;
+            return;
+        }
+        if !window().navigator().on_line() {
+            log::debug!("Browser is offline, deferring refresh");
+            self.refresh_deferred = true;
+            return;
+        }
+
+        self.refresh();
+    }
+
+    /// The page became visible, or hidden.
+    fn visibility_change(&mut self) {
+        if document().hidden() {
+            return;
+        }
+
+        if std::mem::take(&mut self.refresh_deferred) {
+            log::debug!("Page is visible again, performing deferred refresh");
+            self.scheduled_refresh();
+        } else if self.failed_refresh.is_none() {
+            // timers of hidden pages are throttled, the refresh may be overdue. Only when
+            // authenticated, as the timer may be waiting to retry the login otherwise.
+            if let Some(expires) = self.state.authentication().map(|auth| auth.expires) {
+                self.schedule_refresh(expires);
             }
         }
     }
 
-    async fn refresh(&mut self) {
+    /// Let the token vault refresh the session after the delay, instead of the agent.
+    #[cfg(feature = "worker")]
+    fn delegate_refresh(&self, delay: Duration) -> bool {
+        let (Some(vault), Some(refresh_token)) = (
+            self.client.as_ref().and_then(|client| client.token_vault()),
+            self.state
+                .authentication()
+                .and_then(|auth| auth.refresh_token.as_deref()),
+        ) else {
+            return false;
+        };
+
+        crate::worker::TokenVault::is_handle(refresh_token)
+            && vault.schedule_refresh(refresh_token, delay)
+    }
+
+    /// The token vault refreshed the session of a handle.
+    #[cfg(feature = "worker")]
+    fn vault_refreshed(&mut self, handle: String) {
+        let current = self
+            .state
+            .authentication()
+            .and_then(|auth| auth.refresh_token.as_deref());
+        if current == Some(handle.as_str()) {
+            log::debug!("Token vault refreshed the session");
+            // picks up the response of the vault
+            self.scheduled_refresh();
+        }
+    }
+
+    /// The browser is online again.
+    fn online(&mut self) {
+        if std::mem::take(&mut self.refresh_deferred) {
+            log::debug!("Browser is online again, performing deferred refresh");
+            self.scheduled_refresh();
+        }
+    }
+
+    /// Refresh the session, unless a refresh is already in flight.
+    fn refresh(&mut self) {
+        if self.refreshing.is_some() {
+            log::debug!("Refresh already in flight");
+            return;
+        }
+
+        if let (Some(client), Some((refresh_token, session_state))) =
+            (&self.client, self.failed_refresh.take())
+        {
+            // retrying a failed refresh
+            let client = client.clone();
+            self.exchange_refresh_token(client, refresh_token, session_state);
+            return;
+        }
+
         let (client, session_state) =
             if let (Some(client), Some(session_state)) = (&self.client, &self.session_state) {
                 (client.clone(), session_state.clone())
@@ -517,33 +1705,147 @@ where
             ..
         }) = &self.state
         {
-            log::debug!("Triggering refresh");
+            let refresh_token = refresh_token.clone();
+            self.exchange_refresh_token(client, refresh_token, session_state);
+        }
+    }
 
+    /// Start exchanging the refresh token, reporting the outcome back to the agent.
+    fn exchange_refresh_token(
+        &mut self,
+        client: C,
+        refresh_token: String,
+        session_state: C::SessionState,
+    ) {
+        log::debug!("Triggering refresh");
+
+        self.refreshing = Some(Vec::new());
+        let generation = self.generation;
+        let tx = self.tx.clone();
+        spawn_local(async move {
             let result = client
-                .exchange_refresh_token(refresh_token.clone(), session_state)
+                .exchange_refresh_token(refresh_token.clone(), session_state.clone())
                 .await;
+            let _ = tx
+                .send(Msg::Refreshed {
+                    generation,
+                    refresh_token,
+                    session_state,
+                    result,
+                })
+                .await;
+        });
+    }
 
-            if let Err(err) = &result {
-                log::warn!("Failed to refresh token: {err}");
+    /// The refresh in flight finished.
+    async fn refreshed(
+        &mut self,
+        generation: u64,
+        refresh_token: String,
+        session_state: C::SessionState,
+        result: Result<(OAuth2Context, C::SessionState), OAuth2Error>,
+    ) {
+        let waiting = self.refreshing.take().unwrap_or_default();
+
+        if generation == self.generation {
+            self.apply_refresh(refresh_token, session_state, result);
+        } else {
+            // e.g. logged out in the meantime
+            log::debug!("State changed while refreshing, discarding the outcome");
+        }
+
+        for (request, tx) in waiting {
+            let _ = tx.send(self.acquire_token(request).await);
+        }
+    }
+
+    fn apply_refresh(
+        &mut self,
+        refresh_token: String,
+        session_state: C::SessionState,
+        result: Result<(OAuth2Context, C::SessionState), OAuth2Error>,
+    ) {
+        self.record_outcome(Operation::Refresh, &result);
+
+        if let Err(err) = &result {
+            log::warn!("Failed to refresh token: {err}");
+            self.subscribers
+                .emit(AgentEvent::RefreshFailed(err.clone()));
+        }
+
+        if let Err(OAuth2Error::Network(_)) = &result {
+            if !window().navigator().on_line() {
+                // keep the current session, and try again once online
+                log::info!("Browser is offline, deferring refresh");
+                self.failed_refresh = Some((refresh_token, session_state));
+                self.refresh_deferred = true;
+                return;
             }
+        }
 
-            self.update_state_from_result(result);
+        if let Err(err) = &result {
+            let (policy, random) = self
+                .config
+                .as_ref()
+                .map(|config| (config.retry_policy, config.random.clone()))
+                .unwrap_or_default();
+            if let Some(delay) = policy.delay(self.retries).filter(|_| err.is_transient()) {
+                // keep the current session, and try again later
+                let delay = delay + random.duration(policy.jitter);
+                log::info!("Transient failure, retrying refresh in {delay:?}");
+                self.retries += 1;
+                self.failed_refresh = Some((refresh_token, session_state));
+                let tx = self.tx.clone();
+                self.timeout = Some(Clock::current().timeout(delay, move || {
+                    let _ = tx.try_send(Msg::Refresh);
+                }));
+                return;
+            }
+        }
+
+        let failed = result.is_err();
+        self.update_state_from_result(result, Operation::Refresh);
+        if failed {
+            // keep the refresh token, for retrying
+            self.failed_refresh = Some((refresh_token, session_state));
         }
     }
 
-    /// Extract the state from the query.
+    /// Retry the operation which failed.
+    async fn retry(&mut self) {
+        let Some(operation) = self.state.failed_operation() else {
+            log::debug!("Nothing to retry");
+            return;
+        };
+
+        log::info!("Retrying failed operation: {operation:?}");
+
+        match operation {
+            Operation::Discovery => {
+                if let Some(config) = self.configuration.clone() {
+                    self.configure(config).await;
+                }
+            }
+            Operation::Login | Operation::CodeExchange => {
+                if let Err(err) = self.start_login(None, None) {
+                    self.update_state(OAuth2Context::failed(Operation::Login, err), None);
+                }
+            }
+            Operation::Refresh => self.refresh(),
+        }
+    }
+
+    /// Extract the state from the query, or from the fragment (see [`ResponseMode::Fragment`]).
     fn find_query_state() -> Option<State> {
-        if let Ok(url) = Self::current_url() {
-            let query: HashMap<_, _> = url.query_pairs().collect();
+        let url = Self::current_url().ok()?;
 
-            Some(State {
-                code: query.get("code").map(ToString::to_string),
-                state: query.get("state").map(ToString::to_string),
-                error: query.get("error").map(ToString::to_string),
-            })
-        } else {
-            None
+        let state = State::from_params(url.query_pairs());
+        if state.is_response() {
+            return Some(state);
         }
+
+        // the fragment may also hold the route of a hash router
+        Some(State::from_fragment(url.fragment().unwrap_or_default()))
     }
 
     fn current_url() -> Result<Url, String> {
@@ -554,90 +1856,566 @@ where
         Url::parse(&href).map_err(|err| err.to_string())
     }
 
-    fn cleanup_url() {
-        if let Ok(mut url) = Self::current_url() {
-            url.set_query(None);
-            let state = history().state().unwrap_or(JsValue::NULL);
-            history()
-                .replace_state_with_url(&state, "", Some(url.as_str()))
-                .ok();
+    /// Clean up the URL after returning from the issuer, see [`UrlCleanupPolicy`].
+    fn cleanup_url(&mut self) {
+        let policy = self
+            .config
+            .as_ref()
+            .map(|config| config.url_cleanup.clone())
+            .unwrap_or_default();
+        let Some(url) = Self::current_url()
+            .ok()
+            .and_then(|url| policy.cleanup.apply(&url))
+        else {
+            return;
+        };
+
+        match policy.navigation {
+            CleanupNavigation::Replace => {
+                let state = history().state().unwrap_or(JsValue::NULL);
+                history()
+                    .replace_state_with_url(&state, "", Some(url.as_str()))
+                    .ok();
+            }
+            // navigating right away would abort handling the response
+            CleanupNavigation::Navigate => self.pending_navigation = Some(url.to_string()),
+        }
+    }
+
+    /// Navigate to the cleaned up URL, if navigating is pending.
+    fn navigate_after_cleanup(&mut self) {
+        if let Some(url) = self.pending_navigation.take() {
+            log::debug!("Navigating to the cleaned up URL: {url}");
+            if let Err(err) = client::navigate(&url, true) {
+                log::warn!("Failed to navigate to the cleaned up URL: {err:?}");
+            }
         }
     }
 
     async fn configure(&mut self, config: AgentConfiguration<C>) {
-        self.configured(Self::make_client(config).await).await;
+        config.clock.install();
+        self.configuration = Some(config.clone());
+        self.recorder.clone_from(&config.recorder);
+
+        if matches!(self.state, OAuth2Context::NotInitialized) {
+            // don't wait for the issuer, when there is a session to restore
+            if !self.bootstrap_session(&config) {
+                self.restore_session(&config);
+            }
+        }
+
+        let outcome = Self::make_client(config).await;
+        self.record_outcome(Operation::Discovery, &outcome);
+        self.configured(outcome).await;
     }
 
-    fn start_login(&mut self, options: Option<LoginOptions>) -> Result<(), OAuth2Error> {
+    /// Start the login, optionally requesting additional scopes and a different audience.
+    fn start_login(
+        &mut self,
+        options: Option<LoginOptions>,
+        token: Option<TokenKey>,
+    ) -> Result<(), OAuth2Error> {
+        // navigating to the issuer replaces navigating to the cleaned up URL
+        self.pending_navigation = None;
         let client = self.client.as_ref().ok_or(OAuth2Error::NotInitialized)?;
-        let config = self.config.as_ref().ok_or(OAuth2Error::NotInitialized)?;
+        let mut config = self
+            .config
+            .as_ref()
+            .ok_or(OAuth2Error::NotInitialized)?
+            .clone();
 
         let options =
             options.unwrap_or_else(|| config.default_login_options.clone().unwrap_or_default());
 
+        let default_callback = config
+            .default_login_options
+            .as_ref()
+            .and_then(|opts| opts.app_state_callback.as_ref());
+        if options.app_state_callback.is_some()
+            && options.app_state_callback.as_ref() != default_callback
+        {
+            return Err(OAuth2Error::StartLogin(
+                "the app state callback must be set on the default login options".into(),
+            ));
+        }
+
+        metrics::record(FunnelStage::Started, &config.scope, config.metrics.as_ref());
+
+        if let Some(scopes) = &options.scopes {
+            config.scopes.clone_from(scopes);
+        }
+
+        if let Some(token) = token {
+            config.scopes.extend(token.scopes);
+            config.audience = token.audience.or(config.audience);
+        }
+
         let current_url = Self::current_url().map_err(OAuth2Error::StartLogin)?;
+        let authorization_params = options.authorization_params();
 
-        // take the parameter value first, then the agent configured value, then fall back to the default
+        // take the parameter value first, then the agent configured value, then the client
+        // configured value, then derive it from the public path or the current URL
         let redirect_url = options
             .redirect_url
+            .clone()
             .or_else(|| {
                 config
                     .default_login_options
                     .as_ref()
                     .and_then(|opts| opts.redirect_url.clone())
             })
-            .unwrap_or_else(|| current_url.clone());
+            .or_else(|| client.redirect_url())
+            .map(Ok)
+            .or_else(|| {
+                let public_path = config.public_path.as_deref()?;
+                Some(public_url(&current_url, public_path).map_err(OAuth2Error::StartLogin))
+            })
+            .transpose()?
+            .unwrap_or_else(|| {
+                // a redirect URL must not have a fragment, it is restored after the login instead
+                let mut redirect_url = current_url.clone();
+                redirect_url.set_fragment(None);
+                redirect_url
+            });
+
+        // the full URL (path, query, and fragment), for returning to it after the login
+        let post_login_url = options.post_login_url.clone().unwrap_or(current_url);
+        SessionStorage::set(self.scope().key(STORAGE_KEY_POST_LOGIN_URL), post_login_url)
+            .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
 
-        if redirect_url != current_url {
-            SessionStorage::set(STORAGE_KEY_POST_LOGIN_URL, current_url)
-                .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
+        match &options.app_state {
+            Some(app_state) => {
+                SessionStorage::set(self.scope().key(STORAGE_KEY_APP_STATE), app_state)
+                    .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?
+            }
+            None => SessionStorage::delete(self.scope().key(STORAGE_KEY_APP_STATE)),
         }
 
-        let login_context = client.make_login_context(config, redirect_url.clone())?;
+        let login_context = client.make_login_context(&config, redirect_url.clone(), &options);
+        self.record_outcome(Operation::Login, &login_context);
+        let login_context = login_context?;
 
-        SessionStorage::set(STORAGE_KEY_CSRF_TOKEN, login_context.csrf_token)
-            .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
+        SessionStorage::set(
+            self.scope().key(STORAGE_KEY_CSRF_TOKEN),
+            login_context.csrf_token,
+        )
+        .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
 
-        SessionStorage::set(STORAGE_KEY_LOGIN_STATE, login_context.state)
-            .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
+        SessionStorage::set(
+            self.scope().key(STORAGE_KEY_LOGIN_STATE),
+            login_context.state,
+        )
+        .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
 
-        SessionStorage::set(STORAGE_KEY_REDIRECT_URL, redirect_url)
+        SessionStorage::set(self.scope().key(STORAGE_KEY_REDIRECT_URL), redirect_url)
             .map_err(|err| OAuth2Error::StartLogin(err.to_string()))?;
 
         let mut login_url = login_context.url;
 
-        login_url.query_pairs_mut().extend_pairs(options.query);
+        // the options take precedence over parameters of the client configuration
+        let configured = login_url
+            .query_pairs()
+            .into_owned()
+            .filter(|(key, _)| !authorization_params.iter().any(|(name, _)| name == key))
+            .collect::<Vec<_>>();
+
+        login_url
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(configured)
+            .extend_pairs(authorization_params)
+            .extend_pairs(options.query);
 
         // the next call will most likely navigate away from this page
 
-        window()
-            .location()
-            .set_href(login_url.as_str())
-            .map_err(|err| {
-                OAuth2Error::StartLogin(
-                    err.as_string()
-                        .unwrap_or_else(|| "Unable to navigate to login page".to_string()),
-                )
-            })?;
+        client::navigate(login_url.as_str(), false).map_err(|err| {
+            OAuth2Error::StartLogin(
+                err.as_string()
+                    .unwrap_or_else(|| "Unable to navigate to login page".to_string()),
+            )
+        })?;
+
+        metrics::record(
+            FunnelStage::Redirected,
+            &config.scope,
+            config.metrics.as_ref(),
+        );
 
         Ok(())
     }
 
+    fn account_url(&self, return_url: Option<Url>) -> Option<Url> {
+        let client = self.client.as_ref()?;
+        let return_url = match return_url {
+            Some(return_url) => return_url,
+            None => Self::current_url().ok()?,
+        };
+        client.account_url(return_url)
+    }
+
+    /// Acquire a token for a set of scopes and an audience, once the session is fresh.
+    ///
+    /// While a refresh is in flight, requests wait for its outcome. Instead of using the same
+    /// refresh token in parallel, which would break refresh token rotation.
+    async fn request_token(&mut self, request: TokenRequest, tx: TokenSender) {
+        if let (OAuth2Context::Authenticated(_), Some(config)) = (&self.state, &self.config) {
+            if config
+                .expiry
+                .is_soft_expired_in(self.deadline.map(|d| d.remaining()))
+            {
+                // don't start new requests with a token which is about to expire
+                log::debug!("Session token about to expire, refreshing first");
+                self.refresh();
+            }
+        }
+
+        if let Some(waiting) = &mut self.refreshing {
+            waiting.push((request, tx));
+            return;
+        }
+
+        let _ = tx.send(self.acquire_token(request).await);
+    }
+
+    /// Acquire a token for a set of scopes and an audience.
+    async fn acquire_token(&mut self, request: TokenRequest) -> Result<AccessToken, Error> {
+        let (Some(client), Some(config)) = (&self.client, &self.config) else {
+            return Err(Error::Failed(OAuth2Error::NotInitialized));
+        };
+
+        let client = client.clone();
+        let refresh_tokens = config.refresh_tokens;
+        let session_key = TokenKey::new(config.scopes.clone(), config.audience.clone());
+        let key = TokenKey::new(
+            match request.scopes.is_empty() {
+                true => config.scopes.clone(),
+                false => request.scopes,
+            },
+            request.audience.or_else(|| config.audience.clone()),
+        );
+
+        let (refresh_token, session_state) = match (&self.state, &self.session_state) {
+            (OAuth2Context::Authenticated(auth), Some(session_state)) => {
+                if key == session_key
+                    && Self::is_for_audience(&auth.access_token, &key)
+                    && !config
+                        .expiry
+                        .is_hard_expired_in(self.deadline.map(|d| d.remaining()))
+                {
+                    // the token of the session
+                    return Ok(AccessToken {
+                        access_token: auth.access_token.clone(),
+                        expires: auth.expires,
+                    });
+                }
+
+                if let Some(token) = self
+                    .tokens
+                    .get(&key, config.grace_period, &config.expiry)
+                    .filter(|token| Self::is_for_audience(&token.access_token, &key))
+                {
+                    return Ok(token.clone());
+                }
+
+                (auth.refresh_token.clone(), Some(session_state.clone()))
+            }
+            _ => (None, None),
+        };
+
+        // a refresh token bound to the request, issued when acquiring a token for it before
+        let bound_refresh_token = match refresh_tokens {
+            RefreshTokenPolicy::Shared => None,
+            RefreshTokenPolicy::PerAudience => self.tokens.refresh_token(&key).cloned(),
+        };
+
+        let mut result = Err(OAuth2Error::NotInitialized);
+        if let Some(session_state) = session_state {
+            if let Some(bound_refresh_token) = bound_refresh_token {
+                log::debug!("Acquiring token for: {key:?}, using its refresh token");
+                result = client
+                    .exchange_refresh_token_for(
+                        bound_refresh_token,
+                        session_state.clone(),
+                        &key.scopes,
+                        key.audience.as_deref(),
+                    )
+                    .await;
+                if let Err(err) = &result {
+                    log::info!("Refresh token of {key:?} was rejected, using the session's: {err}");
+                    self.tokens.remove_refresh_token(&key);
+                }
+            }
+
+            if let (Err(_), Some(refresh_token)) = (&result, refresh_token) {
+                log::debug!("Acquiring token for: {key:?}");
+                result = client
+                    .exchange_refresh_token_for(
+                        refresh_token,
+                        session_state,
+                        &key.scopes,
+                        key.audience.as_deref(),
+                    )
+                    .await;
+            }
+        }
+
+        match result {
+            Ok(auth) if !Self::is_for_audience(&auth.access_token, &key) => {
+                // never hand out a token for a different API
+                log::warn!("Acquired token is not issued for the audience: {key:?}");
+                Err(Error::Failed(OAuth2Error::validation(format!(
+                    "token is not issued for the audience: {}",
+                    key.audience.as_deref().unwrap_or_default()
+                ))))
+            }
+            Ok(auth) => {
+                let token = AccessToken {
+                    access_token: auth.access_token,
+                    expires: auth.expires,
+                };
+                if let Some(refresh_token) = auth.refresh_token {
+                    match refresh_tokens {
+                        RefreshTokenPolicy::Shared => self.rotate_refresh_token(refresh_token),
+                        RefreshTokenPolicy::PerAudience => {
+                            self.tokens.insert_refresh_token(key.clone(), refresh_token)
+                        }
+                    }
+                }
+                self.tokens.insert(key, token.clone());
+                Ok(token)
+            }
+            Err(err) => {
+                log::info!("Unable to silently acquire token: {err}");
+                if request.interactive {
+                    self.start_login(None, Some(key)).map_err(Error::Failed)?;
+                }
+                Err(Error::InteractionRequired)
+            }
+        }
+    }
+
+    /// Check if the token was issued for the audience of the key, if it has one.
+    fn is_for_audience(access_token: &str, key: &TokenKey) -> bool {
+        key.audience
+            .as_deref()
+            .map_or(true, |audience| matches_audience(access_token, audience))
+    }
+
+    /// Apply a refresh token, which was rotated while acquiring a token.
+    fn rotate_refresh_token(&mut self, refresh_token: String) {
+        if let OAuth2Context::Authenticated(auth) = &self.state {
+            if auth.refresh_token.as_ref() != Some(&refresh_token) {
+                let state = OAuth2Context::Authenticated(Authentication {
+                    refresh_token: Some(refresh_token),
+                    ..auth.clone()
+                });
+                self.update_state(state, self.session_state.clone());
+            }
+        }
+    }
+
+    /// Check if the issuer is reachable, reporting the outcome back to the agent.
+    fn health_check(&self) {
+        let Some(url) = self
+            .client
+            .as_ref()
+            .and_then(|client| client.health_check_url())
+        else {
+            return;
+        };
+
+        let tx = self.tx.clone();
+        spawn_local(async move {
+            let health = match reqwest::Client::new().get(url).send().await {
+                Ok(_) => IssuerHealth::Reachable,
+                Err(err) => {
+                    log::info!("Issuer health check failed: {err}");
+                    IssuerHealth::Unreachable
+                }
+            };
+            let _ = tx.try_send(Msg::Health(health));
+        });
+    }
+
+    /// Ping the backend, if the session is authenticated and the page is visible.
+    fn keep_alive(&self) {
+        let Some(keep_alive) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.keep_alive.clone())
+        else {
+            return;
+        };
+        let Some(access_token) = self.state.access_token().map(ToString::to_string) else {
+            return;
+        };
+        if document().hidden() {
+            return;
+        }
+
+        let tx = self.tx.clone();
+        spawn_local(async move {
+            if keep_alive.ping(&access_token).await {
+                log::info!("Backend rejected the session");
+                let _ = tx.try_send(Msg::Unauthorized);
+            }
+        });
+    }
+
+    fn update_health(&mut self, health: IssuerHealth) {
+        if self.health != health {
+            self.health = health;
+            if let Some(callback) = &self.health_callback {
+                callback.emit(health);
+            }
+        }
+    }
+
+    /// An unauthorized response was reported.
+    fn unauthorized(&mut self) {
+        if !matches!(self.state, OAuth2Context::Authenticated(..)) {
+            return;
+        }
+
+        self.unauthorized += 1;
+        log::debug!("Unauthorized responses: {}", self.unauthorized);
+
+        let policy = self
+            .config
+            .as_ref()
+            .map(|config| config.unauthorized_policy)
+            .unwrap_or_default();
+
+        match policy {
+            UnauthorizedPolicy::Ignore => {}
+            UnauthorizedPolicy::Logout { threshold } if self.unauthorized >= threshold => {
+                log::info!("Logging out, due to unauthorized responses");
+                let options = self
+                    .config
+                    .as_ref()
+                    .and_then(|config| config.default_logout_options.clone())
+                    .unwrap_or_default()
+                    .with_reason(LogoutReason::Revoked);
+                self.logout_opts(Some(options));
+            }
+            UnauthorizedPolicy::Invalidate { threshold } if self.unauthorized >= threshold => {
+                log::info!("Invalidating session, due to unauthorized responses");
+                self.update_state(
+                    OAuth2Context::NotAuthenticated {
+                        reason: Reason::Invalid,
+                    },
+                    None,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Listen for logouts announced by other tabs of the application.
+    fn listen_logout(&self, scope: &AppScope) -> EventListener {
+        let key = scope.key(STORAGE_KEY_LOGOUT);
+        let tx = self.tx.clone();
+        EventListener::new(&window(), "storage", move |event| {
+            let Some(event) = event.dyn_ref::<StorageEvent>() else {
+                return;
+            };
+            if event.key().as_deref() != Some(key.as_str()) {
+                return;
+            }
+            let Some(value) = event.new_value() else {
+                return;
+            };
+            // the value is the time of the logout, and the reason
+            let reason = serde_json::from_str::<(f64, LogoutReason)>(&value)
+                .map(|(_, reason)| reason)
+                .unwrap_or_default();
+            let _ = tx.try_send(Msg::RemoteLogout(reason));
+        })
+    }
+
+    /// Listen for the page becoming visible, or hidden.
+    fn listen_visibility(&self) -> EventListener {
+        let tx = self.tx.clone();
+        EventListener::new(&document(), "visibilitychange", move |_| {
+            let _ = tx.try_send(Msg::VisibilityChange);
+        })
+    }
+
+    /// Listen for the browser going online again.
+    fn listen_online(&self) -> EventListener {
+        let tx = self.tx.clone();
+        EventListener::new(&window(), "online", move |_| {
+            let _ = tx.try_send(Msg::Online);
+        })
+    }
+
+    /// Another tab of the application logged out.
+    fn remote_logout(&mut self, reason: LogoutReason) {
+        if !matches!(self.state, OAuth2Context::Authenticated(..)) {
+            return;
+        }
+
+        log::info!("Logged out by another tab: {reason:?}");
+        self.update_state(
+            OAuth2Context::NotAuthenticated {
+                reason: Reason::Logout(reason),
+            },
+            None,
+        );
+    }
+
     fn logout_opts(&mut self, options: Option<LogoutOptions>) {
-        if let Some(client) = &self.client {
-            if let Some(session_state) = self.session_state.clone() {
-                // let the client know that log out, clients may navigate to a different
-                // page
-                log::debug!("Notify client of logout");
-                let options = options
-                    .or_else(|| {
-                        self.config
-                            .as_ref()
-                            .and_then(|config| config.default_logout_options.clone())
-                    })
-                    .unwrap_or_default();
-                client.logout(session_state, options);
+        if self.logout_pending {
+            // e.g. two components triggering the logout
+            log::debug!("Logout already in progress, ignoring");
+            return;
+        }
+
+        let options = options
+            .or_else(|| {
+                self.config
+                    .as_ref()
+                    .and_then(|config| config.default_logout_options.clone())
+            })
+            .unwrap_or_default();
+
+        let reason = options.reason;
+
+        // storage writes are synchronous, so this is complete before navigating away, and
+        // other tabs will receive the announcement
+        let scope = self.scope();
+        for key in [
+            STORAGE_KEY_CSRF_TOKEN,
+            STORAGE_KEY_LOGIN_STATE,
+            STORAGE_KEY_POST_LOGIN_URL,
+            STORAGE_KEY_APP_STATE,
+            STORAGE_KEY_LOGIN_RETRIES,
+            STORAGE_KEY_REPLAY,
+        ] {
+            SessionStorage::delete(scope.key(key));
+        }
+        if let Err(err) = LocalStorage::set(
+            scope.key(STORAGE_KEY_LOGOUT),
+            (Clock::current().now(), options.reason),
+        ) {
+            log::warn!("Failed to announce logout: {err}");
+        }
+
+        if options.local_only {
+            log::debug!("Local logout only");
+        } else if let (Some(client), Some(session_state)) =
+            (self.client.clone(), self.session_state.clone())
+        {
+            // let the client know that log out, clients may navigate to a different
+            // page
+            log::debug!("Notify client of logout");
+            if let Err(err) =
+                SessionStorage::set(scope.key(STORAGE_KEY_LOGOUT_REASON), options.reason)
+            {
+                log::warn!("Failed to store logout reason: {err}");
             }
+            self.logout_pending = true;
+            client.logout(session_state, options);
         }
 
         // There is a bug in yew, which panics during re-rendering, which might be triggered
@@ -645,13 +2423,14 @@ where
         // cause the application to navigate to a different page.
         self.update_state(
             OAuth2Context::NotAuthenticated {
-                reason: Reason::Logout,
+                reason: Reason::Logout(reason),
             },
             None,
         );
     }
 }
 
+#[async_trait(?Send)]
 impl<C> OAuth2Operations<C> for Agent<C>
 where
     C: Client,
@@ -685,4 +2464,62 @@ where
             .try_send(Msg::Logout(Some(options)))
             .map_err(|_| Error::NoAgent)
     }
+
+    fn report_unauthorized(&self) -> Result<(), Error> {
+        self.tx
+            .try_send(Msg::Unauthorized)
+            .map_err(|_| Error::NoAgent)
+    }
+
+    async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .try_send(Msg::AccountUrl(return_url, tx))
+            .map_err(|_| Error::NoAgent)?;
+        rx.await.map_err(|_| Error::NoAgent)
+    }
+
+    async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .try_send(Msg::AcquireToken(request, tx))
+            .map_err(|_| Error::NoAgent)?;
+        rx.await.map_err(|_| Error::NoAgent)?
+    }
+
+    async fn login_funnel(&self) -> Result<LoginFunnel, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.tx
+            .try_send(Msg::LoginFunnel(tx))
+            .map_err(|_| Error::NoAgent)?;
+        rx.await.map_err(|_| Error::NoAgent)
+    }
+
+    fn retry(&self) -> Result<(), Error> {
+        self.tx.try_send(Msg::Retry).map_err(|_| Error::NoAgent)
+    }
+
+    fn refresh(&self) -> Result<(), Error> {
+        self.tx
+            .try_send(Msg::RefreshSession)
+            .map_err(|_| Error::NoAgent)
+    }
+
+    fn subscribe(&self, callback: AgentCallback<AgentEvent>) -> Result<EventSubscription, Error> {
+        let id = Subscribers::next_id();
+        self.tx
+            .try_send(Msg::Subscribe(id, callback))
+            .map_err(|_| Error::NoAgent)?;
+        let tx = self.tx.clone();
+        Ok(EventSubscription::new(move || {
+            let _ = tx.try_send(Msg::Unsubscribe(id));
+        }))
+    }
+
+    fn stream(&self) -> ContextStream {
+        let (tx, rx) = unbounded_channel();
+        // if the agent is gone, the stream ends right away
+        let _ = self.tx.try_send(Msg::Watch(tx));
+        ContextStream::new(rx)
+    }
 }
@@ -1,24 +1,44 @@
-use super::{AgentConfiguration, Client, LoginOptions, LogoutOptions};
+use super::{
+    AccessToken, AgentCallback, AgentConfiguration, AgentEvent, Client, ContextStream,
+    EventSubscription, LoginFunnel, LoginOptions, LogoutOptions, OAuth2Error, TokenRequest,
+};
+use async_trait::async_trait;
+use reqwest::Url;
 use std::fmt::{Display, Formatter};
+use tokio::sync::mpsc::unbounded_channel;
 
 /// Operation error
 #[derive(Clone, Debug)]
 pub enum Error {
     /// The agent cannot be reached.
     NoAgent,
+    /// The request cannot be fulfilled without the user logging in.
+    InteractionRequired,
+    /// The agent failed to process the request.
+    Failed(OAuth2Error),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NoAgent => write!(f, "no agent"),
+            Self::InteractionRequired => write!(f, "interaction required"),
+            Self::Failed(err) => write!(f, "failed: {err}"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+}
 
 /// Operations for the OAuth2 agent
+#[async_trait(?Send)]
 pub trait OAuth2Operations<C: Client> {
     /// Configure the agent with a configuration.
     ///
@@ -26,14 +46,113 @@ pub trait OAuth2Operations<C: Client> {
     fn configure(&self, config: AgentConfiguration<C>) -> Result<(), Error>;
 
     /// Start a login flow with default options.
+    ///
+    /// This is a convenience for [`Self::start_login_opts`], using the login options configured
+    /// on the [`crate::components::context::OAuth2`] component.
     fn start_login(&self) -> Result<(), Error>;
 
     /// Start a login flow.
+    ///
+    /// The options replace the configured login options, allowing to override e.g. the redirect
+    /// URL, the scopes, or the URL to return to after the login.
     fn start_login_opts(&self, options: LoginOptions) -> Result<(), Error>;
 
     /// Trigger the logout with default options.
+    ///
+    /// Other tabs of the application get notified, and end their session too. While navigating
+    /// to the issuer for ending the session, further logouts are ignored.
     fn logout(&self) -> Result<(), Error>;
 
     /// Trigger the logout.
     fn logout_opts(&self, options: LogoutOptions) -> Result<(), Error>;
+
+    /// Report that the access token was rejected as unauthorized.
+    ///
+    /// This should only be called after refreshing the token did not help. The agent will act
+    /// according to the configured [`super::UnauthorizedPolicy`].
+    fn report_unauthorized(&self) -> Result<(), Error>;
+
+    /// Get the URL of the issuer's account management console.
+    ///
+    /// The console will be asked to link back to `return_url`, or the current URL if `None`.
+    /// Returns `None` if the client has no account management URL configured, or is not
+    /// initialized yet.
+    async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error>;
+
+    /// Silently acquire an access token for a set of scopes and an audience.
+    ///
+    /// Tokens different to the one of the session are acquired using the session's refresh
+    /// token, and cached until they expire. If that isn't possible, the error
+    /// [`Error::InteractionRequired`] is returned, and a login is started if the request asked for
+    /// this.
+    ///
+    /// If the request has an audience, the `aud` claim of the token is verified before returning
+    /// it, if the token is a JWT. A token not issued for the audience is never returned, so that
+    /// an HTTP integration doesn't send e.g. a token of the issuer to a custom API.
+    ///
+    /// A token which can no longer be used for new requests, according to the
+    /// [`super::ExpiryPolicy`], is refreshed before returning it. While a refresh is in flight,
+    /// all requests wait for it to finish, instead of starting refreshes of their own.
+    async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error>;
+
+    /// Get the persisted counters of the login funnel.
+    ///
+    /// The counters are kept across sessions of the browser, and allow measuring how many logins
+    /// get abandoned before being completed.
+    async fn login_funnel(&self) -> Result<LoginFunnel, Error>;
+
+    /// Retry the operation which failed.
+    ///
+    /// If the context is [`crate::context::OAuth2Context::Failed`], this re-triggers the failed
+    /// [`crate::context::Operation`], without reloading the page. Otherwise, it does nothing.
+    fn retry(&self) -> Result<(), Error>;
+
+    /// Refresh the session now, without waiting for it to expire.
+    ///
+    /// If the context is not [`crate::context::OAuth2Context::Authenticated`], or a refresh is
+    /// already in flight, this does nothing.
+    ///
+    /// The default implementation reports [`Error::NoAgent`], for implementations which can't
+    /// refresh the session.
+    fn refresh(&self) -> Result<(), Error> {
+        Err(Error::NoAgent)
+    }
+
+    /// Subscribe to the events of the lifecycle of the session.
+    ///
+    /// The callback is invoked for each [`AgentEvent`], until the returned subscription is
+    /// dropped.
+    ///
+    /// The default implementation reports [`Error::NoAgent`], for implementations without
+    /// events.
+    fn subscribe(&self, callback: AgentCallback<AgentEvent>) -> Result<EventSubscription, Error> {
+        let _ = callback;
+        Err(Error::NoAgent)
+    }
+
+    /// Get a stream of the context.
+    ///
+    /// The first item is the current context, followed by each change of it. This allows async
+    /// tasks to await changes of the session, without the need for callbacks:
+    ///
+    /// ```rust
+    /// use futures::StreamExt;
+    /// use yew_oauth2::agent::{Client, OAuth2Operations};
+    ///
+    /// async fn await_login<C: Client>(agent: impl OAuth2Operations<C>) -> Option<String> {
+    ///     let mut stream = agent.stream();
+    ///     while let Some(context) = stream.next().await {
+    ///         if let Some(access_token) = context.access_token() {
+    ///             return Some(access_token.to_string());
+    ///         }
+    ///     }
+    ///     None
+    /// }
+    /// ```
+    ///
+    /// The default implementation returns a stream, which ends right away.
+    fn stream(&self) -> ContextStream {
+        let (_, rx) = unbounded_channel();
+        ContextStream::new(rx)
+    }
 }
@@ -0,0 +1,581 @@
+//! Policies, controlling the behavior of the agent.
+
+use super::{
+    state::{split_fragment, State},
+    Clock,
+};
+use oauth2::url::form_urlencoded;
+use reqwest::Url;
+use std::time::Duration;
+
+/// Handling of unauthorized responses, reported to the agent.
+///
+/// HTTP integrations can report responses which were rejected as unauthorized (e.g. `401`) using
+/// [`super::OAuth2Operations::report_unauthorized`], after they already tried refreshing the
+/// token. Once the number of consecutive reports reaches the threshold, the agent will act
+/// according to this policy. A new access token resets the counter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnauthorizedPolicy {
+    /// Ignore the reports.
+    #[default]
+    Ignore,
+    /// Log out, like when calling [`super::OAuth2Operations::logout`].
+    Logout {
+        /// Number of consecutive reports
+        threshold: u32,
+    },
+    /// Mark the session as invalid, switching to
+    /// [`crate::context::Reason::Invalid`], keeping the session of the issuer.
+    Invalidate {
+        /// Number of consecutive reports
+        threshold: u32,
+    },
+}
+
+/// Handling of authorization responses, which don't match the pending login.
+///
+/// This happens if the `state` sent back by the issuer is not the one of the pending login, or
+/// there is no pending login, e.g. after clearing the storage of the browser or copying the URL
+/// to a different browser.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownStatePolicy {
+    /// Fail with [`super::OAuth2Error::UnknownState`].
+    #[default]
+    Fail,
+    /// Ignore the response, continuing without a session.
+    Ignore,
+    /// Start a new login.
+    Login,
+}
+
+/// Thresholds before the expiration of an access token.
+///
+/// A token which expires within the soft threshold is not used for starting new requests anymore.
+/// Instead, the agent refreshes the session (or acquires a new token) first. Requests which are
+/// already in flight may continue using it, until the hard threshold, after which the token is
+/// considered expired for everything. This prevents sending requests with a token which expires
+/// while the request is in transit.
+///
+/// Refreshing the session is scheduled before the soft threshold, at the latest.
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExpiryPolicy {
+    /// The time before the expiration, after which a token is not used for new requests.
+    pub soft: Duration,
+    /// The time before the expiration, after which a token is not used at all.
+    pub hard: Duration,
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        Self {
+            soft: Duration::from_secs(10),
+            hard: Duration::ZERO,
+        }
+    }
+}
+
+impl ExpiryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the soft threshold
+    pub fn with_soft(mut self, soft: Duration) -> Self {
+        self.soft = soft;
+        self
+    }
+
+    /// Set the hard threshold
+    pub fn with_hard(mut self, hard: Duration) -> Self {
+        self.hard = hard;
+        self
+    }
+
+    /// Check if a token with the expiration (in seconds) must not be used for new requests.
+    pub fn is_soft_expired(&self, expires: Option<u64>) -> bool {
+        self.is_soft_expired_in(remaining(expires))
+    }
+
+    /// Check if a token with the expiration (in seconds) must not be used at all.
+    pub fn is_hard_expired(&self, expires: Option<u64>) -> bool {
+        self.is_hard_expired_in(remaining(expires))
+    }
+
+    /// Check if a token, expiring in the remaining time, must not be used for new requests.
+    pub(crate) fn is_soft_expired_in(&self, remaining: Option<Duration>) -> bool {
+        remaining.is_some_and(|remaining| remaining <= self.soft.max(self.hard))
+    }
+
+    /// Check if a token, expiring in the remaining time, must not be used at all.
+    pub(crate) fn is_hard_expired_in(&self, remaining: Option<Duration>) -> bool {
+        remaining.is_some_and(|remaining| remaining <= self.hard)
+    }
+}
+
+/// The time remaining until the expiration (in seconds), using the wall clock.
+fn remaining(expires: Option<u64>) -> Option<Duration> {
+    let now = Clock::current().now_secs();
+    expires.map(|expires| Duration::from_secs_f64((expires as f64 - now).max(0f64)))
+}
+
+/// When to refresh the session, before the access token expires.
+///
+/// A fixed duration suits tokens of a similar lifetime. A percentage of the lifetime adapts to
+/// short-lived tokens, which would otherwise be refreshed far too often, as well as to long-lived
+/// ones.
+///
+/// In any case, the session is refreshed before the soft threshold of the [`ExpiryPolicy`]. But
+/// not before a tenth of the lifetime of the token has passed, so that a token which would be due
+/// right away, e.g. refreshing more than its lifetime before the expiration, isn't refreshed in a
+/// loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefreshAhead {
+    /// Refresh the duration before the expiration.
+    Before(Duration),
+    /// Refresh once the percentage (`0` to `100`) of the lifetime of the token has passed.
+    Lifetime(u8),
+}
+
+impl RefreshAhead {
+    /// The time before the expiration, at which a token with the lifetime is refreshed.
+    pub fn before(&self, lifetime: Duration) -> Duration {
+        let before = match self {
+            Self::Before(before) => *before,
+            Self::Lifetime(percent) => {
+                lifetime.mul_f64(f64::from(100u8.saturating_sub(*percent)) / 100f64)
+            }
+        };
+        before.min(max_refresh_ahead(lifetime))
+    }
+}
+
+/// The longest time before the expiration, at which a token with the lifetime is refreshed.
+pub(crate) fn max_refresh_ahead(lifetime: Duration) -> Duration {
+    lifetime.mul_f64(0.9)
+}
+
+/// Refreshing the session while the page is hidden, e.g. in a background tab.
+///
+/// Browsers throttle the timers of hidden pages, so a scheduled refresh may fire late, or not
+/// at all. Whenever the page becomes visible again, the agent checks the expiration of the
+/// session, and refreshes it right away if required.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HiddenRefreshPolicy {
+    /// Defer a scheduled refresh until the page becomes visible again.
+    ///
+    /// This keeps background tabs from using up refresh tokens. Tokens of a hidden page may expire
+    /// in the meantime.
+    #[default]
+    Defer,
+    /// Refresh as scheduled, like when the page is visible.
+    Refresh,
+}
+
+/// Persisting the session in the storage of the browser.
+///
+/// A persisted session, which is not expired yet, is restored immediately when the application
+/// starts. So that returning users see their content without waiting for the issuer. The
+/// restored session is then revalidated in the background, by refreshing it, and only
+/// downgraded if that fails.
+///
+/// **NOTE:** Persisting the session makes its tokens available to any script running on the
+/// origin, and keeps them beyond the lifetime of the page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SessionPersistence {
+    /// Don't persist the session.
+    #[default]
+    None,
+    /// Persist the session for the browser tab, using the session storage.
+    Tab,
+    /// Persist the session for the browser, using the local storage.
+    Browser,
+}
+
+/// Handling of refresh tokens, issued when acquiring additional access tokens.
+///
+/// When acquiring an access token for different scopes or audiences (see
+/// [`super::TokenRequest`]), the issuer may respond with a refresh token as well.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefreshTokenPolicy {
+    /// The issuer has a single refresh token for the session.
+    ///
+    /// A refresh token in the response is a rotated refresh token, replacing the one of the
+    /// session.
+    #[default]
+    Shared,
+    /// The issuer binds refresh tokens to the audience and scopes of the request.
+    ///
+    /// A refresh token in the response is kept for the request, and used for acquiring further
+    /// tokens of it, leaving the one of the session alone. If it is rejected, the refresh token
+    /// of the session is used again.
+    PerAudience,
+}
+
+/// Retrying when the issuer is temporarily unavailable.
+///
+/// When the authorization or token endpoint responds with `temporarily_unavailable`, the agent
+/// will retry the operation, waiting an exponentially increasing delay between attempts. Refreshing
+/// the session is also retried when the issuer cannot be reached at all (see
+/// [`super::OAuth2Error::is_transient`]). While retrying a login, the context is
+/// [`crate::context::Reason::TemporarilyUnavailable`]. While retrying a refresh, the current
+/// session is kept. Once all attempts are exhausted, the context fails, with the error of the last
+/// attempt.
+///
+/// A random jitter, up to the configured maximum, is added to each delay. So that many clients,
+/// failing at the same time, don't retry at the same time as well.
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// The maximum number of retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub initial_delay: Duration,
+    /// The maximum delay between two retries.
+    pub max_delay: Duration,
+    /// The maximum random delay, added to each retry.
+    pub jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A policy which never retries.
+    pub fn none() -> Self {
+        Self::default().with_max_attempts(0)
+    }
+
+    /// Set the maximum number of retries
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Set the maximum delay between two retries
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set the maximum random delay, added to each retry
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay before the next retry, or `None` if there should be no further retry.
+    ///
+    /// The `attempt` is the number of retries performed so far. The delay doesn't include the
+    /// jitter.
+    pub fn delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let delay = self
+            .initial_delay
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_delay);
+
+        Some(delay.min(self.max_delay))
+    }
+}
+
+/// Which part of the URL to remove, after returning from the issuer.
+///
+/// With [`Self::Query`] and [`Self::OAuth2Params`], the URL active when starting the login is
+/// restored afterwards, see [`super::LoginOptions`]. Otherwise, the application chose where to
+/// end up, and only the post-login redirect callback is invoked.
+///
+/// ```rust
+/// use reqwest::Url;
+/// use yew_oauth2::agent::UrlCleanup;
+///
+/// let url = Url::parse("https://app/items?page=2&code=abc&state=xyz").unwrap();
+/// let cleaned = UrlCleanup::OAuth2Params.apply(&url).unwrap();
+/// assert_eq!(cleaned.as_str(), "https://app/items?page=2");
+///
+/// // the route of a hash router is kept
+/// let url = Url::parse("https://app/#/callback?code=abc&state=xyz").unwrap();
+/// let cleaned = UrlCleanup::Query.apply(&url).unwrap();
+/// assert_eq!(cleaned.as_str(), "https://app/#/callback");
+///
+/// let url = Url::parse("https://app/?code=abc&state=xyz#/items").unwrap();
+/// let cleaned = UrlCleanup::Query.apply(&url).unwrap();
+/// assert_eq!(cleaned.as_str(), "https://app/#/items");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum UrlCleanup {
+    /// Remove the whole query.
+    #[default]
+    Query,
+    /// Remove only the parameters of the authorization response (`code`, `state`, `error`, …),
+    /// keeping the other parameters of the application.
+    OAuth2Params,
+    /// Keep the URL as it is.
+    ///
+    /// Reloading the page will not exchange the code again, the response is marked as handled.
+    Nothing,
+    /// Replace the URL with the path, e.g. `/` or `/home`.
+    Path(String),
+}
+
+/// The parameters of an authorization response.
+const OAUTH2_PARAMS: &[&str] = &[
+    "code",
+    "state",
+    "session_state",
+    "iss",
+    "error",
+    "error_description",
+    "error_uri",
+];
+
+/// Remove the parameters of an authorization response, `None` if there are no others.
+fn strip_params(params: &str) -> Option<String> {
+    let params = form_urlencoded::parse(params.as_bytes())
+        .filter(|(name, _)| !OAUTH2_PARAMS.contains(&name.as_ref()))
+        .collect::<Vec<_>>();
+    (!params.is_empty()).then(|| {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish()
+    })
+}
+
+/// Join the route of a hash router with the parameters, into a fragment.
+fn join_fragment(route: &str, params: Option<String>) -> Option<String> {
+    match (route, params) {
+        ("", params) => params,
+        (route, None) => Some(route.to_string()),
+        (route, Some(params)) => Some(format!("{route}?{params}")),
+    }
+}
+
+impl UrlCleanup {
+    /// The URL, cleaned up. `None` if there is nothing to clean up.
+    ///
+    /// A fragment holding the response (see [`super::ResponseMode::Fragment`]) is cleaned up
+    /// like the query. The route of a hash router in the fragment (`#/callback?code=…`) is kept.
+    pub fn apply(&self, url: &Url) -> Option<Url> {
+        let mut url = url.clone();
+        let fragment = url
+            .fragment()
+            .filter(|fragment| State::from_fragment(fragment).is_response())
+            .map(|fragment| {
+                let (route, params) = split_fragment(fragment);
+                (route.to_string(), params.to_string())
+            });
+        match self {
+            Self::Query => {
+                url.set_query(None);
+                if let Some((route, _)) = fragment {
+                    url.set_fragment(join_fragment(&route, None).as_deref());
+                }
+            }
+            Self::OAuth2Params => {
+                let query = url.query().and_then(strip_params);
+                url.set_query(query.as_deref());
+                if let Some((route, params)) = fragment {
+                    url.set_fragment(join_fragment(&route, strip_params(&params)).as_deref());
+                }
+            }
+            Self::Nothing => return None,
+            Self::Path(path) => url = url.join(path).ok()?,
+        }
+        Some(url)
+    }
+}
+
+/// How to navigate to the cleaned up URL.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CleanupNavigation {
+    /// Replace the URL using the History API (`history.replaceState`), without loading the page
+    /// again.
+    #[default]
+    Replace,
+    /// Navigate to the URL (`location.replace`), loading the page again.
+    ///
+    /// This happens after handling the response, also replacing the post-login redirect. As the
+    /// application is loaded again, the session is only kept if it is persisted, see
+    /// [`SessionPersistence`].
+    Navigate,
+}
+
+/// Cleaning up the URL, after returning from the issuer.
+///
+/// By default, the whole query is removed using the History API.
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`super::LoginOptions`] for an example on how to
+/// work with this.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UrlCleanupPolicy {
+    /// Which part of the URL to remove.
+    pub cleanup: UrlCleanup,
+    /// How to navigate to the cleaned up URL.
+    pub navigation: CleanupNavigation,
+}
+
+impl UrlCleanupPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set which part of the URL to remove
+    pub fn with_cleanup(mut self, cleanup: UrlCleanup) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// Set how to navigate to the cleaned up URL
+    pub fn with_navigation(mut self, navigation: CleanupNavigation) -> Self {
+        self.navigation = navigation;
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::url;
+
+    #[test]
+    fn retry_doubles_delay() {
+        let policy = RetryPolicy::default().with_max_attempts(4);
+        assert_eq!(policy.delay(0), Some(Duration::from_secs(1)));
+        assert_eq!(policy.delay(1), Some(Duration::from_secs(2)));
+        assert_eq!(policy.delay(2), Some(Duration::from_secs(4)));
+        assert_eq!(policy.delay(3), Some(Duration::from_secs(8)));
+        assert_eq!(policy.delay(4), None);
+    }
+
+    #[test]
+    fn retry_caps_delay() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(u32::MAX)
+            .with_max_delay(Duration::from_secs(10));
+        assert_eq!(policy.delay(3), Some(Duration::from_secs(8)));
+        assert_eq!(policy.delay(4), Some(Duration::from_secs(10)));
+        // the factor saturates
+        assert_eq!(policy.delay(100), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn retry_overflow() {
+        let policy = RetryPolicy::default()
+            .with_max_attempts(u32::MAX)
+            .with_initial_delay(Duration::MAX)
+            .with_max_delay(Duration::from_secs(30));
+        assert_eq!(policy.delay(0), Some(Duration::from_secs(30)));
+        assert_eq!(policy.delay(1), Some(Duration::from_secs(30)));
+        assert_eq!(policy.delay(u32::MAX - 1), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn retry_none() {
+        assert_eq!(RetryPolicy::none().delay(0), None);
+    }
+
+    #[test]
+    fn refresh_ahead() {
+        let lifetime = Duration::from_secs(300);
+        assert_eq!(
+            RefreshAhead::Before(Duration::from_secs(30)).before(lifetime),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            RefreshAhead::Lifetime(80).before(lifetime),
+            Duration::from_secs(60)
+        );
+        assert_eq!(RefreshAhead::Lifetime(100).before(lifetime), Duration::ZERO);
+        // refreshing right away is brought back to a tenth of the lifetime
+        assert_eq!(
+            RefreshAhead::Lifetime(0).before(lifetime),
+            Duration::from_secs(270)
+        );
+        assert_eq!(
+            RefreshAhead::Before(lifetime).before(lifetime),
+            Duration::from_secs(270)
+        );
+        assert_eq!(
+            RefreshAhead::Before(Duration::from_secs(600)).before(lifetime),
+            Duration::from_secs(270)
+        );
+        // more than 100 percent refreshes at the expiration
+        assert_eq!(RefreshAhead::Lifetime(150).before(lifetime), Duration::ZERO);
+    }
+
+    #[test]
+    fn cleanup_query() {
+        let cleaned = UrlCleanup::Query.apply(&url("https://app/items?page=2&code=abc&state=xyz"));
+        assert_eq!(cleaned, Some(url("https://app/items")));
+    }
+
+    #[test]
+    fn cleanup_params() {
+        let cleaned = UrlCleanup::OAuth2Params.apply(&url(
+            "https://app/items?page=2&code=abc&state=xyz&session_state=s&iss=https%3A%2F%2Fissuer",
+        ));
+        assert_eq!(cleaned, Some(url("https://app/items?page=2")));
+
+        let cleaned = UrlCleanup::OAuth2Params.apply(&url("https://app/?code=abc&state=xyz"));
+        assert_eq!(cleaned, Some(url("https://app/")));
+
+        let cleaned = UrlCleanup::OAuth2Params.apply(&url(
+            "https://app/?error=access_denied&error_description=no&state=xyz",
+        ));
+        assert_eq!(cleaned, Some(url("https://app/")));
+    }
+
+    #[test]
+    fn cleanup_fragment() {
+        // the response in the fragment
+        let cleaned = UrlCleanup::Query.apply(&url("https://app/#code=abc&state=xyz"));
+        assert_eq!(cleaned, Some(url("https://app/")));
+
+        // the response next to the route of a hash router
+        let cleaned =
+            UrlCleanup::OAuth2Params.apply(&url("https://app/#/callback?tab=1&code=abc&state=xyz"));
+        assert_eq!(cleaned, Some(url("https://app/#/callback?tab=1")));
+
+        // a fragment without a response is kept
+        let cleaned = UrlCleanup::Query.apply(&url("https://app/?code=abc&state=xyz#/items"));
+        assert_eq!(cleaned, Some(url("https://app/#/items")));
+    }
+
+    #[test]
+    fn cleanup_other() {
+        let current = url("https://app/items?code=abc&state=xyz");
+        assert_eq!(UrlCleanup::Nothing.apply(&current), None);
+        assert_eq!(
+            UrlCleanup::Path("/home".into()).apply(&current),
+            Some(url("https://app/home"))
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Providing access tokens to API clients.
+
+use super::{Agent, Client, OAuth2Agent, OAuth2Operations, TokenRequest};
+use crate::context::LatestAccessToken;
+use async_trait::async_trait;
+use std::rc::Rc;
+
+/// A source of access tokens, for API clients.
+///
+/// Clients generated by tools like `progenitor` or `openapi-generator` can be constructed with a
+/// provider, instead of depending on a specific part of this crate. The agent provides a token
+/// which is valid for new requests, refreshing the session first if required. The
+/// [`LatestAccessToken`] provides the token of the last known state, without waiting.
+///
+/// ```rust
+/// use yew_oauth2::agent::AccessTokenProvider;
+///
+/// struct ApiClient<P: AccessTokenProvider> {
+///     base: String,
+///     tokens: P,
+/// }
+///
+/// impl<P: AccessTokenProvider> ApiClient<P> {
+///     async fn authorization(&self) -> Option<String> {
+///         Some(format!("Bearer {}", self.tokens.token().await?))
+///     }
+/// }
+/// ```
+#[async_trait(?Send)]
+pub trait AccessTokenProvider {
+    /// Get the access token, if there is a session.
+    async fn token(&self) -> Option<String>;
+}
+
+#[async_trait(?Send)]
+impl AccessTokenProvider for OAuth2Agent {
+    async fn token(&self) -> Option<String> {
+        self.access_token()
+            .await
+            .map(|token| token.access_token)
+            .ok()
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: Client> AccessTokenProvider for Agent<C> {
+    async fn token(&self) -> Option<String> {
+        self.acquire_token(TokenRequest::new())
+            .await
+            .map(|token| token.access_token)
+            .ok()
+    }
+}
+
+#[async_trait(?Send)]
+impl AccessTokenProvider for LatestAccessToken {
+    async fn token(&self) -> Option<String> {
+        self.access_token()
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: AccessTokenProvider + ?Sized> AccessTokenProvider for Rc<P> {
+    async fn token(&self) -> Option<String> {
+        (**self).token().await
+    }
+}
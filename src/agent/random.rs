@@ -0,0 +1,101 @@
+//! Sources of randomness, used for the PKCE verifier, the CSRF token, the nonce, and jitter.
+
+use super::OAuth2Error;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use gloo_utils::window;
+use std::{fmt::Debug, rc::Rc, time::Duration};
+
+/// A source of random bytes.
+///
+/// The default is [`WebCrypto`]. Providing a different source allows injecting deterministic
+/// values, e.g. for testing.
+pub trait RandomSource: Debug {
+    /// Fill the buffer with random bytes.
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), String>;
+}
+
+/// The random source of the browser's WebCrypto API.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WebCrypto;
+
+impl RandomSource for WebCrypto {
+    fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), String> {
+        let crypto = window()
+            .crypto()
+            .map_err(|err| format!("WebCrypto is not available: {err:?}"))?;
+        crypto
+            .get_random_values_with_u8_array(dest)
+            .map_err(|err| format!("failed to get random values: {err:?}"))?;
+        Ok(())
+    }
+}
+
+/// A handle to a [`RandomSource`].
+#[derive(Clone, Debug)]
+pub struct Random(Rc<dyn RandomSource>);
+
+impl Random {
+    pub fn new<R>(source: R) -> Self
+    where
+        R: RandomSource + 'static,
+    {
+        Self(Rc::new(source))
+    }
+
+    /// Create a new random, URL safe, token.
+    pub(crate) fn token(&self) -> Result<String, OAuth2Error> {
+        let mut bytes = [0u8; 32];
+        self.0
+            .fill_bytes(&mut bytes)
+            .map_err(OAuth2Error::StartLogin)?;
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    /// Create a random duration, up to the maximum.
+    ///
+    /// Falls back to no duration at all, if the source fails.
+    pub(crate) fn duration(&self, max: Duration) -> Duration {
+        if max.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let mut bytes = [0u8; 4];
+        match self.0.fill_bytes(&mut bytes) {
+            Ok(()) => max.mul_f64(f64::from(u32::from_le_bytes(bytes)) / f64::from(u32::MAX)),
+            Err(err) => {
+                log::debug!("Failed to create random duration: {err}");
+                Duration::ZERO
+            }
+        }
+    }
+}
+
+thread_local! {
+    static DEFAULT: Random = Random::new(WebCrypto);
+}
+
+impl Default for Random {
+    /// The source of the Browser.
+    ///
+    /// All default instances share the same source, so that they compare equal.
+    fn default() -> Self {
+        DEFAULT.with(Clone::clone)
+    }
+}
+
+impl PartialEq for Random {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_is_shared() {
+        assert_eq!(Random::default(), Random::default());
+        assert_ne!(Random::default(), Random::new(WebCrypto));
+    }
+}
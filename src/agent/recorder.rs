@@ -0,0 +1,246 @@
+//! Recording traces of login flows, for bug reports.
+
+use super::{Clock, OAuth2Error};
+use crate::context::{OAuth2Context, Operation};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, fmt::Formatter, rc::Rc, sync::Arc};
+
+/// An error of a trace, without any details which might be sensitive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TracedError {
+    /// The kind of error, e.g. `Network` or `Server`.
+    pub kind: String,
+    /// The OAuth2 error code, if the issuer responded with one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl From<&OAuth2Error> for TracedError {
+    fn from(err: &OAuth2Error) -> Self {
+        let (kind, code) = match err {
+            OAuth2Error::NotInitialized => ("NotInitialized", None),
+            OAuth2Error::Configuration { .. } => ("Configuration", None),
+            OAuth2Error::InvalidConfiguration(_) => ("InvalidConfiguration", None),
+            OAuth2Error::Discovery(_) => ("Discovery", None),
+            OAuth2Error::Network(_) => ("Network", None),
+            OAuth2Error::Cors { .. } => ("Cors", None),
+            OAuth2Error::Server { error, .. } => ("Server", Some(error.clone())),
+            OAuth2Error::Validation { .. } => ("Validation", None),
+            OAuth2Error::AcrNotSatisfied { .. } => ("AcrNotSatisfied", None),
+            OAuth2Error::UnknownState => ("UnknownState", None),
+            OAuth2Error::TemporarilyUnavailable(_) => ("TemporarilyUnavailable", None),
+            OAuth2Error::StartLogin(_) => ("StartLogin", None),
+            OAuth2Error::Storage(_) => ("Storage", None),
+            OAuth2Error::Internal(_) => ("Internal", None),
+        };
+
+        Self {
+            kind: kind.to_string(),
+            code,
+        }
+    }
+}
+
+impl TracedError {
+    /// Create an error, resembling the recorded one.
+    pub(crate) fn to_error(&self) -> OAuth2Error {
+        let message = || format!("replayed {} error", self.kind);
+        match self.kind.as_str() {
+            "NotInitialized" => OAuth2Error::NotInitialized,
+            "Configuration" => OAuth2Error::Configuration {
+                message: message(),
+                source: None,
+            },
+            "InvalidConfiguration" => OAuth2Error::InvalidConfiguration(vec![]),
+            "Discovery" => OAuth2Error::Discovery(Arc::new(ReplayedError(message()))),
+            "Network" => OAuth2Error::Network(Arc::new(ReplayedError(message()))),
+            "Cors" => OAuth2Error::Cors {
+                endpoint: String::new(),
+                origin: String::new(),
+                source: Arc::new(ReplayedError(message())),
+            },
+            "Server" => OAuth2Error::Server {
+                error: self.code.clone().unwrap_or_default(),
+                description: None,
+                uri: None,
+            },
+            "Validation" | "AcrNotSatisfied" => OAuth2Error::validation(message()),
+            "UnknownState" => OAuth2Error::UnknownState,
+            "TemporarilyUnavailable" => OAuth2Error::TemporarilyUnavailable(None),
+            "StartLogin" => OAuth2Error::StartLogin(message()),
+            "Storage" => OAuth2Error::Storage(message()),
+            _ => OAuth2Error::Internal(message()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ReplayedError(String);
+
+impl std::fmt::Display for ReplayedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ReplayedError {}
+
+/// A state of the context, without any tokens or claims.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum TracedState {
+    NotInitialized,
+    #[serde(rename_all = "camelCase")]
+    NotAuthenticated {
+        reason: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Authenticated {
+        /// Seconds until the access token expires
+        expires_in: Option<u64>,
+        /// If there is a refresh token
+        refresh_token: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Failed {
+        operation: Operation,
+        error: TracedError,
+    },
+}
+
+impl From<&OAuth2Context> for TracedState {
+    fn from(context: &OAuth2Context) -> Self {
+        match context {
+            OAuth2Context::NotInitialized => Self::NotInitialized,
+            OAuth2Context::NotAuthenticated { reason } => Self::NotAuthenticated {
+                reason: format!("{reason:?}"),
+            },
+            OAuth2Context::Authenticated(auth) => Self::Authenticated {
+                expires_in: auth
+                    .expires
+                    .map(|expires| expires.saturating_sub(Clock::current().now_secs() as u64)),
+                refresh_token: auth.refresh_token.is_some(),
+            },
+            OAuth2Context::Failed { error, operation } => Self::Failed {
+                operation: *operation,
+                error: error.into(),
+            },
+        }
+    }
+}
+
+/// An event of a trace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TraceEvent {
+    /// The context changed.
+    State(TracedState),
+    /// An operation finished.
+    #[serde(rename_all = "camelCase")]
+    Outcome {
+        operation: Operation,
+        /// The error, if the operation failed.
+        #[serde(default)]
+        error: Option<TracedError>,
+    },
+}
+
+/// An entry of a trace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceEntry {
+    /// Milliseconds since the start of the recording.
+    pub elapsed: u64,
+    pub event: TraceEvent,
+}
+
+/// A trace of a login flow.
+///
+/// It contains the states of the context, the outcome of operations, and their timing. It
+/// doesn't contain any tokens, claims, or error descriptions, and can be attached to bug reports.
+/// A trace can be replayed using the [`super::client::ReplayClient`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowTrace {
+    pub entries: Vec<TraceEntry>,
+}
+
+impl FlowTrace {
+    /// Serialize the trace as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// Load a trace from JSON.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// The outcomes of an operation, in the order they were recorded.
+    pub(crate) fn outcomes(&self, op: Operation) -> impl Iterator<Item = Option<&TracedError>> {
+        self.entries
+            .iter()
+            .filter_map(move |entry| match &entry.event {
+                TraceEvent::Outcome { operation, error } if *operation == op => {
+                    Some(error.as_ref())
+                }
+                _ => None,
+            })
+    }
+}
+
+/// A handle to record a [`FlowTrace`].
+///
+/// Once provided to the [`crate::components::context::OAuth2`] component, the agent records its
+/// login and refresh flows. The trace is kept in memory, and can be retrieved using
+/// [`Self::trace`].
+#[derive(Clone)]
+pub struct FlowRecorder {
+    started: f64,
+    trace: Rc<RefCell<FlowTrace>>,
+}
+
+impl FlowRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Clock::current().now(),
+            trace: Default::default(),
+        }
+    }
+
+    /// Get the trace recorded so far.
+    pub fn trace(&self) -> FlowTrace {
+        self.trace.borrow().clone()
+    }
+
+    /// Discard the trace recorded so far.
+    pub fn clear(&self) {
+        self.trace.borrow_mut().entries.clear();
+    }
+
+    pub(crate) fn record(&self, event: TraceEvent) {
+        let elapsed = (Clock::current().now() - self.started).max(0f64) as u64;
+        self.trace
+            .borrow_mut()
+            .entries
+            .push(TraceEntry { elapsed, event });
+    }
+}
+
+impl Default for FlowRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for FlowRecorder {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.trace, &other.trace)
+    }
+}
+
+impl std::fmt::Debug for FlowRecorder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FlowRecorder")
+    }
+}
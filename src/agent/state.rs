@@ -1,18 +1,135 @@
-use super::OAuth2Error;
+use super::{Clock, OAuth2Error, SessionPersistence};
+use crate::context::Authentication;
 use gloo_storage::errors::StorageError;
-use gloo_storage::{SessionStorage, Storage};
-use std::fmt::Display;
+use gloo_storage::{LocalStorage, SessionStorage, Storage};
+use oauth2::url::form_urlencoded;
+use reqwest::Url;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{borrow::Cow, collections::HashMap, fmt::Display, time::Duration};
 
-pub(crate) const STORAGE_KEY_CSRF_TOKEN: &str = "ctron/oauth2/csrfToken";
-pub(crate) const STORAGE_KEY_LOGIN_STATE: &str = "ctron/oauth2/loginState";
-pub(crate) const STORAGE_KEY_REDIRECT_URL: &str = "ctron/oauth2/redirectUrl";
-pub(crate) const STORAGE_KEY_POST_LOGIN_URL: &str = "ctron/oauth2/postLoginUrl";
+pub(crate) const STORAGE_KEY_CSRF_TOKEN: &str = "csrfToken";
+pub(crate) const STORAGE_KEY_LOGIN_STATE: &str = "loginState";
+pub(crate) const STORAGE_KEY_REDIRECT_URL: &str = "redirectUrl";
+pub(crate) const STORAGE_KEY_POST_LOGIN_URL: &str = "postLoginUrl";
+pub(crate) const STORAGE_KEY_APP_STATE: &str = "appState";
+pub(crate) const STORAGE_KEY_LOGIN_RETRIES: &str = "loginRetries";
+pub(crate) const STORAGE_KEY_CONSUMED_STATES: &str = "consumedStates";
+pub(crate) const STORAGE_KEY_LOGOUT_REASON: &str = "logoutReason";
+/// Local storage key, announcing a logout to other tabs
+pub(crate) const STORAGE_KEY_LOGOUT: &str = "logout";
+/// Local storage key, marking that the last visit ended with a session
+pub(crate) const STORAGE_KEY_SESSION_HINT: &str = "sessionHint";
+/// Local storage key, of the nonces of ID tokens which were already used
+pub(crate) const STORAGE_KEY_CONSUMED_NONCES: &str = "consumedNonces";
+/// Session or local storage key, of the persisted session
+pub(crate) const STORAGE_KEY_SESSION: &str = "session";
+/// Session storage key, of the positions of the [`super::client::ReplayClient`] in its trace
+pub(crate) const STORAGE_KEY_REPLAY: &str = "replay";
+
+/// The scope of an application, allowing multiple applications on the same origin.
+///
+/// Applications with different base paths use different keys for storing their state, and only
+/// handle authorization responses sent to URLs below their base path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct AppScope {
+    base_path: Option<String>,
+}
+
+impl AppScope {
+    pub fn new(base_path: Option<&str>) -> Self {
+        let base_path = base_path
+            .map(|path| path.trim_matches('/'))
+            .filter(|path| !path.is_empty())
+            .map(|path| format!("/{path}"));
+        Self { base_path }
+    }
+
+    /// The storage key of an item.
+    pub fn key(&self, name: &str) -> String {
+        format!(
+            "ctron/oauth2{}/{name}",
+            self.base_path.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Check if the URL is below the base path.
+    pub fn contains(&self, url: &Url) -> bool {
+        match &self.base_path {
+            None => true,
+            Some(base_path) => url
+                .path()
+                .strip_prefix(base_path.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+        }
+    }
+}
+
+/// The URL of the public path (e.g. `/app/`) on the origin of the current URL.
+///
+/// Fails if the current URL is not below the public path, as the issuer would redirect to a
+/// different application.
+pub(crate) fn public_url(current_url: &Url, public_path: &str) -> Result<Url, String> {
+    let public_path = match public_path.trim_matches('/') {
+        "" => "/".to_string(),
+        path => format!("/{path}/"),
+    };
+
+    let path = current_url.path();
+    if !path.starts_with(&public_path) && format!("{path}/") != public_path {
+        return Err(format!(
+            "current location '{path}' is not below the public path '{public_path}'"
+        ));
+    }
+
+    current_url
+        .join(&public_path)
+        .map_err(|err| format!("invalid public path '{public_path}': {err}"))
+}
+
+/// The number of consumed states to remember.
+const MAX_CONSUMED_STATES: usize = 16;
 
 #[derive(Debug)]
 pub(crate) struct State {
     pub code: Option<String>,
     pub state: Option<String>,
     pub error: Option<String>,
+    pub error_description: Option<String>,
+    pub error_uri: Option<String>,
+}
+
+impl State {
+    /// The state from parameters, e.g. of the query.
+    pub fn from_params<'a>(params: impl Iterator<Item = (Cow<'a, str>, Cow<'a, str>)>) -> Self {
+        let params: HashMap<_, _> = params.collect();
+        let param = |name: &str| params.get(name).map(ToString::to_string);
+        Self {
+            code: param("code"),
+            state: param("state"),
+            error: param("error"),
+            error_description: param("error_description"),
+            error_uri: param("error_uri"),
+        }
+    }
+
+    /// The state from the fragment, see [`split_fragment`].
+    pub fn from_fragment(fragment: &str) -> Self {
+        let (_, params) = split_fragment(fragment);
+        Self::from_params(form_urlencoded::parse(params.as_bytes()))
+    }
+
+    /// If this is an authorization response, with a code or an error.
+    pub fn is_response(&self) -> bool {
+        self.code.is_some() || self.error.is_some()
+    }
+}
+
+/// Split the fragment into the route of a hash router, and the parameters.
+///
+/// Hash routers keep a query of their own in the fragment (`#/callback?code=…`). Without one,
+/// the whole fragment are the parameters (`#code=…`).
+pub(crate) fn split_fragment(fragment: &str) -> (&str, &str) {
+    fragment.split_once('?').unwrap_or(("", fragment))
 }
 
 pub(crate) fn get_from_store<K: AsRef<str> + Display>(key: K) -> Result<String, OAuth2Error> {
@@ -30,6 +147,122 @@ pub(crate) fn get_from_store_optional<K: AsRef<str> + Display>(
     }
 }
 
+/// Check if the authorization response of a state was already handled.
+pub(crate) fn is_consumed(scope: &AppScope, state: &str) -> bool {
+    SessionStorage::get::<Vec<String>>(scope.key(STORAGE_KEY_CONSUMED_STATES))
+        .map(|states| states.iter().any(|s| s == state))
+        .unwrap_or_default()
+}
+
+/// Record handling the authorization response of a state, so that its code is only exchanged
+/// once.
+pub(crate) fn consume(scope: &AppScope, state: String) {
+    let key = scope.key(STORAGE_KEY_CONSUMED_STATES);
+    let mut states = SessionStorage::get::<Vec<String>>(&key).unwrap_or_default();
+    states.push(state);
+    if states.len() > MAX_CONSUMED_STATES {
+        states.drain(..states.len() - MAX_CONSUMED_STATES);
+    }
+    if let Err(err) = SessionStorage::set(key, states) {
+        log::warn!("Failed to store consumed states: {err}");
+    }
+}
+
+/// Record the use of the nonce of an ID token.
+///
+/// Returns `false` if the nonce was already used within the window. Nonces are shared between
+/// tabs and windows (e.g. popups), and forgotten once the window passed.
+pub(crate) fn consume_nonce(scope: &AppScope, nonce: &str, window: Duration) -> bool {
+    let key = scope.key(STORAGE_KEY_CONSUMED_NONCES);
+    let mut nonces = LocalStorage::get::<Vec<(String, f64)>>(&key).unwrap_or_default();
+
+    if !use_nonce(&mut nonces, nonce, Clock::current().now(), window) {
+        return false;
+    }
+
+    if let Err(err) = LocalStorage::set(key, nonces) {
+        log::warn!("Failed to store consumed nonces: {err}");
+    }
+    true
+}
+
+/// Record the use of a nonce at `now` (in milliseconds), forgetting the ones outside the window.
+fn use_nonce(nonces: &mut Vec<(String, f64)>, nonce: &str, now: f64, window: Duration) -> bool {
+    let window = window.as_secs_f64() * 1000f64;
+    nonces.retain(|(_, used)| now - used < window);
+
+    if nonces.iter().any(|(used, _)| used == nonce) {
+        return false;
+    }
+
+    nonces.push((nonce.to_string(), now));
+    true
+}
+
+/// Check if the last visit of the application ended with a session.
+#[cfg(feature = "yew")]
+pub(crate) fn has_session_hint(scope: &AppScope) -> bool {
+    LocalStorage::get::<bool>(scope.key(STORAGE_KEY_SESSION_HINT)).unwrap_or_default()
+}
+
+/// Mark if the application currently has a session, for the next visit.
+pub(crate) fn set_session_hint(scope: &AppScope, session: bool) {
+    let key = scope.key(STORAGE_KEY_SESSION_HINT);
+    match session {
+        true => {
+            if let Err(err) = LocalStorage::set(key, true) {
+                log::warn!("Failed to store session hint: {err}");
+            }
+        }
+        false => LocalStorage::delete(key),
+    }
+}
+
+/// A session, persisted in the storage of the browser.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "S: Serialize + DeserializeOwned")]
+pub(crate) struct PersistedSession<S> {
+    pub authentication: Authentication,
+    pub session_state: S,
+}
+
+/// Persist the session, according to the persistence policy.
+pub(crate) fn persist_session<S: Serialize + DeserializeOwned>(
+    persistence: SessionPersistence,
+    scope: &AppScope,
+    session: &PersistedSession<S>,
+) {
+    let key = scope.key(STORAGE_KEY_SESSION);
+    let result = match persistence {
+        SessionPersistence::None => return,
+        SessionPersistence::Tab => SessionStorage::set(key, session),
+        SessionPersistence::Browser => LocalStorage::set(key, session),
+    };
+    if let Err(err) = result {
+        log::warn!("Failed to persist session: {err}");
+    }
+}
+
+/// Get the persisted session, according to the persistence policy.
+pub(crate) fn persisted_session<S: Serialize + DeserializeOwned>(
+    persistence: SessionPersistence,
+    scope: &AppScope,
+) -> Option<PersistedSession<S>> {
+    let key = scope.key(STORAGE_KEY_SESSION);
+    match persistence {
+        SessionPersistence::None => None,
+        SessionPersistence::Tab => SessionStorage::get(key).ok(),
+        SessionPersistence::Browser => LocalStorage::get(key).ok(),
+    }
+}
+
+/// Remove the persisted session, from all storages.
+pub(crate) fn forget_session(scope: &AppScope) {
+    let key = scope.key(STORAGE_KEY_SESSION);
+    SessionStorage::delete(&key);
+    LocalStorage::delete(key);
+}
+
 /// Login state, stored in the session
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct LoginState {
@@ -40,9 +273,99 @@ pub struct LoginState {
 impl LoginState {
     /// Read the state from the session
     pub fn from_storage() -> Result<Self, OAuth2Error> {
+        Self::from_scoped_storage(None)
+    }
+
+    /// Read the state from the session, of the application with the provided base path
+    pub fn from_scoped_storage(base_path: Option<&str>) -> Result<Self, OAuth2Error> {
+        let scope = AppScope::new(base_path);
         Ok(Self {
-            redirect_url: get_from_store_optional(STORAGE_KEY_REDIRECT_URL)?,
-            post_login_url: get_from_store_optional(STORAGE_KEY_POST_LOGIN_URL)?,
+            redirect_url: get_from_store_optional(scope.key(STORAGE_KEY_REDIRECT_URL))?,
+            post_login_url: get_from_store_optional(scope.key(STORAGE_KEY_POST_LOGIN_URL))?,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::url;
+
+    #[test]
+    fn scope_key() {
+        assert_eq!(AppScope::new(None).key("session"), "ctron/oauth2/session");
+        assert_eq!(
+            AppScope::new(Some("/")).key("session"),
+            "ctron/oauth2/session"
+        );
+        assert_eq!(
+            AppScope::new(Some("app")).key("session"),
+            "ctron/oauth2/app/session"
+        );
+        assert_eq!(
+            AppScope::new(Some("/admin/app/")).key("session"),
+            "ctron/oauth2/admin/app/session"
+        );
+        assert_eq!(AppScope::new(Some("/app/")), AppScope::new(Some("app")));
+    }
+
+    #[test]
+    fn scope_contains() {
+        let scope = AppScope::new(Some("/app"));
+        assert!(scope.contains(&url("https://host/app")));
+        assert!(scope.contains(&url("https://host/app/callback?code=abc")));
+        assert!(!scope.contains(&url("https://host/application")));
+        assert!(!scope.contains(&url("https://host/")));
+        assert!(AppScope::new(None).contains(&url("https://host/other")));
+    }
+
+    #[test]
+    fn public_url_below() {
+        assert_eq!(
+            public_url(&url("https://host/app/items?page=2"), "/app"),
+            Ok(url("https://host/app/"))
+        );
+        assert_eq!(
+            public_url(&url("https://host/app"), "app/"),
+            Ok(url("https://host/app/"))
+        );
+        assert_eq!(
+            public_url(&url("https://host/items#/route"), "/"),
+            Ok(url("https://host/"))
+        );
+        assert_eq!(
+            public_url(&url("https://host/items"), ""),
+            Ok(url("https://host/"))
+        );
+    }
+
+    #[test]
+    fn nonce_accepts() {
+        let window = Duration::from_secs(60);
+        let mut nonces = vec![];
+        assert!(use_nonce(&mut nonces, "a", 0f64, window));
+        assert!(use_nonce(&mut nonces, "b", 1_000f64, window));
+        // the window passed
+        assert!(use_nonce(&mut nonces, "a", 60_000f64, window));
+        assert_eq!(
+            nonces,
+            vec![("b".to_string(), 1_000f64), ("a".to_string(), 60_000f64)]
+        );
+    }
+
+    #[test]
+    fn nonce_rejects() {
+        let window = Duration::from_secs(60);
+        let mut nonces = vec![];
+        assert!(use_nonce(&mut nonces, "a", 0f64, window));
+        assert!(!use_nonce(&mut nonces, "a", 0f64, window));
+        assert!(!use_nonce(&mut nonces, "a", 59_999f64, window));
+        assert_eq!(nonces, vec![("a".to_string(), 0f64)]);
+    }
+
+    #[test]
+    fn public_url_outside() {
+        assert!(public_url(&url("https://host/other"), "/app").is_err());
+        assert!(public_url(&url("https://host/application"), "/app").is_err());
+    }
+}
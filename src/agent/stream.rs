@@ -0,0 +1,30 @@
+//! A stream of the changes of the context.
+
+use crate::context::OAuth2Context;
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// A stream of the [`OAuth2Context`], see [`super::OAuth2Operations::stream`].
+///
+/// The first item is the current context, followed by each change of it. The stream ends once the
+/// agent is gone.
+#[derive(Debug)]
+pub struct ContextStream(UnboundedReceiver<OAuth2Context>);
+
+impl ContextStream {
+    pub(crate) fn new(rx: UnboundedReceiver<OAuth2Context>) -> Self {
+        Self(rx)
+    }
+}
+
+impl Stream for ContextStream {
+    type Item = OAuth2Context;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
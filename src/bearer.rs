@@ -0,0 +1,125 @@
+//! Sending requests with the access token of the session, shared by the HTTP integrations.
+//!
+//! The integrations, like [`crate::middleware::BearerAuth`], only differ in how a request is sent
+//! and copied. Acquiring the token, and refreshing it once the request was rejected as
+//! unauthorized, is the same for all of them.
+
+use crate::agent::{Error, OAuth2Agent, TokenRequest};
+use async_trait::async_trait;
+use core::fmt::{Debug, Display, Formatter};
+
+/// An error sending a request with the access token of the session.
+///
+/// The HTTP integrations provide aliases like [`crate::middleware::BearerAuthError`], for their
+/// errors `E` and responses `R`.
+#[derive(Debug)]
+pub enum BearerError<E, R> {
+    /// Failed to acquire an access token.
+    Token(Error),
+    /// Failed to send the request, or to read the response.
+    Request(E),
+    /// The request was rejected as unauthorized (`401`), even after refreshing the token.
+    Unauthorized(R),
+}
+
+impl<E: Display, R> Display for BearerError<E, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(err) => write!(f, "failed to acquire token: {err}"),
+            Self::Request(err) => write!(f, "request failed: {err}"),
+            Self::Unauthorized(_) => f.write_str("unauthorized"),
+        }
+    }
+}
+
+impl<E, R> std::error::Error for BearerError<E, R>
+where
+    E: std::error::Error + 'static,
+    R: Debug,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Token(err) => Some(err),
+            Self::Request(err) => Some(err),
+            Self::Unauthorized(_) => None,
+        }
+    }
+}
+
+impl<E, R> From<Error> for BearerError<E, R> {
+    fn from(err: Error) -> Self {
+        Self::Token(err)
+    }
+}
+
+/// Sending requests of an HTTP integration.
+#[async_trait(?Send)]
+pub(crate) trait Sender {
+    type Request;
+    type Response;
+    type Error;
+
+    /// Copy the request, for sending it once more.
+    ///
+    /// Returns `None` if that isn't possible, e.g. for a request with a streaming body.
+    fn try_clone(request: &Self::Request) -> Option<Self::Request>;
+
+    /// Check if the response rejects the request as unauthorized.
+    fn is_unauthorized(response: &Self::Response) -> bool;
+
+    /// Send the request, with the access token.
+    async fn send(
+        &mut self,
+        request: Self::Request,
+        token: &str,
+    ) -> Result<Self::Response, Self::Error>;
+}
+
+/// Send the request with the access token.
+///
+/// The token is acquired from the agent, or the [global agent](OAuth2Agent::global), right before
+/// sending the request. If the request is rejected as unauthorized, the session is refreshed and
+/// a copy of the request is sent once more. If that is rejected as well, or the request can't be
+/// copied, this is reported to the agent and returned as [`BearerError::Unauthorized`].
+pub(crate) async fn send<S: Sender>(
+    agent: Option<&OAuth2Agent>,
+    token_request: &TokenRequest,
+    sender: &mut S,
+    request: S::Request,
+) -> Result<S::Response, BearerError<S::Error, S::Response>> {
+    let agent = match agent {
+        Some(agent) => agent.clone(),
+        None => OAuth2Agent::global().ok_or(Error::NoAgent)?,
+    };
+
+    let token = agent.acquire_token(token_request.clone()).await?;
+    let retry = S::try_clone(&request);
+    let response = sender
+        .send(request, &token.access_token)
+        .await
+        .map_err(BearerError::Request)?;
+    if !S::is_unauthorized(&response) {
+        return Ok(response);
+    }
+
+    log::debug!("Request unauthorized, refreshing the token");
+    let refreshed = match retry {
+        Some(_) => agent.replace_token(&token, token_request.clone()).await?,
+        None => None,
+    };
+    let (Some(retry), Some(refreshed)) = (retry, refreshed) else {
+        let _ = agent.report_unauthorized();
+        return Err(BearerError::Unauthorized(response));
+    };
+
+    let response = sender
+        .send(retry, &refreshed.access_token)
+        .await
+        .map_err(BearerError::Request)?;
+    if S::is_unauthorized(&response) {
+        let _ = agent.report_unauthorized();
+        return Err(BearerError::Unauthorized(response));
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,89 @@
+//! The [`AccountLink`] component
+
+use super::missing_context;
+use crate::{
+    agent::{Client, OAuth2Operations},
+    components::context::use_auth_agent,
+};
+use reqwest::Url;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+/// Properties for the [`AccountLink`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct AccountLinkProperties {
+    /// The URL the account console should link back to.
+    ///
+    /// Defaults to the current URL.
+    #[prop_or_default]
+    pub return_url: Option<Url>,
+    #[prop_or_default]
+    pub id: Option<String>,
+    #[prop_or_default]
+    pub style: Option<String>,
+    #[prop_or_default]
+    pub class: Option<String>,
+    #[prop_or_default]
+    pub target: Option<String>,
+    /// The content of the link.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A Yew component, rendering a link to the issuer's account management console.
+///
+/// Nothing will be rendered if the client has no account management URL configured.
+#[function_component(AccountLink)]
+pub fn account_link<C>(props: &AccountLinkProperties) -> Html
+where
+    C: Client,
+{
+    let agent = use_auth_agent::<C>();
+    let href = use_state_eq(|| None::<Url>);
+
+    {
+        let href = href.clone();
+        use_effect_with(
+            (agent.clone(), props.return_url.clone()),
+            move |(agent, return_url)| {
+                if let Some(agent) = agent.clone() {
+                    let return_url = return_url.clone();
+                    spawn_local(async move {
+                        if let Ok(url) = agent.account_url(return_url).await {
+                            href.set(url);
+                        }
+                    });
+                }
+            },
+        );
+    }
+
+    match (agent, &*href) {
+        (None, _) => missing_context(),
+        (Some(_), Some(href)) => html!(
+            <a
+                href={ href.to_string() }
+                id={ props.id.clone() }
+                style={ props.style.clone() }
+                class={ &props.class }
+                target={ props.target.clone() }
+                >
+                { for props.children.iter() }
+            </a>
+        ),
+        (Some(_), None) => html!(),
+    }
+}
+
+pub mod oauth2 {
+    //! Convenient access for the OAuth2 variant
+    use crate::agent::client::OAuth2Client as Client;
+    pub type AccountLink = super::AccountLink<Client>;
+}
+
+#[cfg(feature = "openid")]
+pub mod openid {
+    //! Convenient access for the Open ID Connect variant
+    use crate::agent::client::OpenIdClient as Client;
+    pub type AccountLink = super::AccountLink<Client>;
+}
@@ -1,7 +1,7 @@
 //! The [`Authenticated`] component
 
 use super::missing_context;
-use crate::context::OAuth2Context;
+use crate::context::{OAuth2Context, SessionHint};
 use yew::prelude::*;
 
 /// Properties for the [`Authenticated`] component
@@ -9,20 +9,52 @@ use yew::prelude::*;
 pub struct AuthenticatedProperties {
     /// The children to show then the context is authenticated.
     pub children: Children,
+
+    /// Content to show when the context is not authenticated, e.g. a login prompt.
+    ///
+    /// This is shown in the same cases as the [`super::NotAuthenticated`] component.
+    #[prop_or_default]
+    pub fallback: Option<Html>,
+
+    /// Content to show while the agent initializes, if the last visit ended with a session.
+    ///
+    /// This improves the perceived startup time for returning users, e.g. by rendering the
+    /// layout of the application, until the session is known. See [`crate::context::SessionHint`].
+    #[prop_or_default]
+    pub optimistic: Option<Html>,
 }
 
 /// A Yew component, rendering when the agent is authenticated.
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+///
+/// #[function_component(MyContent)]
+/// fn my_content() -> Html {
+///     html!(
+///         <Authenticated fallback={html!(<p>{ "Please log in" }</p>)}>
+///             <p>{ "Welcome back" }</p>
+///         </Authenticated>
+///     )
+/// }
+/// ```
 #[function_component(Authenticated)]
 pub fn authenticated(props: &AuthenticatedProperties) -> Html {
     let auth = use_context::<OAuth2Context>();
+    let hint = use_context::<SessionHint>().unwrap_or_default();
 
-    html!(
-        if let Some(auth) = auth {
-            if let OAuth2Context::Authenticated{..} = auth {
-                { for props.children.iter() }
-            }
-        } else {
-            { missing_context() }
+    match auth {
+        None => missing_context(),
+        Some(OAuth2Context::Authenticated { .. }) => html!({ for props.children.iter() }),
+        Some(OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed { .. }) => {
+            props.fallback.clone().unwrap_or_default()
         }
-    )
+        Some(OAuth2Context::NotInitialized) => match hint.returning {
+            true => props.optimistic.clone().unwrap_or_default(),
+            false => html!(),
+        },
+    }
 }
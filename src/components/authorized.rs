@@ -0,0 +1,124 @@
+//! The [`Authorized`] component
+
+use super::missing_context;
+use crate::context::{Authentication, ClaimPaths, OAuth2Context};
+use std::{fmt::Formatter, rc::Rc};
+use yew::prelude::*;
+
+/// A predicate, deciding if an authenticated session is authorized.
+#[derive(Clone)]
+pub struct AuthorizationPredicate(Rc<dyn Fn(&Authentication) -> bool>);
+
+impl AuthorizationPredicate {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Authentication) -> bool + 'static,
+    {
+        Self(Rc::new(f))
+    }
+
+    /// Evaluate the predicate.
+    pub fn test(&self, auth: &Authentication) -> bool {
+        (self.0)(auth)
+    }
+}
+
+impl<F> From<F> for AuthorizationPredicate
+where
+    F: Fn(&Authentication) -> bool + 'static,
+{
+    fn from(f: F) -> Self {
+        Self::new(f)
+    }
+}
+
+impl PartialEq for AuthorizationPredicate {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for AuthorizationPredicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuthorizationPredicate")
+    }
+}
+
+/// Properties for the [`Authorized`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct AuthorizedProperties {
+    /// Roles the session must have, see [`ClaimPaths::roles`].
+    #[prop_or_default]
+    pub roles: Vec<String>,
+
+    /// Scopes the session must have, see [`ClaimPaths::scopes`].
+    #[prop_or_default]
+    pub scopes: Vec<String>,
+
+    /// An additional predicate the session must pass.
+    #[prop_or_default]
+    pub predicate: Option<AuthorizationPredicate>,
+
+    /// Content to show when the session is authenticated, but not authorized.
+    #[prop_or_default]
+    pub forbidden: Option<Html>,
+
+    /// The children to show when the session is authorized.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+impl AuthorizedProperties {
+    fn is_authorized(&self, auth: &Authentication, paths: &ClaimPaths) -> bool {
+        let roles = paths.roles(auth);
+        let scopes = paths.scopes(auth);
+
+        self.roles.iter().all(|role| roles.contains(role))
+            && self.scopes.iter().all(|scope| scopes.contains(scope))
+            && self
+                .predicate
+                .as_ref()
+                .map_or(true, |predicate| predicate.test(auth))
+    }
+}
+
+/// A Yew component, rendering when the session is authorized.
+///
+/// The session must be authenticated, have all the required roles and scopes, and pass the
+/// predicate. If the session is authenticated, but not authorized, the `forbidden` content is
+/// shown instead. Nothing is shown when the session is not authenticated.
+///
+/// **NOTE**: This only decides what to show. The backend must still check the authorization.
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+///
+/// #[function_component(AdminPanel)]
+/// fn admin_panel() -> Html {
+///     html!(
+///         <Authorized
+///             roles={vec!["admin".to_string()]}
+///             forbidden={html!(<p>{ "Only for administrators" }</p>)}
+///             >
+///             <p>{ "Settings" }</p>
+///         </Authorized>
+///     )
+/// }
+/// ```
+#[function_component(Authorized)]
+pub fn authorized(props: &AuthorizedProperties) -> Html {
+    let auth = use_context::<OAuth2Context>();
+    let paths = use_context::<ClaimPaths>().unwrap_or_default();
+
+    match auth {
+        None => missing_context(),
+        Some(OAuth2Context::Authenticated(auth)) => match props.is_authorized(&auth, &paths) {
+            true => html!({ for props.children.iter() }),
+            false => props.forbidden.clone().unwrap_or_default(),
+        },
+        Some(_) => html!(),
+    }
+}
@@ -17,6 +17,10 @@ impl<C: Client> Agent<C> {
 
         Self(agent, id)
     }
+
+    pub(crate) fn id(&self) -> usize {
+        self.1
+    }
 }
 
 impl<C: Client> PartialEq for Agent<C> {
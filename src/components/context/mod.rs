@@ -1,12 +1,24 @@
 //! The main, wrapping [`OAuth2`] component
 
 mod agent;
+mod operations;
 
 pub use agent::*;
+pub use operations::*;
 
 use crate::{
-    agent::{AgentConfiguration, Client, LoginOptions, LogoutOptions, OAuth2Operations},
-    context::{LatestAccessToken, OAuth2Context},
+    agent::state::{has_session_hint, AppScope},
+    agent::{
+        AgentConfiguration, AgentEvent, BackendSession, Bootstrap, Client, Clock,
+        EventSubscription, ExpiryPolicy, FlowRecorder, HiddenRefreshPolicy, KeepAlive,
+        LoginOptions, LogoutOptions, Metrics, OAuth2Error, OAuth2Operations, Random, RefreshAhead,
+        RefreshTokenPolicy, RetryPolicy, SessionPersistence, UnauthorizedPolicy,
+        UnknownStatePolicy, UrlCleanupPolicy,
+    },
+    context::{
+        Authentication, ClaimPaths, IssuerHealth, LatestAccessToken, OAuth2Context, Operation,
+        SessionHint,
+    },
 };
 use agent::Agent as AgentContext;
 use std::time::Duration;
@@ -24,7 +36,8 @@ pub struct OAuth2Properties<C: Client> {
 
     /// The grace period for the session timeout
     ///
-    /// The amount of time before the token expiration when the agent will refresh it.
+    /// The amount of time before the token expiration when the agent will refresh it. Also see
+    /// [`Self::refresh_ahead`].
     #[prop_or(Duration::from_secs(30))]
     pub grace_period: Duration,
 
@@ -44,6 +57,14 @@ pub struct OAuth2Properties<C: Client> {
     #[prop_or_default]
     pub children: Children,
 
+    /// Content to render instead of the children, while the agent is initializing.
+    ///
+    /// By default, the children are rendered right away, seeing
+    /// [`OAuth2Context::NotInitialized`]. This allows showing e.g. a spinner while discovering
+    /// the issuer instead.
+    #[prop_or_default]
+    pub loading: Option<Html>,
+
     /// Default [`LoginOptions`] that will be used unless more specific options have been requested.
     #[prop_or_default]
     pub login_options: Option<LoginOptions>,
@@ -51,6 +72,155 @@ pub struct OAuth2Properties<C: Client> {
     /// Default [`LogoutOptions`] that will be used unless more specific options have been requested.
     #[prop_or_default]
     pub logout_options: Option<LogoutOptions>,
+
+    /// How to handle unauthorized responses, reported by HTTP integrations.
+    #[prop_or_default]
+    pub unauthorized_policy: UnauthorizedPolicy,
+
+    /// How to handle authorization responses, which don't match the pending login.
+    #[prop_or_default]
+    pub unknown_state_policy: UnknownStatePolicy,
+
+    /// The interval for checking if the issuer is reachable.
+    ///
+    /// If present, the agent will periodically try to reach the issuer, and provide the outcome
+    /// as [`IssuerHealth`] context.
+    #[prop_or_default]
+    pub health_check: Option<Duration>,
+
+    /// How to retry, when the issuer is temporarily unavailable, or a refresh fails transiently.
+    #[prop_or_default]
+    pub retry_policy: RetryPolicy,
+
+    /// The source of randomness for the PKCE verifier, the CSRF token, the nonce, and the jitter
+    /// of refreshes and retries.
+    ///
+    /// Defaults to the WebCrypto API of the browser.
+    #[prop_or_default]
+    pub random: Random,
+
+    /// A reporter for the stages of the login funnel.
+    ///
+    /// Independent of this, the agent keeps counters of the funnel, see
+    /// [`OAuth2Operations::login_funnel`].
+    #[prop_or_default]
+    pub metrics: Option<Metrics>,
+
+    /// The base path of the application, e.g. `/app-a`.
+    ///
+    /// When running multiple applications on the same origin, this keeps their stored state
+    /// apart, and ensures that an application only handles authorization responses sent to URLs
+    /// below its base path.
+    #[prop_or_default]
+    pub base_path: Option<String>,
+
+    /// Establishing a session with the backend of the application, after the login.
+    #[prop_or_default]
+    pub backend_session: Option<BackendSession>,
+
+    /// Pinging the backend of the application, while the session is authenticated.
+    #[prop_or_default]
+    pub keep_alive: Option<KeepAlive>,
+
+    /// A recorder, capturing a redacted trace of the login and refresh flows.
+    ///
+    /// The trace can be attached to bug reports, and replayed using the
+    /// [`crate::agent::client::ReplayClient`].
+    #[prop_or_default]
+    pub recorder: Option<FlowRecorder>,
+
+    /// Persisting the session, restoring it when the application starts.
+    #[prop_or_default]
+    pub session_persistence: SessionPersistence,
+
+    /// The thresholds before the expiration of access tokens, for new and in-flight requests.
+    #[prop_or_default]
+    pub expiry: ExpiryPolicy,
+
+    /// The time for which the nonces of ID tokens are remembered, rejecting them when used again.
+    ///
+    /// This protects against replaying authorization responses, e.g. when multiple responses
+    /// arrive in popup or silent flows. A duration of zero disables the check.
+    #[prop_or(Duration::from_secs(600))]
+    pub nonce_window: Duration,
+
+    /// Handling of refresh tokens, issued when acquiring additional access tokens.
+    #[prop_or_default]
+    pub refresh_tokens: RefreshTokenPolicy,
+
+    /// When to refresh the session, before the access token expires.
+    ///
+    /// If `None`, the session is refreshed the [`Self::grace_period`] before the expiration.
+    #[prop_or_default]
+    pub refresh_ahead: Option<RefreshAhead>,
+
+    /// How to refresh the session while the page is hidden, e.g. in a background tab.
+    #[prop_or_default]
+    pub hidden_refresh: HiddenRefreshPolicy,
+
+    /// The maximum random time, by which a scheduled refresh is brought forward.
+    ///
+    /// This keeps multiple tabs, or many clients started at the same time, from refreshing at the
+    /// same instant. It never delays a refresh.
+    #[prop_or(Duration::from_secs(5))]
+    pub refresh_jitter: Duration,
+
+    /// Dispatch DOM events on the window, on changes of the session.
+    ///
+    /// This allows other frameworks on the page to react to the session, see
+    /// [`crate::agent::AgentEvent::dispatch`] for the events.
+    #[prop_or_default]
+    pub dom_events: bool,
+
+    /// Initialize the session from a bootstrap, handed over by the server.
+    ///
+    /// When present and valid, this takes precedence over a persisted session. See [`Bootstrap`].
+    #[prop_or_default]
+    pub bootstrap: Option<Bootstrap>,
+
+    /// The source of time for the expiration of tokens, and scheduling refreshes
+    ///
+    /// This applies to the whole application, and allows testing with a
+    /// [`crate::agent::VirtualTime`].
+    #[prop_or_default]
+    pub clock: Clock,
+
+    /// Cleaning up the URL, after returning from the issuer.
+    #[prop_or_default]
+    pub url_cleanup: UrlCleanupPolicy,
+
+    /// The public path the application is served from, e.g. `/app/`.
+    ///
+    /// If present, the default redirect URL is the public path on the current origin, instead of
+    /// the current URL. So that a single redirect URL can be registered with the issuer, also
+    /// when served behind a path-prefixed reverse proxy. Starting a login from a location outside
+    /// the public path fails.
+    #[prop_or_default]
+    pub public_path: Option<String>,
+
+    /// A sink, receiving each change of the [`OAuth2Context`].
+    ///
+    /// This allows mirroring the context into a global store (like one of `yewdux`), without
+    /// writing a subscriber component. The callback is invoked before the children get
+    /// re-rendered, also for changes which don't require re-rendering, like a rotated refresh
+    /// token.
+    #[prop_or_default]
+    pub on_context: Option<Callback<OAuth2Context>>,
+
+    /// Invoked once the login completed, right after exchanging the code returned by the issuer.
+    ///
+    /// Unlike [`Self::on_context`], this is not invoked for restored or refreshed sessions. So it
+    /// allows running one-shot logic, like an initial fetch of data, or navigating.
+    #[prop_or_default]
+    pub on_login_complete: Option<Callback<Authentication>>,
+
+    /// Invoked if the login failed, returning from the issuer.
+    #[prop_or_default]
+    pub on_login_failed: Option<Callback<OAuth2Error>>,
+
+    /// The paths of the claims containing the roles and scopes of the session.
+    #[prop_or_default]
+    pub claim_paths: ClaimPaths,
 }
 
 impl<C: Client> PartialEq for OAuth2Properties<C> {
@@ -60,7 +230,34 @@ impl<C: Client> PartialEq for OAuth2Properties<C> {
             && self.grace_period == other.grace_period
             && self.max_expiration == other.max_expiration
             && self.audience == other.audience
+            && self.unauthorized_policy == other.unauthorized_policy
+            && self.unknown_state_policy == other.unknown_state_policy
+            && self.health_check == other.health_check
+            && self.retry_policy == other.retry_policy
+            && self.random == other.random
+            && self.metrics == other.metrics
+            && self.base_path == other.base_path
+            && self.backend_session == other.backend_session
+            && self.keep_alive == other.keep_alive
+            && self.recorder == other.recorder
+            && self.session_persistence == other.session_persistence
+            && self.expiry == other.expiry
+            && self.nonce_window == other.nonce_window
+            && self.refresh_tokens == other.refresh_tokens
+            && self.refresh_ahead == other.refresh_ahead
+            && self.hidden_refresh == other.hidden_refresh
+            && self.refresh_jitter == other.refresh_jitter
+            && self.dom_events == other.dom_events
+            && self.bootstrap == other.bootstrap
+            && self.clock == other.clock
+            && self.url_cleanup == other.url_cleanup
+            && self.public_path == other.public_path
+            && self.on_context == other.on_context
+            && self.on_login_complete == other.on_login_complete
+            && self.on_login_failed == other.on_login_failed
+            && self.claim_paths == other.claim_paths
             && self.children == other.children
+            && self.loading == other.loading
     }
 }
 
@@ -69,14 +266,21 @@ impl<C: Client> PartialEq for OAuth2Properties<C> {
 /// All items making using of the OAuth2 or OpenID Connect context must be below this element.
 pub struct OAuth2<C: Client> {
     context: OAuth2Context,
+    health: IssuerHealth,
     latest_access_token: LatestAccessToken,
     agent: AgentContext<C>,
+    operations: AuthOperations,
     config: AgentConfiguration<C>,
+    session_hint: SessionHint,
+    _login_events: Option<EventSubscription>,
 }
 
 #[doc(hidden)]
 pub enum Msg {
     Context(OAuth2Context),
+    Health(IssuerHealth),
+    LoginComplete(Authentication),
+    LoginFailed(OAuth2Error),
 }
 
 impl<C: Client> Component for OAuth2<C> {
@@ -86,30 +290,84 @@ impl<C: Client> Component for OAuth2<C> {
     fn create(ctx: &Context<Self>) -> Self {
         let config = Self::make_config(ctx.props());
         let callback = ctx.link().callback(Msg::Context);
+        let health_callback = ctx.link().callback(Msg::Health);
+
+        let agent = crate::agent::Agent::new(move |s| callback.emit(s))
+            .with_health_callback(move |h| health_callback.emit(h));
 
-        let agent = crate::agent::Agent::new(move |s| callback.emit(s));
-        let _ = agent.configure(config.clone());
+        // subscribe before configuring, the agent handles the login response while configuring
+        let login_callback = ctx.link().batch_callback(|event| match event {
+            AgentEvent::LoginCompleted(auth) => Some(Msg::LoginComplete(auth)),
+            AgentEvent::LoginFailed(err) => Some(Msg::LoginFailed(err)),
+            _ => None,
+        });
+        let login_events = agent
+            .subscribe((move |event| login_callback.emit(event)).into())
+            .ok();
+
+        let context = match Self::validate(ctx.props()) {
+            Some(failed) => failed,
+            None => {
+                let _ = agent.configure(config.clone());
+                OAuth2Context::NotInitialized
+            }
+        };
+        let agent = AgentContext::new(agent);
+        let operations = AuthOperations::new(agent.clone());
+        operations.agent().set_global();
+
+        if let (Some(on_context), OAuth2Context::Failed { .. }) =
+            (&ctx.props().on_context, &context)
+        {
+            on_context.emit(context.clone());
+        }
 
         Self {
-            context: OAuth2Context::NotInitialized,
+            context,
+            health: IssuerHealth::Unknown,
             latest_access_token: LatestAccessToken {
                 access_token: Default::default(),
             },
-            agent: AgentContext::new(agent),
+            agent,
+            operations,
+            session_hint: Self::session_hint(ctx.props()),
             config,
+            _login_events: login_events,
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Self::Message::Context(context) => {
                 if self.context != context {
+                    if let Some(on_context) = &ctx.props().on_context {
+                        on_context.emit(context.clone());
+                    }
                     self.latest_access_token
                         .set_access_token(context.access_token());
+                    // a rotated refresh token alone doesn't require re-rendering the children,
+                    // they will still see the new context with the next render.
+                    let render = !self.context.eq_ignore_refresh_token(&context);
                     self.context = context;
+                    return render;
+                }
+            }
+            Self::Message::Health(health) => {
+                if self.health != health {
+                    self.health = health;
                     return true;
                 }
             }
+            Self::Message::LoginComplete(auth) => {
+                if let Some(on_login_complete) = &ctx.props().on_login_complete {
+                    on_login_complete.emit(auth);
+                }
+            }
+            Self::Message::LoginFailed(err) => {
+                if let Some(on_login_failed) = &ctx.props().on_login_failed {
+                    on_login_failed.emit(err);
+                }
+            }
         }
         false
     }
@@ -117,22 +375,41 @@ impl<C: Client> Component for OAuth2<C> {
     fn changed(&mut self, ctx: &Context<Self>, _: &Self::Properties) -> bool {
         let config = Self::make_config(ctx.props());
         if self.config != config {
-            // only reconfigure agent when necessary
+            // only reconfigure agent when necessary, an invalid configuration stops it
             let _ = self.agent.configure(config.clone());
             self.config = config;
         }
 
+        self.session_hint = Self::session_hint(ctx.props());
+
         true
     }
 
+    fn destroy(&mut self, _: &Context<Self>) {
+        self.operations.agent().clear_global();
+    }
+
     fn view(&self, ctx: &Context<Self>) -> Html {
+        let content = match (&self.context, &ctx.props().loading) {
+            (OAuth2Context::NotInitialized, Some(loading)) => loading.clone(),
+            _ => html!({ for ctx.props().children.iter() }),
+        };
+
         html!(
             <>
                 <ContextProvider<OAuth2Context> context={self.context.clone()} >
                     <ContextProvider<AgentContext<C>> context={self.agent.clone()}>
-                        <ContextProvider<LatestAccessToken> context={self.latest_access_token.clone()}>
-                            { for ctx.props().children.iter() }
-                        </ContextProvider<LatestAccessToken>>
+                        <ContextProvider<AuthOperations> context={self.operations.clone()}>
+                            <ContextProvider<LatestAccessToken> context={self.latest_access_token.clone()}>
+                                <ContextProvider<IssuerHealth> context={self.health}>
+                                    <ContextProvider<ClaimPaths> context={ctx.props().claim_paths.clone()}>
+                                        <ContextProvider<SessionHint> context={self.session_hint}>
+                                            { content }
+                                        </ContextProvider<SessionHint>>
+                                    </ContextProvider<ClaimPaths>>
+                                </ContextProvider<IssuerHealth>>
+                            </ContextProvider<LatestAccessToken>>
+                        </ContextProvider<AuthOperations>>
                     </ContextProvider<AgentContext<C>>>
                 </ContextProvider<OAuth2Context>>
             </>
@@ -141,6 +418,23 @@ impl<C: Client> Component for OAuth2<C> {
 }
 
 impl<C: Client> OAuth2<C> {
+    /// Validate the configuration, returning the failed context if it's invalid.
+    fn validate(props: &OAuth2Properties<C>) -> Option<OAuth2Context> {
+        C::validate_config(&props.config).err().map(|errors| {
+            log::error!("Invalid configuration: {errors:?}");
+            OAuth2Context::failed(
+                Operation::Discovery,
+                OAuth2Error::InvalidConfiguration(errors),
+            )
+        })
+    }
+
+    fn session_hint(props: &OAuth2Properties<C>) -> SessionHint {
+        SessionHint {
+            returning: has_session_hint(&AppScope::new(props.base_path.as_deref())),
+        }
+    }
+
     fn make_config(props: &OAuth2Properties<C>) -> AgentConfiguration<C> {
         AgentConfiguration {
             config: props.config.clone(),
@@ -150,16 +444,60 @@ impl<C: Client> OAuth2<C> {
             audience: props.audience.clone(),
             default_login_options: props.login_options.clone(),
             default_logout_options: props.logout_options.clone(),
+            unauthorized_policy: props.unauthorized_policy,
+            unknown_state_policy: props.unknown_state_policy,
+            health_check: props.health_check,
+            retry_policy: props.retry_policy,
+            random: props.random.clone(),
+            metrics: props.metrics.clone(),
+            base_path: props.base_path.clone(),
+            backend_session: props.backend_session.clone(),
+            keep_alive: props.keep_alive.clone(),
+            recorder: props.recorder.clone(),
+            session_persistence: props.session_persistence,
+            expiry: props.expiry,
+            nonce_window: props.nonce_window,
+            refresh_tokens: props.refresh_tokens,
+            refresh_ahead: props.refresh_ahead,
+            hidden_refresh: props.hidden_refresh,
+            refresh_jitter: props.refresh_jitter,
+            dom_events: props.dom_events,
+            bootstrap: props.bootstrap.clone(),
+            clock: props.clock.clone(),
+            url_cleanup: props.url_cleanup.clone(),
+            public_path: props.public_path.clone(),
         }
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{agent::client::OAuth2Client, config::oauth2::Config};
+
+    fn props() -> OAuth2Properties<OAuth2Client> {
+        yew::props!(OAuth2Properties<OAuth2Client> {
+            config: Config::new("client", "https://issuer/auth", "https://issuer/token"),
+        })
+    }
+
+    /// Properties using the defaults must not reconfigure the agent on each render.
+    #[test]
+    fn default_config_is_stable() {
+        assert_eq!(OAuth2::make_config(&props()), OAuth2::make_config(&props()));
+    }
+}
+
 #[cfg(feature = "openid")]
 pub mod openid {
     //! Convenient access to OpenID Connect context
     pub type OAuth2 = super::OAuth2<crate::agent::client::OpenIdClient>;
 }
 
+/// A context with a [`crate::agent::client::MockClient`], for tests and demos.
+#[cfg(feature = "testing")]
+pub type MockOAuth2 = OAuth2<crate::agent::client::MockClient>;
+
 pub mod oauth2 {
     //! Convenient access to OAuth2 context
     pub type OAuth2 = super::OAuth2<crate::agent::client::OAuth2Client>;
@@ -0,0 +1,110 @@
+use super::Agent;
+use crate::agent::{
+    AccessToken, AgentCallback, AgentEvent, Client, ContextStream, Error, EventSubscription,
+    LoginFunnel, LoginOptions, LogoutOptions, OAuth2Agent, TokenRequest,
+};
+use reqwest::Url;
+use yew::hook;
+
+/// A handle to the operations of the agent, independent of its client.
+///
+/// Unlike [`Agent`], this doesn't require knowing the type of the client. So that deeply nested
+/// components, or reusable components, can log in, log out, or refresh the session, without
+/// passing down the agent.
+#[derive(Clone)]
+pub struct AuthOperations(OAuth2Agent, usize);
+
+impl AuthOperations {
+    pub fn new<C: Client>(agent: Agent<C>) -> Self {
+        let id = agent.id();
+        Self(OAuth2Agent::new((*agent).clone()), id)
+    }
+
+    /// Get the handle of the agent, independent of the component tree.
+    pub fn agent(&self) -> &OAuth2Agent {
+        &self.0
+    }
+
+    /// See [`crate::agent::OAuth2Operations::start_login`]
+    pub fn start_login(&self) -> Result<(), Error> {
+        self.0.start_login()
+    }
+
+    /// See [`crate::agent::OAuth2Operations::start_login_opts`]
+    pub fn start_login_opts(&self, options: LoginOptions) -> Result<(), Error> {
+        self.0.start_login_opts(options)
+    }
+
+    /// See [`crate::agent::OAuth2Operations::logout`]
+    pub fn logout(&self) -> Result<(), Error> {
+        self.0.logout()
+    }
+
+    /// See [`crate::agent::OAuth2Operations::logout_opts`]
+    pub fn logout_opts(&self, options: LogoutOptions) -> Result<(), Error> {
+        self.0.logout_opts(options)
+    }
+
+    /// See [`crate::agent::OAuth2Operations::report_unauthorized`]
+    pub fn report_unauthorized(&self) -> Result<(), Error> {
+        self.0.report_unauthorized()
+    }
+
+    /// See [`crate::agent::OAuth2Operations::account_url`]
+    pub async fn account_url(&self, return_url: Option<Url>) -> Result<Option<Url>, Error> {
+        self.0.account_url(return_url).await
+    }
+
+    /// See [`crate::agent::OAuth2Operations::acquire_token`]
+    pub async fn acquire_token(&self, request: TokenRequest) -> Result<AccessToken, Error> {
+        self.0.acquire_token(request).await
+    }
+
+    /// See [`crate::agent::OAuth2Operations::login_funnel`]
+    pub async fn login_funnel(&self) -> Result<LoginFunnel, Error> {
+        self.0.login_funnel().await
+    }
+
+    /// See [`crate::agent::OAuth2Operations::retry`]
+    pub fn retry(&self) -> Result<(), Error> {
+        self.0.retry()
+    }
+
+    /// See [`crate::agent::OAuth2Operations::refresh`]
+    pub fn refresh(&self) -> Result<(), Error> {
+        self.0.refresh_now()
+    }
+
+    /// See [`crate::agent::OAuth2Operations::subscribe`]
+    pub fn subscribe(
+        &self,
+        callback: impl Into<AgentCallback<AgentEvent>>,
+    ) -> Result<EventSubscription, Error> {
+        self.0.subscribe(callback)
+    }
+
+    /// See [`crate::agent::OAuth2Operations::stream`]
+    pub fn stream(&self) -> ContextStream {
+        self.0.stream()
+    }
+}
+
+impl PartialEq for AuthOperations {
+    fn eq(&self, other: &Self) -> bool {
+        self.1.eq(&other.1)
+    }
+}
+
+impl std::fmt::Debug for AuthOperations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AuthOperations").field(&self.1).finish()
+    }
+}
+
+/// Get the operations of the authentication agent.
+///
+/// In contrast to [`super::use_auth_agent`], this works without knowing the type of the client.
+#[hook]
+pub fn use_auth_operations() -> Option<AuthOperations> {
+    yew::prelude::use_context()
+}
@@ -1,7 +1,7 @@
 //! The [`Failure`] component
 
 use super::missing_context;
-use crate::context::OAuth2Context;
+use crate::{agent::OAuth2Error, context::OAuth2Context};
 use yew::prelude::*;
 
 /// Properties for the [`Failure`] component
@@ -27,7 +27,7 @@ pub fn failure(props: &FailureProps) -> Html {
 
     match auth {
         None => missing_context(),
-        Some(OAuth2Context::Failed(..)) => {
+        Some(OAuth2Context::Failed { .. }) => {
             html!(
                 <@{element}
                     id={ props.id.clone() }
@@ -42,7 +42,7 @@ pub fn failure(props: &FailureProps) -> Html {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Properties)]
+#[derive(Clone, Debug, PartialEq, Properties)]
 pub struct FailureMessageProps {
     #[prop_or_default]
     pub id: Option<String>,
@@ -52,8 +52,34 @@ pub struct FailureMessageProps {
     pub class: Option<String>,
     #[prop_or_default]
     pub element: Option<String>,
+    /// Render the error, instead of showing its message.
+    #[prop_or_default]
+    pub children: Option<Callback<OAuth2Error, Html>>,
 }
 
+/// A Yew component, rendering the error when the agent failed.
+///
+/// By default, this renders the message of the error. Providing a function as child allows
+/// rendering the error instead:
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+///
+/// #[function_component(MyFailure)]
+/// fn my_failure() -> Html {
+///     html!(
+///         <FailureMessage>
+///             { |err: OAuth2Error| match err {
+///                 OAuth2Error::Server { error, .. } if error == "access_denied" => {
+///                     html!({ "You denied access" })
+///                 }
+///                 err => html!({ err.to_string() }),
+///             } }
+///         </FailureMessage>
+///     )
+/// }
+/// ```
 #[function_component(FailureMessage)]
 pub fn failure_message(props: &FailureMessageProps) -> Html {
     let auth = use_context::<OAuth2Context>();
@@ -62,14 +88,18 @@ pub fn failure_message(props: &FailureMessageProps) -> Html {
 
     match auth {
         None => missing_context(),
-        Some(OAuth2Context::Failed(message)) => {
+        Some(OAuth2Context::Failed { error, .. }) => {
+            let content = match &props.children {
+                Some(render) => render.emit(error),
+                None => html!({ error.to_string() }),
+            };
             html!(
                 <@{element}
                     id={ props.id.clone() }
                     style={ props.style.clone() }
                     class={ &props.class }
                     >
-                    { message }
+                    { content }
                 </@>
             )
         }
@@ -0,0 +1,27 @@
+//! The [`IssuerUnreachable`] component
+
+use super::missing_context;
+use crate::context::IssuerHealth;
+use yew::prelude::*;
+
+/// Properties for the [`IssuerUnreachable`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct IssuerUnreachableProperties {
+    /// The children to show when the issuer is unreachable.
+    pub children: Children,
+}
+
+/// A Yew component, rendering when the last health check could not reach the issuer.
+///
+/// This requires health checks to be enabled on the [`crate::components::context::OAuth2`]
+/// component.
+#[function_component(IssuerUnreachable)]
+pub fn issuer_unreachable(props: &IssuerUnreachableProperties) -> Html {
+    let health = use_context::<IssuerHealth>();
+
+    match health {
+        None => missing_context(),
+        Some(IssuerHealth::Unreachable) => html!({ for props.children.iter() }),
+        Some(IssuerHealth::Unknown | IssuerHealth::Reachable) => html!(),
+    }
+}
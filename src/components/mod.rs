@@ -1,18 +1,30 @@
 //! Components used when rendering HTML
 
+pub mod account;
 pub mod authenticated;
+pub mod authorized;
 pub mod context;
 pub mod failure;
+pub mod health;
 pub mod noauth;
 pub mod redirect;
+pub mod retry;
+pub mod scoped;
 pub mod use_authentication;
+#[cfg(feature = "openid")]
+pub mod user;
 
 // only put pub use for common components
 
 pub use authenticated::*;
+pub use authorized::*;
 pub use failure::*;
+pub use health::*;
 pub use noauth::*;
+pub use scoped::{ScopedLogin, SignIn};
 pub use use_authentication::*;
+#[cfg(feature = "openid")]
+pub use user::*;
 
 use yew::prelude::*;
 
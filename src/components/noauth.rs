@@ -18,7 +18,7 @@ pub fn not_authenticated(props: &Props) -> Html {
     match auth {
         None => missing_context(),
         Some(OAuth2Context::NotInitialized) => html!(),
-        Some(OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed(..)) => {
+        Some(OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed { .. }) => {
             html!({ for props.children.iter() })
         }
         Some(OAuth2Context::Authenticated { .. }) => {
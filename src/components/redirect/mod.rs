@@ -3,6 +3,8 @@
 pub mod location;
 #[cfg(feature = "yew-nested-router")]
 pub mod router;
+#[cfg(feature = "yew-router")]
+pub mod yew_router;
 
 use super::missing_context;
 use crate::agent::{Client, OAuth2Operations};
@@ -124,7 +126,7 @@ where
 
         match &auth {
             OAuth2Context::NotInitialized
-            | OAuth2Context::Failed(..)
+            | OAuth2Context::Failed { .. }
             | OAuth2Context::Authenticated { .. } => {
                 // nothing that we should handle
             }
@@ -135,7 +137,7 @@ where
                         let _ = agent.start_login();
                     }
                 }
-                Reason::Expired | Reason::Logout => {
+                Reason::Expired | Reason::Logout(_) | Reason::Invalid => {
                     match self.auth {
                         None | Some(OAuth2Context::NotInitialized) => {
                             if let Some(agent) = &mut self.agent {
@@ -148,6 +150,9 @@ where
                         }
                     }
                 }
+                Reason::TemporarilyUnavailable => {
+                    // the agent will retry the login
+                }
             },
         }
 
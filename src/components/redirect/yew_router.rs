@@ -0,0 +1,144 @@
+//! Redirect by pushing a new [`yew_router::Routable`] route.
+//!
+//! Returning to the route after the login is handled by the agent, using
+//! [`crate::agent::LoginOptions::with_yew_router_redirect`].
+
+use super::{Redirect, Redirector, RedirectorProperties};
+use crate::components::missing_context;
+use crate::context::OAuth2Context;
+use ::yew_router::prelude::*;
+use yew::prelude::*;
+
+/// A redirector using the official Yew router, and the Browser's History API.
+pub struct RouterRedirector<R>
+where
+    R: Routable + 'static,
+{
+    navigator: Option<Navigator>,
+    _marker: std::marker::PhantomData<R>,
+}
+
+impl<R> Redirector for RouterRedirector<R>
+where
+    R: Routable + 'static,
+{
+    type Properties = RouterProperties<R>;
+
+    fn new<COMP: Component>(ctx: &Context<COMP>) -> Self {
+        // while the "route" can change, the "navigator" itself does not.
+        Self {
+            navigator: ctx.link().navigator(),
+            _marker: Default::default(),
+        }
+    }
+
+    fn logout(&self, props: &Self::Properties) {
+        let route = props.logout.clone();
+        log::debug!("ChangeRoute due to logout: {}", route.to_path());
+
+        if let Some(navigator) = &self.navigator {
+            navigator.push(&route);
+        }
+    }
+}
+
+/// Properties for the [`RouterRedirector`] component.
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct RouterProperties<R>
+where
+    R: Routable + 'static,
+{
+    #[prop_or_default]
+    pub children: Html,
+    pub logout: R,
+}
+
+impl<R> RedirectorProperties for RouterProperties<R>
+where
+    R: Routable + 'static,
+{
+    fn children(&self) -> &Html {
+        &self.children
+    }
+}
+
+/// Properties for the [`RouteGuard`] component.
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct RouteGuardProperties<R>
+where
+    R: Routable + 'static,
+{
+    /// The content of the guarded route, shown when the session is authenticated.
+    #[prop_or_default]
+    pub children: Html,
+
+    /// The route to redirect to, when the session is not authenticated, e.g. a public landing
+    /// page.
+    pub redirect: R,
+}
+
+/// A guard for routes, which require an authenticated session.
+///
+/// Unlike [`RouterRedirector`], it doesn't start the login, but replaces the route with a public
+/// one. A login started from there can return to the guarded route, using
+/// [`crate::agent::LoginOptions::with_post_login_route`].
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::oauth2::yew_router::*;
+/// use yew_router::prelude::*;
+///
+/// #[derive(Clone, PartialEq, Routable)]
+/// enum Route {
+///     #[at("/")]
+///     Home,
+///     #[at("/account")]
+///     Account,
+/// }
+///
+/// fn switch(route: Route) -> Html {
+///     match route {
+///         Route::Home => html!(<p>{ "Welcome" }</p>),
+///         Route::Account => html!(
+///             <RouteGuard<Route> redirect={Route::Home}>
+///                 <p>{ "Your account" }</p>
+///             </RouteGuard<Route>>
+///         ),
+///     }
+/// }
+/// ```
+#[function_component(RouteGuard)]
+pub fn route_guard<R>(props: &RouteGuardProperties<R>) -> Html
+where
+    R: Routable + 'static,
+{
+    let auth = use_context::<OAuth2Context>();
+
+    match auth {
+        None => missing_context(),
+        Some(OAuth2Context::Authenticated(..)) => props.children.clone(),
+        Some(OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed { .. }) => {
+            html!(<::yew_router::components::Redirect<R> to={props.redirect.clone()} />)
+        }
+        Some(OAuth2Context::NotInitialized) => html!(),
+    }
+}
+
+pub mod oauth2 {
+    //! Convenient access for the OAuth2 variant
+    use super::*;
+    pub use super::{RouteGuard, RouteGuardProperties};
+    use crate::agent::client::OAuth2Client;
+    pub type RouterRedirect<R> = Redirect<OAuth2Client, RouterRedirector<R>>;
+}
+
+#[cfg(feature = "openid")]
+pub mod openid {
+    //! Convenient access for the Open ID Connect variant
+    use super::*;
+    pub use super::{RouteGuard, RouteGuardProperties};
+    use crate::agent::client::OpenIdClient;
+    pub type RouterRedirect<R> = Redirect<OpenIdClient, RouterRedirector<R>>;
+}
@@ -0,0 +1,91 @@
+//! The [`Retry`] component
+
+use super::missing_context;
+use crate::{
+    agent::{Client, OAuth2Operations},
+    components::context::use_auth_agent,
+    hook::use_auth_state,
+};
+use yew::prelude::*;
+
+/// Properties for the [`Retry`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct RetryProperties {
+    #[prop_or_default]
+    pub id: Option<String>,
+    #[prop_or_default]
+    pub style: Option<String>,
+    #[prop_or_default]
+    pub class: Option<String>,
+    /// The content of the button.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A Yew component, rendering a button to retry the failed operation.
+///
+/// The button is only rendered when the context is [`crate::context::OAuth2Context::Failed`].
+/// Clicking it re-triggers the failed [`crate::context::Operation`] using the agent, instead of
+/// reloading the page.
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+/// use yew_oauth2::oauth2::*;
+///
+/// #[function_component(MyFailure)]
+/// fn my_failure() -> Html {
+///     html!(
+///         <Failure>
+///             <FailureMessage/>
+///             <Retry>{ "Try again" }</Retry>
+///         </Failure>
+///     )
+/// }
+/// ```
+#[function_component(Retry)]
+pub fn retry<C>(props: &RetryProperties) -> Html
+where
+    C: Client,
+{
+    let auth = use_auth_state();
+    let agent = use_auth_agent::<C>();
+
+    let onclick = use_callback(agent.clone(), |_: MouseEvent, agent| {
+        if let Some(agent) = agent {
+            if let Err(err) = agent.retry() {
+                log::warn!("Failed to retry: {err}");
+            }
+        }
+    });
+
+    match (auth, agent) {
+        (None, _) | (_, None) => missing_context(),
+        (Some(auth), Some(_)) if auth.failed_operation().is_some() => html!(
+            <button
+                id={ props.id.clone() }
+                style={ props.style.clone() }
+                class={ &props.class }
+                {onclick}
+                >
+                { for props.children.iter() }
+            </button>
+        ),
+        (Some(_), Some(_)) => html!(),
+    }
+}
+
+pub mod oauth2 {
+    //! Convenient access for the OAuth2 variant
+    use crate::agent::client::OAuth2Client as Client;
+    pub type Retry = super::Retry<Client>;
+}
+
+#[cfg(feature = "openid")]
+pub mod openid {
+    //! Convenient access for the Open ID Connect variant
+    use crate::agent::client::OpenIdClient as Client;
+    pub type Retry = super::Retry<Client>;
+}
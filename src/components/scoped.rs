@@ -0,0 +1,157 @@
+//! The [`LoginScope`] and [`SignIn`] components
+
+use super::missing_context;
+use crate::{
+    agent::{Client, LoginOptions, OAuth2Operations},
+    components::context::use_auth_agent,
+    context::OAuth2Context,
+    hook::{use_auth_state, use_scoped_login},
+};
+use yew::prelude::*;
+
+/// A handle to start the login of a [`LoginScope`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopedLogin {
+    login: Callback<()>,
+}
+
+impl ScopedLogin {
+    /// Start the login, navigating to the issuer.
+    pub fn login(&self) {
+        self.login.emit(());
+    }
+}
+
+/// Properties for the [`LoginScope`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct LoginScopeProperties {
+    /// The options to use when starting the login.
+    ///
+    /// Defaults to the login options of the [`crate::components::context::OAuth2`] component.
+    #[prop_or_default]
+    pub login_options: Option<LoginOptions>,
+
+    /// Children which will have access to the [`ScopedLogin`].
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A Yew component, scoping the login to a part of the page, like an embedded widget.
+///
+/// Unlike the redirect components, it will not start a login when the session is not
+/// authenticated. It shares the agent of the wrapping [`crate::components::context::OAuth2`]
+/// component, and only starts the login when requested by one of its children, using a
+/// [`SignIn`] component or the [`crate::hook::use_scoped_login`] hook.
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+/// use yew_oauth2::oauth2::*;
+///
+/// #[function_component(Comments)]
+/// fn comments() -> Html {
+///     html!(
+///         <LoginScope>
+///             <Authenticated>
+///                 <textarea/>
+///             </Authenticated>
+///             <SignIn>{ "Sign in to comment" }</SignIn>
+///         </LoginScope>
+///     )
+/// }
+/// ```
+#[function_component(LoginScope)]
+pub fn login_scope<C>(props: &LoginScopeProperties) -> Html
+where
+    C: Client,
+{
+    let agent = use_auth_agent::<C>();
+
+    let login = use_callback(
+        (agent.clone(), props.login_options.clone()),
+        |(), (agent, login_options)| {
+            let Some(agent) = agent else {
+                return;
+            };
+            let result = match login_options {
+                Some(login_options) => agent.start_login_opts(login_options.clone()),
+                None => agent.start_login(),
+            };
+            if let Err(err) = result {
+                log::warn!("Failed to start login: {err}");
+            }
+        },
+    );
+
+    match agent {
+        None => missing_context(),
+        Some(_) => html!(
+            <ContextProvider<ScopedLogin> context={ScopedLogin { login }}>
+                { for props.children.iter() }
+            </ContextProvider<ScopedLogin>>
+        ),
+    }
+}
+
+/// Properties for the [`SignIn`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct SignInProperties {
+    #[prop_or_default]
+    pub id: Option<String>,
+    #[prop_or_default]
+    pub style: Option<String>,
+    #[prop_or_default]
+    pub class: Option<String>,
+    /// The content of the button.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A Yew component, rendering an inline sign-in button when the session is not authenticated.
+///
+/// Clicking the button starts the login of the wrapping [`LoginScope`].
+#[function_component(SignIn)]
+pub fn sign_in(props: &SignInProperties) -> Html {
+    let auth = use_auth_state();
+    let scope = use_scoped_login();
+
+    let onclick = use_callback(scope.clone(), |_: MouseEvent, scope| {
+        if let Some(scope) = scope {
+            scope.login();
+        }
+    });
+
+    match (auth, scope) {
+        (None, _) | (_, None) => missing_context(),
+        (Some(OAuth2Context::NotAuthenticated { .. } | OAuth2Context::Failed { .. }), Some(_)) => {
+            html!(
+                <button
+                    id={ props.id.clone() }
+                    style={ props.style.clone() }
+                    class={ &props.class }
+                    {onclick}
+                    >
+                    { for props.children.iter() }
+                </button>
+            )
+        }
+        (Some(OAuth2Context::NotInitialized | OAuth2Context::Authenticated(..)), Some(_)) => {
+            html!()
+        }
+    }
+}
+
+pub mod oauth2 {
+    //! Convenient access for the OAuth2 variant
+    use crate::agent::client::OAuth2Client as Client;
+    pub type LoginScope = super::LoginScope<Client>;
+}
+
+#[cfg(feature = "openid")]
+pub mod openid {
+    //! Convenient access for the Open ID Connect variant
+    use crate::agent::client::OpenIdClient as Client;
+    pub type LoginScope = super::LoginScope<Client>;
+}
@@ -0,0 +1,136 @@
+//! The [`UserProvider`] component
+
+use crate::{
+    context::{Claims, UserInfo},
+    hook::use_auth_state,
+};
+use std::{fmt::Formatter, rc::Rc};
+use yew::prelude::*;
+
+type MapperFn<U> = dyn Fn(&Claims, Option<&UserInfo>) -> U;
+
+/// A function mapping the claims of the session to an application specific user model.
+pub struct UserMapper<U>(Rc<MapperFn<U>>);
+
+impl<U> UserMapper<U> {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Claims, Option<&UserInfo>) -> U + 'static,
+    {
+        Self(Rc::new(f))
+    }
+
+    /// Map the claims to the user model.
+    pub fn map(&self, claims: &Claims, user_info: Option<&UserInfo>) -> U {
+        (self.0)(claims, user_info)
+    }
+}
+
+impl<U, F> From<F> for UserMapper<U>
+where
+    F: Fn(&Claims, Option<&UserInfo>) -> U + 'static,
+{
+    fn from(f: F) -> Self {
+        Self::new(f)
+    }
+}
+
+impl<U> Clone for UserMapper<U> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<U> PartialEq for UserMapper<U> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<U> std::fmt::Debug for UserMapper<U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UserMapper")
+    }
+}
+
+/// Properties for the [`UserProvider`] component
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct UserProviderProperties<U>
+where
+    U: PartialEq + 'static,
+{
+    /// The mapper, creating the user model from the claims.
+    pub mapper: UserMapper<U>,
+
+    /// Children which will have access to the user model.
+    #[prop_or_default]
+    pub children: Children,
+}
+
+/// A Yew component providing an application specific user model to its children.
+///
+/// The user model is created from the claims of the session using the provided mapper. It is only
+/// re-created when the claims (or the user info) change. Children can access it using the
+/// [`crate::hook::use_user`] hook. The value of the context is `None` when the session is not
+/// authenticated.
+///
+/// ## Example
+///
+/// ```rust
+/// use yew::prelude::*;
+/// use yew_oauth2::prelude::*;
+///
+/// #[derive(PartialEq)]
+/// pub struct AppUser {
+///     pub name: String,
+/// }
+///
+/// #[function_component(MyApplication)]
+/// fn my_app() -> Html {
+///     let mapper = use_memo((), |()| {
+///         UserMapper::new(|claims: &Claims, _: Option<&UserInfo>| AppUser {
+///             name: claims.subject().to_string(),
+///         })
+///     });
+///
+///     html!(
+///         <UserProvider<AppUser> mapper={(*mapper).clone()}>
+///             <MyApplicationMain/>
+///         </UserProvider<AppUser>>
+///     )
+/// }
+///
+/// #[function_component(MyApplicationMain)]
+/// fn my_app_main() -> Html {
+///     match use_user::<AppUser>() {
+///         Some(user) => html!({ format!("Hello {}", user.name) }),
+///         None => html!(),
+///     }
+/// }
+/// ```
+#[function_component(UserProvider)]
+pub fn user_provider<U>(props: &UserProviderProperties<U>) -> Html
+where
+    U: PartialEq + 'static,
+{
+    let auth = use_auth_state();
+    let authentication = auth.as_ref().and_then(|auth| auth.authentication());
+
+    let claims = authentication.and_then(|auth| auth.claims.clone());
+    let user_info = authentication.and_then(|auth| auth.user_info.clone());
+
+    let user = use_memo(
+        (claims, user_info, props.mapper.clone()),
+        |(claims, user_info, mapper)| {
+            claims
+                .as_ref()
+                .map(|claims| Rc::new(mapper.map(claims, user_info.as_deref())))
+        },
+    );
+
+    html!(
+        <ContextProvider<Option<Rc<U>>> context={(*user).clone()}>
+            { for props.children.iter() }
+        </ContextProvider<Option<Rc<U>>>>
+    )
+}
@@ -1,10 +1,118 @@
 //! Configuration
 
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// A problem with a configuration, found by validating it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A required value is missing.
+    Missing {
+        /// The name of the field
+        field: &'static str,
+    },
+    /// A URL is malformed.
+    InvalidUrl {
+        /// The name of the field
+        field: &'static str,
+        /// The malformed value
+        value: String,
+        /// What is wrong with the value
+        message: String,
+    },
+    /// Two options can't be used together.
+    Incompatible {
+        /// The names of the fields
+        fields: [&'static str; 2],
+        /// Why they can't be used together
+        message: &'static str,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { field } => write!(f, "missing value for `{field}`"),
+            Self::InvalidUrl {
+                field,
+                value,
+                message,
+            } => write!(f, "invalid URL for `{field}` ({value}): {message}"),
+            Self::Incompatible {
+                fields: [a, b],
+                message,
+            } => write!(f, "`{a}` and `{b}` can't be used together: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Collects the problems found while validating a configuration.
+#[derive(Default)]
+struct Validator(Vec<ConfigError>);
+
+impl Validator {
+    fn required(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.0.push(ConfigError::Missing { field });
+        }
+    }
+
+    fn url(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.0.push(ConfigError::Missing { field });
+        } else {
+            self.optional_url(field, Some(value));
+        }
+    }
+
+    fn optional_url(&mut self, field: &'static str, value: Option<&str>) {
+        if let Some(Err(err)) = value.map(Url::parse) {
+            self.0.push(ConfigError::InvalidUrl {
+                field,
+                value: value.unwrap_or_default().to_string(),
+                message: err.to_string(),
+            });
+        }
+    }
+
+    /// A required URL, which may also be a path relative to the origin of the page.
+    fn page_url(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.0.push(ConfigError::Missing { field });
+        } else {
+            self.optional_page_url(field, Some(value));
+        }
+    }
+
+    /// An optional URL, which may also be a path relative to the origin of the page.
+    fn optional_page_url(&mut self, field: &'static str, value: Option<&str>) {
+        if !value.is_some_and(|value| value.starts_with('/')) {
+            self.optional_url(field, value);
+        }
+    }
+
+    fn incompatible(&mut self, conflict: bool, fields: [&'static str; 2], message: &'static str) {
+        if conflict {
+            self.0.push(ConfigError::Incompatible { fields, message });
+        }
+    }
+
+    fn finish(self) -> Result<(), Vec<ConfigError>> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(self.0),
+        }
+    }
+}
 
 /// Configuration for OpenID Connect
 pub mod openid {
     use super::*;
+    use std::time::Duration;
 
     /// OpenID Connect client configuration
     ///
@@ -26,12 +134,236 @@ pub mod openid {
         /// The name of the query parameter for the post logout redirect.
         ///
         /// The defaults to `post_logout_redirect_uri` for OpenID RP initiated logout.
-        /// However, e.g. older Keycloak instances, require this to be `redirect_uri`.
+        /// However, e.g. older Keycloak instances (before 18), require this to be `redirect_uri`,
+        /// and Cognito requires `logout_uri`.
         pub post_logout_redirect_name: Option<String>,
         /// Additional audiences of the ID token which are considered trustworthy.
         ///
         /// Those audiences are allowed in addition to the client ID.
         pub additional_trusted_audiences: Vec<String>,
+        /// The URL of the issuer's account management console.
+        ///
+        /// For Keycloak, this would be `{issuer_url}/account`.
+        pub account_url: Option<String>,
+        /// Fetch the user info from the issuer's user info endpoint after the login.
+        #[serde(default)]
+        pub fetch_user_info: bool,
+        /// Additional parameters sent with the authorization request.
+        ///
+        /// This can be used for issuer specific parameters, like Auth0's `organization`.
+        #[serde(default)]
+        pub additional_auth_params: Vec<(String, String)>,
+        /// Additional parameters sent to the end session URL when logging out.
+        ///
+        /// This can be used for issuer specific parameters, like Cognito's `client_id`.
+        #[serde(default)]
+        pub additional_logout_params: Vec<(String, String)>,
+        /// Accept an issuer containing a `{tenantid}` placeholder.
+        ///
+        /// Multi-tenant issuers, like the `common` and `organizations` tenants of Azure AD, announce
+        /// an issuer of `https://login.microsoftonline.com/{tenantid}/v2.0`, which never matches
+        /// the issuer URL. When enabled, the placeholder is accepted during discovery, and the
+        /// issuer of the ID token is verified by replacing the placeholder with its `tid` claim.
+        #[serde(default)]
+        pub multi_tenant: bool,
+        /// The endpoints of the issuer, skipping the discovery.
+        #[serde(default)]
+        pub metadata: Option<StaticMetadata>,
+        /// Cache the discovered metadata in the local storage, for this duration.
+        ///
+        /// This saves the discovery round trip when the application is loaded again. Cached
+        /// metadata is discarded when the verification of an ID token fails, as the keys may have
+        /// been rotated.
+        #[serde(default)]
+        pub discovery_cache_ttl: Option<Duration>,
+        /// The URL of the discovery document, overriding the one derived from the issuer URL.
+        ///
+        /// By default, the discovery document is fetched from
+        /// `{issuer_url}/.well-known/openid-configuration`. The issuer announced by the document
+        /// must still match the issuer URL.
+        #[serde(default)]
+        pub metadata_url: Option<String>,
+        /// Issuers accepted in addition to the issuer URL.
+        ///
+        /// Some setups, like reverse proxied issuers, announce (and issue ID tokens with) an
+        /// issuer which differs from the issuer URL used for discovery. Issuers listed here are
+        /// accepted for the discovery document and the `iss` claim of ID tokens.
+        ///
+        /// **NOTE:** Verifying the issuer prevents accepting tokens of a different issuer. Only
+        /// list issuers which are under the same control as the configured one.
+        #[serde(default)]
+        pub expected_issuers: Vec<String>,
+        /// Options for validating the claims of ID tokens.
+        #[serde(default)]
+        pub id_token_validation: IdTokenValidation,
+        /// An override for the token endpoint URL, keeping the other endpoints of the metadata.
+        ///
+        /// Some issuers don't send CORS headers for their token endpoint, so that a browser
+        /// can't call it. Requests to the token endpoint can then be sent through a proxy,
+        /// which may be a path on the origin of the application, like `/oauth/token`.
+        #[serde(default)]
+        pub token_url: Option<String>,
+        /// The URL the issuer redirects to after the login, instead of the current URL.
+        ///
+        /// This may be a path relative to the origin of the page, e.g. `/auth/callback`, so that
+        /// the same configuration works on different origins. The redirect URL of the
+        /// [`crate::agent::LoginOptions`] takes precedence.
+        #[serde(default)]
+        pub redirect_url: Option<String>,
+        /// The HTTP client for the requests to the issuer, defaults to `reqwest`.
+        #[serde(skip)]
+        pub http_client: Option<crate::agent::client::SharedHttpClient>,
+        /// The worker to send requests to the token endpoint through, keeping the refresh tokens.
+        #[cfg(feature = "worker")]
+        #[serde(skip)]
+        pub token_vault: Option<crate::worker::TokenVault>,
+    }
+
+    /// Options for validating the claims of ID tokens.
+    ///
+    /// The defaults follow the OpenID Connect specification, relaxing them may be required for
+    /// some issuers.
+    ///
+    /// **NOTE**: This is a non-exhaustive struct. See [`Config`] for an example on how to work
+    /// with this.
+    #[non_exhaustive]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct IdTokenValidation {
+        /// Require the client ID to be one of the audiences (`aud`) of the ID token.
+        ///
+        /// Additional audiences must still be listed in [`Config::additional_trusted_audiences`].
+        pub require_audience: bool,
+        /// Verify the authorized party (`azp`) of the ID token.
+        ///
+        /// If the ID token has multiple audiences, the authorized party must be present. If it is
+        /// present, it must be the client ID.
+        pub verify_authorized_party: bool,
+        /// The algorithms accepted for signing ID tokens, like `RS256`.
+        ///
+        /// If empty, the algorithms announced by the issuer are accepted.
+        pub signing_algs: Vec<String>,
+        /// The difference between the clocks of the browser and the issuer which is tolerated.
+        ///
+        /// The timestamps of an ID token are taken from the clock of the issuer. An ID token is
+        /// still accepted for the leeway after it expired (`exp`), and the leeway before it was
+        /// issued (`iat`) or becomes valid (`nbf`). Defaults to 60 seconds.
+        ///
+        /// The expiration of the session itself is computed from the lifetime of the access
+        /// token (`expires_in`), using the clock of the browser only. A skewed clock doesn't
+        /// affect it.
+        pub clock_leeway: Duration,
+        /// Require the time of the authentication (`auth_time`) to be present.
+        pub require_auth_time: bool,
+        /// The maximum time since the authentication (`auth_time`).
+        ///
+        /// If the ID token has no time of the authentication, it is only rejected if
+        /// [`Self::require_auth_time`] is set as well.
+        pub max_auth_age: Option<Duration>,
+    }
+
+    /// The default of [`IdTokenValidation::clock_leeway`].
+    pub const DEFAULT_CLOCK_LEEWAY: Duration = Duration::from_secs(60);
+
+    impl Default for IdTokenValidation {
+        fn default() -> Self {
+            Self {
+                require_audience: true,
+                verify_authorized_party: false,
+                signing_algs: vec![],
+                clock_leeway: DEFAULT_CLOCK_LEEWAY,
+                require_auth_time: false,
+                max_auth_age: None,
+            }
+        }
+    }
+
+    impl IdTokenValidation {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Set if the client ID must be an audience
+        pub fn with_require_audience(mut self, require_audience: bool) -> Self {
+            self.require_audience = require_audience;
+            self
+        }
+
+        /// Set if the authorized party is verified
+        pub fn with_verify_authorized_party(mut self, verify_authorized_party: bool) -> Self {
+            self.verify_authorized_party = verify_authorized_party;
+            self
+        }
+
+        /// Set the accepted signing algorithms
+        pub fn with_signing_algs(
+            mut self,
+            signing_algs: impl IntoIterator<Item = impl Into<String>>,
+        ) -> Self {
+            self.signing_algs = signing_algs.into_iter().map(Into::into).collect();
+            self
+        }
+
+        /// Set the leeway for the expiration
+        pub fn with_clock_leeway(mut self, clock_leeway: Duration) -> Self {
+            self.clock_leeway = clock_leeway;
+            self
+        }
+
+        /// Set if the time of the authentication is required
+        pub fn with_require_auth_time(mut self, require_auth_time: bool) -> Self {
+            self.require_auth_time = require_auth_time;
+            self
+        }
+
+        /// Set the maximum time since the authentication
+        pub fn with_max_auth_age(mut self, max_auth_age: Duration) -> Self {
+            self.max_auth_age = Some(max_auth_age);
+            self
+        }
+    }
+
+    /// Endpoints of an issuer, provided instead of discovering them.
+    ///
+    /// This allows starting without the discovery round trip, and using issuers which don't
+    /// serve the discovery document to the browser (e.g. due to missing CORS headers). The signing
+    /// keys are still fetched from the JWKS endpoint. ID tokens must be signed using one of the
+    /// [`IdTokenValidation::signing_algs`], or `RS256` if there are none.
+    ///
+    /// The end session endpoint is configured using [`Config::end_session_url`].
+    #[non_exhaustive]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct StaticMetadata {
+        /// The URL of the authorization endpoint
+        pub authorization_endpoint: String,
+        /// The URL of the token endpoint
+        pub token_endpoint: String,
+        /// The URL of the JSON web key set
+        pub jwks_uri: String,
+        /// The URL of the user info endpoint
+        pub userinfo_endpoint: Option<String>,
+    }
+
+    impl StaticMetadata {
+        /// Create new metadata
+        pub fn new(
+            authorization_endpoint: impl Into<String>,
+            token_endpoint: impl Into<String>,
+            jwks_uri: impl Into<String>,
+        ) -> Self {
+            Self {
+                authorization_endpoint: authorization_endpoint.into(),
+                token_endpoint: token_endpoint.into(),
+                jwks_uri: jwks_uri.into(),
+                userinfo_endpoint: None,
+            }
+        }
+
+        /// Set the URL of the user info endpoint
+        pub fn with_userinfo_endpoint(mut self, userinfo_endpoint: impl Into<String>) -> Self {
+            self.userinfo_endpoint = Some(userinfo_endpoint.into());
+            self
+        }
     }
 
     impl Config {
@@ -45,9 +377,53 @@ pub mod openid {
                 after_logout_url: None,
                 post_logout_redirect_name: None,
                 additional_trusted_audiences: vec![],
+                account_url: None,
+                fetch_user_info: false,
+                additional_auth_params: vec![],
+                additional_logout_params: vec![],
+                multi_tenant: false,
+                metadata: None,
+                discovery_cache_ttl: None,
+                metadata_url: None,
+                expected_issuers: vec![],
+                id_token_validation: Default::default(),
+                token_url: None,
+                redirect_url: None,
+                http_client: None,
+                #[cfg(feature = "worker")]
+                token_vault: None,
             }
         }
 
+        /// Create a new configuration for a Keycloak realm
+        ///
+        /// This derives the issuer URL, the end session URL, and the account management console
+        /// URL from the base URL of the Keycloak instance. For Keycloak before 17, the base URL
+        /// must include the `/auth` path. Keycloak before 18 also requires setting the post
+        /// logout redirect name to `redirect_uri`.
+        ///
+        /// ```rust
+        /// use yew_oauth2::config::openid::Config;
+        ///
+        /// let config = Config::keycloak("https://sso.example.com", "my-realm", "my-client");
+        /// assert_eq!(config.issuer_url, "https://sso.example.com/realms/my-realm");
+        /// ```
+        pub fn keycloak(
+            base_url: impl AsRef<str>,
+            realm: impl AsRef<str>,
+            client_id: impl Into<String>,
+        ) -> Self {
+            let issuer_url = format!(
+                "{}/realms/{}",
+                base_url.as_ref().trim_end_matches('/'),
+                realm.as_ref()
+            );
+
+            Self::new(client_id, &issuer_url)
+                .with_end_session_url(format!("{issuer_url}/protocol/openid-connect/logout"))
+                .with_account_url(format!("{issuer_url}/account"))
+        }
+
         /// Set an override for the URL for ending the session.
         pub fn with_end_session_url(mut self, end_session_url: impl Into<String>) -> Self {
             self.end_session_url = Some(end_session_url.into());
@@ -100,6 +476,196 @@ pub mod openid {
                 .push(additional_trusted_audience.into());
             self
         }
+
+        /// Set the URL of the account management console
+        pub fn with_account_url(mut self, account_url: impl Into<String>) -> Self {
+            self.account_url = Some(account_url.into());
+            self
+        }
+
+        /// Set if the user info should be fetched after the login
+        pub fn with_fetch_user_info(mut self, fetch_user_info: bool) -> Self {
+            self.fetch_user_info = fetch_user_info;
+            self
+        }
+
+        /// Set the additional parameters of the authorization request
+        pub fn with_additional_auth_params(
+            mut self,
+            additional_auth_params: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        ) -> Self {
+            self.additional_auth_params = additional_auth_params
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect();
+            self
+        }
+
+        /// Add an additional parameter to the authorization request
+        pub fn add_additional_auth_param(
+            mut self,
+            key: impl Into<String>,
+            value: impl Into<String>,
+        ) -> Self {
+            self.additional_auth_params.push((key.into(), value.into()));
+            self
+        }
+
+        /// Set the additional parameters sent when logging out
+        pub fn with_additional_logout_params(
+            mut self,
+            additional_logout_params: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        ) -> Self {
+            self.additional_logout_params = additional_logout_params
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect();
+            self
+        }
+
+        /// Add an additional parameter sent when logging out
+        pub fn add_additional_logout_param(
+            mut self,
+            key: impl Into<String>,
+            value: impl Into<String>,
+        ) -> Self {
+            self.additional_logout_params
+                .push((key.into(), value.into()));
+            self
+        }
+
+        /// Set if an issuer containing a `{tenantid}` placeholder is accepted
+        pub fn with_multi_tenant(mut self, multi_tenant: bool) -> Self {
+            self.multi_tenant = multi_tenant;
+            self
+        }
+
+        /// Set the endpoints of the issuer, skipping the discovery
+        pub fn with_metadata(mut self, metadata: StaticMetadata) -> Self {
+            self.metadata = Some(metadata);
+            self
+        }
+
+        /// Set the duration for caching the discovered metadata
+        pub fn with_discovery_cache_ttl(mut self, discovery_cache_ttl: Duration) -> Self {
+            self.discovery_cache_ttl = Some(discovery_cache_ttl);
+            self
+        }
+
+        /// Set the URL of the discovery document
+        pub fn with_metadata_url(mut self, metadata_url: impl Into<String>) -> Self {
+            self.metadata_url = Some(metadata_url.into());
+            self
+        }
+
+        /// Set the override for the token endpoint URL, e.g. a path of a same-origin proxy
+        pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+            self.token_url = Some(token_url.into());
+            self
+        }
+
+        /// Set the redirect URL, which may be a path relative to the origin of the page
+        pub fn with_redirect_url(mut self, redirect_url: impl Into<String>) -> Self {
+            self.redirect_url = Some(redirect_url.into());
+            self
+        }
+
+        /// Set the issuers accepted in addition to the issuer URL
+        pub fn with_expected_issuers(
+            mut self,
+            expected_issuers: impl IntoIterator<Item = impl Into<String>>,
+        ) -> Self {
+            self.expected_issuers = expected_issuers.into_iter().map(Into::into).collect();
+            self
+        }
+
+        /// Add an issuer accepted in addition to the issuer URL
+        pub fn add_expected_issuer(mut self, expected_issuer: impl Into<String>) -> Self {
+            self.expected_issuers.push(expected_issuer.into());
+            self
+        }
+
+        /// Set the options for validating ID tokens
+        pub fn with_id_token_validation(mut self, id_token_validation: IdTokenValidation) -> Self {
+            self.id_token_validation = id_token_validation;
+            self
+        }
+
+        /// Set the HTTP client for the requests to the issuer
+        pub fn with_http_client(
+            mut self,
+            http_client: impl crate::agent::client::HttpClient + 'static,
+        ) -> Self {
+            self.http_client = Some(crate::agent::client::SharedHttpClient::new(http_client));
+            self
+        }
+
+        /// Set the worker keeping the refresh tokens, see [`crate::worker`]
+        #[cfg(feature = "worker")]
+        pub fn with_token_vault(mut self, token_vault: crate::worker::TokenVault) -> Self {
+            self.token_vault = Some(token_vault);
+            self
+        }
+
+        /// Validate the configuration, without contacting the issuer.
+        ///
+        /// Reports all problems found, like missing values, malformed URLs, or options which
+        /// can't be used together. The context components validate the configuration before
+        /// starting the agent, failing with [`crate::agent::OAuth2Error::InvalidConfiguration`].
+        ///
+        /// ```rust
+        /// use yew_oauth2::config::{openid::Config, ConfigError};
+        ///
+        /// let errors = Config::new("", "not a url").validate().unwrap_err();
+        /// assert_eq!(errors.len(), 2);
+        /// assert_eq!(errors[0], ConfigError::Missing { field: "client_id" });
+        /// ```
+        pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+            let mut v = Validator::default();
+
+            v.required("client_id", &self.client_id);
+            v.url("issuer_url", &self.issuer_url);
+            v.optional_url("end_session_url", self.end_session_url.as_deref());
+            v.optional_url("account_url", self.account_url.as_deref());
+            v.optional_url("metadata_url", self.metadata_url.as_deref());
+            v.optional_page_url("token_url", self.token_url.as_deref());
+            v.optional_page_url("redirect_url", self.redirect_url.as_deref());
+            for issuer in &self.expected_issuers {
+                v.url("expected_issuers", issuer);
+            }
+
+            if let Some(metadata) = &self.metadata {
+                v.url(
+                    "metadata.authorization_endpoint",
+                    &metadata.authorization_endpoint,
+                );
+                v.url("metadata.token_endpoint", &metadata.token_endpoint);
+                v.url("metadata.jwks_uri", &metadata.jwks_uri);
+                v.optional_url(
+                    "metadata.userinfo_endpoint",
+                    metadata.userinfo_endpoint.as_deref(),
+                );
+            }
+
+            let metadata = self.metadata.is_some();
+            v.incompatible(
+                metadata && self.metadata_url.is_some(),
+                ["metadata", "metadata_url"],
+                "the discovery document is not fetched when providing the metadata",
+            );
+            v.incompatible(
+                metadata && self.discovery_cache_ttl.is_some(),
+                ["metadata", "discovery_cache_ttl"],
+                "there is nothing to cache when providing the metadata",
+            );
+            v.incompatible(
+                metadata && self.multi_tenant,
+                ["metadata", "multi_tenant"],
+                "the tenant placeholder is only supported with discovery",
+            );
+
+            v.finish()
+        }
     }
 }
 
@@ -121,7 +687,38 @@ pub mod oauth2 {
         /// The authentication URL
         pub auth_url: String,
         /// The token exchange URL
+        ///
+        /// This may be a path relative to the origin of the page, e.g. for sending requests
+        /// through a same-origin proxy, when the token endpoint doesn't send CORS headers.
         pub token_url: String,
+        /// The URL of the issuer's account management console.
+        pub account_url: Option<String>,
+        /// Additional parameters sent with the authorization request.
+        ///
+        /// This can be used for issuer specific parameters, like Auth0's `audience`.
+        #[serde(default)]
+        pub additional_auth_params: Vec<(String, String)>,
+        /// Accept token responses which don't follow the specification.
+        ///
+        /// Some issuers, like GitHub, respond with form-encoded responses, report errors with a
+        /// status of `200`, or omit the token type. When enabled, such responses are corrected
+        /// before parsing them.
+        #[serde(default)]
+        pub lenient_token_response: bool,
+        /// The URL the issuer redirects to after the login, instead of the current URL.
+        ///
+        /// This may be a path relative to the origin of the page, e.g. `/auth/callback`, so that
+        /// the same configuration works on different origins. The redirect URL of the
+        /// [`crate::agent::LoginOptions`] takes precedence.
+        #[serde(default)]
+        pub redirect_url: Option<String>,
+        /// The HTTP client for the requests to the issuer, defaults to `reqwest`.
+        #[serde(skip)]
+        pub http_client: Option<crate::agent::client::SharedHttpClient>,
+        /// The worker to send requests to the token endpoint through, keeping the refresh tokens.
+        #[cfg(feature = "worker")]
+        #[serde(skip)]
+        pub token_vault: Option<crate::worker::TokenVault>,
     }
 
     impl Config {
@@ -135,7 +732,276 @@ pub mod oauth2 {
                 client_id: client_id.into(),
                 auth_url: auth_url.into(),
                 token_url: token_url.into(),
+                account_url: None,
+                additional_auth_params: vec![],
+                lenient_token_response: false,
+                redirect_url: None,
+                http_client: None,
+                #[cfg(feature = "worker")]
+                token_vault: None,
             }
         }
+
+        /// Set the URL of the account management console
+        pub fn with_account_url(mut self, account_url: impl Into<String>) -> Self {
+            self.account_url = Some(account_url.into());
+            self
+        }
+
+        /// Set the additional parameters of the authorization request
+        pub fn with_additional_auth_params(
+            mut self,
+            additional_auth_params: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        ) -> Self {
+            self.additional_auth_params = additional_auth_params
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect();
+            self
+        }
+
+        /// Add an additional parameter to the authorization request
+        pub fn add_additional_auth_param(
+            mut self,
+            key: impl Into<String>,
+            value: impl Into<String>,
+        ) -> Self {
+            self.additional_auth_params.push((key.into(), value.into()));
+            self
+        }
+
+        /// Set if token responses which don't follow the specification are accepted
+        pub fn with_lenient_token_response(mut self, lenient_token_response: bool) -> Self {
+            self.lenient_token_response = lenient_token_response;
+            self
+        }
+
+        /// Set the redirect URL, which may be a path relative to the origin of the page
+        pub fn with_redirect_url(mut self, redirect_url: impl Into<String>) -> Self {
+            self.redirect_url = Some(redirect_url.into());
+            self
+        }
+
+        /// Set the HTTP client for the requests to the issuer
+        pub fn with_http_client(
+            mut self,
+            http_client: impl crate::agent::client::HttpClient + 'static,
+        ) -> Self {
+            self.http_client = Some(crate::agent::client::SharedHttpClient::new(http_client));
+            self
+        }
+
+        /// Set the worker keeping the refresh tokens, see [`crate::worker`]
+        #[cfg(feature = "worker")]
+        pub fn with_token_vault(mut self, token_vault: crate::worker::TokenVault) -> Self {
+            self.token_vault = Some(token_vault);
+            self
+        }
+
+        /// Validate the configuration, without contacting the issuer.
+        ///
+        /// Reports all problems found, like missing values or malformed URLs. The context
+        /// components validate the configuration before starting the agent, failing with
+        /// [`crate::agent::OAuth2Error::InvalidConfiguration`].
+        pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+            let mut v = Validator::default();
+
+            v.required("client_id", &self.client_id);
+            v.url("auth_url", &self.auth_url);
+            v.page_url("token_url", &self.token_url);
+            v.optional_page_url("redirect_url", self.redirect_url.as_deref());
+            v.optional_url("account_url", self.account_url.as_deref());
+
+            v.finish()
+        }
+    }
+}
+
+/// Configuration for a backend for frontend
+pub mod bff {
+    use super::*;
+
+    /// Configuration of a backend, holding the tokens of the session
+    ///
+    /// See [`crate::agent::client::BffClient`].
+    ///
+    /// ## Non-exhaustive
+    ///
+    /// This struct is `#[non_exhaustive]`, so it is not possible to directly create a struct, creating a new struct
+    /// is done using the [`Config::new`] function.
+    #[non_exhaustive]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct Config {
+        /// The client ID
+        pub client_id: String,
+        /// The authentication URL
+        pub auth_url: String,
+        /// The session endpoint of the backend
+        pub session_url: String,
+        /// The logout endpoint of the backend, ending the session of the issuer too
+        pub logout_url: Option<String>,
+        /// Additional parameters sent with the authorization request.
+        #[serde(default)]
+        pub additional_auth_params: Vec<(String, String)>,
+        /// The name of the header protecting the session endpoint against CSRF.
+        pub csrf_header: String,
+        /// The value of the header protecting the session endpoint against CSRF.
+        pub csrf_value: String,
+    }
+
+    impl Config {
+        /// Create a new configuration
+        pub fn new(
+            client_id: impl Into<String>,
+            auth_url: impl Into<String>,
+            session_url: impl Into<String>,
+        ) -> Self {
+            Self {
+                client_id: client_id.into(),
+                auth_url: auth_url.into(),
+                session_url: session_url.into(),
+                logout_url: None,
+                additional_auth_params: vec![],
+                csrf_header: "X-Requested-With".into(),
+                csrf_value: "yew-oauth2".into(),
+            }
+        }
+
+        /// Set the logout endpoint of the backend
+        pub fn with_logout_url(mut self, logout_url: impl Into<String>) -> Self {
+            self.logout_url = Some(logout_url.into());
+            self
+        }
+
+        /// Add an additional parameter to the authorization request
+        pub fn add_additional_auth_param(
+            mut self,
+            key: impl Into<String>,
+            value: impl Into<String>,
+        ) -> Self {
+            self.additional_auth_params.push((key.into(), value.into()));
+            self
+        }
+
+        /// Set the header protecting the session endpoint against CSRF
+        pub fn with_csrf_header(
+            mut self,
+            name: impl Into<String>,
+            value: impl Into<String>,
+        ) -> Self {
+            self.csrf_header = name.into();
+            self.csrf_value = value.into();
+            self
+        }
+
+        /// Validate the configuration, without contacting the backend.
+        pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+            let mut v = Validator::default();
+
+            v.required("client_id", &self.client_id);
+            v.url("auth_url", &self.auth_url);
+            v.url("session_url", &self.session_url);
+            v.optional_url("logout_url", self.logout_url.as_deref());
+            v.required("csrf_header", &self.csrf_header);
+
+            v.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn fields(result: Result<(), Vec<ConfigError>>) -> Vec<&'static str> {
+        result
+            .unwrap_err()
+            .into_iter()
+            .map(|err| match err {
+                ConfigError::Missing { field } | ConfigError::InvalidUrl { field, .. } => field,
+                ConfigError::Incompatible { fields: [a, _], .. } => a,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn openid_valid() {
+        let config = openid::Config::new("client", "https://issuer/realms/app")
+            .with_token_url("/token")
+            .with_redirect_url("/callback")
+            .with_expected_issuers(["https://issuer/realms/other"]);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn openid_invalid() {
+        let config = openid::Config::new(" ", "issuer")
+            .with_end_session_url("logout")
+            .with_redirect_url("callback");
+        assert_eq!(
+            fields(config.validate()),
+            ["client_id", "issuer_url", "end_session_url", "redirect_url"]
+        );
+
+        let config = openid::Config::new("client", "").with_expected_issuers([""]);
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::Missing {
+                    field: "issuer_url"
+                },
+                ConfigError::Missing {
+                    field: "expected_issuers"
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn openid_metadata() {
+        let metadata = openid::StaticMetadata::new("https://issuer/auth", "", "jwks");
+        let config = openid::Config::new("client", "https://issuer")
+            .with_metadata(metadata)
+            .with_metadata_url("https://issuer/metadata")
+            .with_discovery_cache_ttl(Duration::from_secs(60));
+        assert_eq!(
+            fields(config.validate()),
+            [
+                "metadata.token_endpoint",
+                "metadata.jwks_uri",
+                "metadata",
+                "metadata",
+            ]
+        );
+    }
+
+    #[test]
+    fn oauth2_invalid() {
+        let config = oauth2::Config::new("client", "https://issuer/auth", "/token");
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = oauth2::Config::new("client", "auth", "token").with_account_url("account");
+        assert_eq!(
+            fields(config.validate()),
+            ["auth_url", "token_url", "account_url"]
+        );
+
+        // a missing value is only reported as missing
+        let config = oauth2::Config::new("client", "https://issuer/auth", "");
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::Missing { field: "token_url" }])
+        );
+    }
+
+    #[test]
+    fn bff_invalid() {
+        let config =
+            bff::Config::new("", "https://issuer/auth", "session").with_csrf_header("", "1");
+        assert_eq!(
+            fields(config.validate()),
+            ["client_id", "session_url", "csrf_header"]
+        );
     }
 }
@@ -0,0 +1,451 @@
+//! Checking an issuer against the OpenID Connect Basic RP conformance profile.
+//!
+//! The [`Conformance`] client runs the checks a relying party performs, one by one, and reports
+//! the outcome of each of them. Instead of failing with the first problem, like the agent does,
+//! this shows everything which is wrong with the setup of an issuer. It serves as a diagnostic
+//! tool, and allows testing the crate against an issuer, like a Keycloak instance.
+//!
+//! The checks of the discovery document only require the configuration. The checks of an ID
+//! token (its signature, `iss`, `aud`, `azp`, `exp`, `nonce`, and `at_hash`) require the tokens of
+//! a login, e.g. captured by the test setup.
+//!
+//! ```rust,no_run
+//! use yew_oauth2::{config::openid::Config, conformance::Conformance};
+//!
+//! # async fn run(id_token: String, access_token: String) {
+//! let config = Config::new("my-client", "https://my-sso/realms/my-realm");
+//! let report = Conformance::new(config)
+//!     .with_id_token(id_token)
+//!     .with_access_token(access_token)
+//!     .run()
+//!     .await;
+//!
+//! log::info!("{report}");
+//! assert!(report.passed());
+//! # }
+//! ```
+
+use crate::{
+    agent::{
+        client::{default_metadata_url, OpenIdClient},
+        Client, Clock,
+    },
+    config::openid,
+    context::token::{jwt_claims, jwt_header},
+};
+use chrono::{DateTime, Utc};
+use openidconnect::{core::CoreIdToken, AccessToken, AccessTokenHash, IssuerUrl, Nonce};
+use serde_json::Value;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// The outcome of a check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The check passed.
+    Passed,
+    /// The check failed, with the reason.
+    Failed(String),
+    /// The check was not performed, with the reason.
+    Skipped(String),
+}
+
+/// A single check of the conformance profile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    /// The name of the check, like `at_hash`
+    pub name: &'static str,
+    /// What the check verifies
+    pub description: &'static str,
+    /// The outcome
+    pub outcome: Outcome,
+}
+
+/// The outcome of all checks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The checks, in the order they were performed
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// Check if no check failed.
+    pub fn passed(&self) -> bool {
+        !self
+            .checks
+            .iter()
+            .any(|check| matches!(check.outcome, Outcome::Failed(_)))
+    }
+
+    /// Get the failed checks.
+    pub fn failed(&self) -> impl Iterator<Item = &Check> {
+        self.checks
+            .iter()
+            .filter(|check| matches!(check.outcome, Outcome::Failed(_)))
+    }
+
+    fn record(&mut self, name: &'static str, description: &'static str, outcome: Outcome) {
+        self.checks.push(Check {
+            name,
+            description,
+            outcome,
+        });
+    }
+
+    fn check(&mut self, name: &'static str, description: &'static str, result: Result<(), String>) {
+        let outcome = match result {
+            Ok(()) => Outcome::Passed,
+            Err(reason) => Outcome::Failed(reason),
+        };
+        self.record(name, description, outcome);
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                Outcome::Passed => writeln!(f, "PASS {}: {}", check.name, check.description)?,
+                Outcome::Failed(reason) => {
+                    writeln!(f, "FAIL {}: {} ({reason})", check.name, check.description)?
+                }
+                Outcome::Skipped(reason) => {
+                    writeln!(f, "SKIP {}: {} ({reason})", check.name, check.description)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A client, checking an issuer against the Basic RP conformance profile.
+#[derive(Clone, Debug)]
+pub struct Conformance {
+    config: openid::Config,
+    id_token: Option<String>,
+    access_token: Option<String>,
+    nonce: Option<String>,
+}
+
+impl Conformance {
+    pub fn new(config: openid::Config) -> Self {
+        Self {
+            config,
+            id_token: None,
+            access_token: None,
+            nonce: None,
+        }
+    }
+
+    /// Set the ID token of a login, enabling the checks of the ID token
+    pub fn with_id_token(mut self, id_token: impl Into<String>) -> Self {
+        self.id_token = Some(id_token.into());
+        self
+    }
+
+    /// Set the access token of the same login, enabling the `at_hash` check
+    pub fn with_access_token(mut self, access_token: impl Into<String>) -> Self {
+        self.access_token = Some(access_token.into());
+        self
+    }
+
+    /// Set the nonce sent with the authorization request, enabling the `nonce` check
+    pub fn with_nonce(mut self, nonce: impl Into<String>) -> Self {
+        self.nonce = Some(nonce.into());
+        self
+    }
+
+    /// Run the checks.
+    pub async fn run(self) -> Report {
+        let mut report = Report::default();
+
+        self.check_metadata(&mut report).await;
+
+        let client = OpenIdClient::from_config(self.config.clone()).await;
+        report.check(
+            "client",
+            "the client can be created from the configuration",
+            client.as_ref().map(|_| ()).map_err(|err| err.to_string()),
+        );
+
+        match (&self.id_token, client) {
+            (None, _) => report.record(
+                "id_token",
+                "the checks of the ID token",
+                Outcome::Skipped("no ID token provided".to_string()),
+            ),
+            (Some(_), Err(_)) => report.record(
+                "id_token",
+                "the checks of the ID token",
+                Outcome::Skipped("no client".to_string()),
+            ),
+            (Some(id_token), Ok(client)) => self.check_id_token(&mut report, &client, id_token),
+        }
+
+        report
+    }
+
+    /// Check the discovery document.
+    async fn check_metadata(&self, report: &mut Report) {
+        const DESCRIPTION: &str = "the discovery document can be fetched";
+
+        if self.config.metadata.is_some() {
+            report.record(
+                "discovery",
+                DESCRIPTION,
+                Outcome::Skipped("the metadata is configured".to_string()),
+            );
+            return;
+        }
+
+        let metadata = match self.fetch_metadata().await {
+            Ok(metadata) => {
+                report.record("discovery", DESCRIPTION, Outcome::Passed);
+                metadata
+            }
+            Err(err) => {
+                report.record("discovery", DESCRIPTION, Outcome::Failed(err));
+                return;
+            }
+        };
+
+        let announced = metadata["issuer"].as_str().unwrap_or_default();
+        report.check(
+            "issuer",
+            "the announced issuer is the issuer URL",
+            match announced == self.config.issuer_url
+                || self.config.expected_issuers.iter().any(|e| e == announced)
+            {
+                true => Ok(()),
+                false => Err(format!("announced: `{announced}`")),
+            },
+        );
+
+        for field in ["authorization_endpoint", "token_endpoint", "jwks_uri"] {
+            report.check(
+                field,
+                "the endpoint is announced",
+                match metadata[field].as_str() {
+                    Some(_) => Ok(()),
+                    None => Err("missing".to_string()),
+                },
+            );
+        }
+
+        report.check(
+            "response_type",
+            "the authorization code flow is supported",
+            contains(&metadata, "response_types_supported", "code"),
+        );
+        report.check(
+            "rs256",
+            "ID tokens may be signed using RS256",
+            contains(&metadata, "id_token_signing_alg_values_supported", "RS256"),
+        );
+
+        match metadata.get("code_challenge_methods_supported") {
+            Some(_) => report.check(
+                "pkce",
+                "PKCE is supported using S256",
+                contains(&metadata, "code_challenge_methods_supported", "S256"),
+            ),
+            None => report.record(
+                "pkce",
+                "PKCE is supported using S256",
+                Outcome::Skipped("not announced".to_string()),
+            ),
+        }
+    }
+
+    async fn fetch_metadata(&self) -> Result<Value, String> {
+        let url = match &self.config.metadata_url {
+            Some(url) => url.clone(),
+            None => {
+                let issuer = IssuerUrl::new(self.config.issuer_url.clone())
+                    .map_err(|err| err.to_string())?;
+                default_metadata_url(&issuer).to_string()
+            }
+        };
+
+        let response = reqwest::get(&url).await.map_err(|err| err.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("unexpected status: {}", response.status()));
+        }
+        let body = response.text().await.map_err(|err| err.to_string())?;
+        serde_json::from_str(&body).map_err(|err| err.to_string())
+    }
+
+    /// Check the ID token.
+    fn check_id_token(&self, report: &mut Report, client: &OpenIdClient, id_token: &str) {
+        let (Some(header), Some(claims)) = (jwt_header(id_token), jwt_claims(id_token)) else {
+            report.record(
+                "id_token",
+                "the ID token is a JWT",
+                Outcome::Failed("failed to decode".to_string()),
+            );
+            return;
+        };
+
+        let alg = header
+            .get("alg")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        report.check(
+            "alg",
+            "the ID token is signed",
+            match alg {
+                "" | "none" => Err(format!("algorithm: `{alg}`")),
+                _ => Ok(()),
+            },
+        );
+
+        let parsed = CoreIdToken::from_str(id_token);
+        report.check(
+            "signature",
+            "the signature is valid, using the keys of the issuer",
+            parsed
+                .as_ref()
+                .map_err(|err| err.to_string())
+                .and_then(|parsed| {
+                    parsed
+                        .claims(
+                            &client
+                                .id_token_verifier()
+                                .require_issuer_match(false)
+                                .require_audience_match(false)
+                                .set_time_fn(|| DateTime::<Utc>::MIN_UTC),
+                            |_: Option<&Nonce>| Ok(()),
+                        )
+                        .map(|_| ())
+                        .map_err(|err| err.to_string())
+                }),
+        );
+
+        let iss = claims
+            .get("iss")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        report.check(
+            "iss",
+            "the issuer of the ID token is the issuer",
+            match iss == self.config.issuer_url
+                || self.config.expected_issuers.iter().any(|e| e == iss)
+            {
+                true => Ok(()),
+                false => Err(format!("issuer: `{iss}`")),
+            },
+        );
+
+        let audiences = match claims.get("aud") {
+            Some(Value::String(aud)) => vec![aud.clone()],
+            Some(Value::Array(aud)) => aud
+                .iter()
+                .filter_map(|aud| aud.as_str().map(ToString::to_string))
+                .collect(),
+            _ => vec![],
+        };
+        report.check(
+            "aud",
+            "the client is an audience of the ID token",
+            match audiences.contains(&self.config.client_id) {
+                true => Ok(()),
+                false => Err(format!("audiences: {audiences:?}")),
+            },
+        );
+
+        let azp = claims.get("azp").and_then(Value::as_str);
+        report.check(
+            "azp",
+            "the authorized party is the client, and present for multiple audiences",
+            match azp {
+                Some(azp) if azp != self.config.client_id => {
+                    Err(format!("authorized party: `{azp}`"))
+                }
+                None if audiences.len() > 1 => Err("missing".to_string()),
+                _ => Ok(()),
+            },
+        );
+
+        let now = Clock::current().utc().timestamp();
+        report.check(
+            "exp",
+            "the ID token is not expired",
+            match claims.get("exp").and_then(Value::as_i64) {
+                Some(exp) if exp > now => Ok(()),
+                Some(exp) => Err(format!("expired {}s ago", now - exp)),
+                None => Err("missing".to_string()),
+            },
+        );
+        report.check(
+            "iat",
+            "the time of issuing the ID token is present",
+            match claims.get("iat").and_then(Value::as_i64) {
+                Some(_) => Ok(()),
+                None => Err("missing".to_string()),
+            },
+        );
+
+        let nonce = claims.get("nonce").and_then(Value::as_str);
+        match &self.nonce {
+            Some(expected) => report.check(
+                "nonce",
+                "the nonce of the ID token is the one of the request",
+                match nonce {
+                    Some(nonce) if nonce == expected => Ok(()),
+                    Some(nonce) => Err(format!("nonce: `{nonce}`")),
+                    None => Err("missing".to_string()),
+                },
+            ),
+            None => report.record(
+                "nonce",
+                "the nonce of the ID token is the one of the request",
+                Outcome::Skipped("no nonce provided".to_string()),
+            ),
+        }
+
+        const AT_HASH: &str = "the access token hash matches the access token";
+        match (parsed, &self.access_token) {
+            (Ok(parsed), Some(access_token)) => match claims.get("at_hash").and_then(Value::as_str)
+            {
+                Some(at_hash) => report.check(
+                    "at_hash",
+                    AT_HASH,
+                    parsed
+                        .signing_alg()
+                        .and_then(|alg| {
+                            AccessTokenHash::from_token(
+                                &AccessToken::new(access_token.clone()),
+                                &alg,
+                            )
+                        })
+                        .map_err(|err| err.to_string())
+                        .and_then(|expected| match expected.as_str() == at_hash {
+                            true => Ok(()),
+                            false => Err(format!("expected: `{}`", expected.as_str())),
+                        }),
+                ),
+                None => report.record(
+                    "at_hash",
+                    AT_HASH,
+                    Outcome::Skipped("not present".to_string()),
+                ),
+            },
+            _ => report.record(
+                "at_hash",
+                AT_HASH,
+                Outcome::Skipped("no access token provided".to_string()),
+            ),
+        }
+    }
+}
+
+/// Check if a metadata field is an array, containing the value.
+fn contains(metadata: &Value, field: &str, value: &str) -> Result<(), String> {
+    match metadata[field].as_array() {
+        Some(values) if values.iter().any(|v| v.as_str() == Some(value)) => Ok(()),
+        Some(values) => Err(format!("announced: {values:?}")),
+        None => Err("missing".to_string()),
+    }
+}
@@ -0,0 +1,73 @@
+//! Evaluating claims of the session.
+
+use super::{token, Authentication};
+
+/// The paths of the claims containing the roles and scopes of a session.
+///
+/// A path navigates the claims using dots, e.g. `realm_access.roles`. For each, the first path
+/// present in the claims is used, see [`Authentication::claim`]. The values of a claim can be an
+/// array, or a space separated string.
+///
+/// The paths are configured on the [`crate::components::context::OAuth2`] component, and used by
+/// the [`crate::components::Authorized`] component, and the hooks
+/// [`crate::hook::use_has_role`] and [`crate::hook::use_has_scope`].
+///
+/// **NOTE**: This is a non-exhaustive struct. See [`crate::agent::LoginOptions`] for an example
+/// on how to work with this.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ClaimPaths {
+    /// Paths of the claim containing the roles.
+    ///
+    /// Defaults to `roles`.
+    pub roles: Vec<String>,
+    /// Paths of the claim containing the scopes.
+    ///
+    /// Defaults to `scope` and `scp`.
+    pub scopes: Vec<String>,
+}
+
+impl Default for ClaimPaths {
+    fn default() -> Self {
+        Self {
+            roles: vec!["roles".into()],
+            scopes: vec!["scope".into(), "scp".into()],
+        }
+    }
+}
+
+impl ClaimPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the paths of the roles claim
+    pub fn with_roles(mut self, roles: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.roles = roles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the paths of the scopes claim
+    pub fn with_scopes(mut self, scopes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Get the roles of the session.
+    pub fn roles(&self, auth: &Authentication) -> Vec<String> {
+        Self::values(auth, &self.roles)
+    }
+
+    /// Get the scopes of the session.
+    pub fn scopes(&self, auth: &Authentication) -> Vec<String> {
+        Self::values(auth, &self.scopes)
+    }
+
+    fn values(auth: &Authentication, paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .find_map(|path| auth.claim(path))
+            .map(|value| token::string_list(&value))
+            .unwrap_or_default()
+    }
+}
@@ -1,9 +1,16 @@
 //! The Authentication Context
 
+mod claims;
+pub(crate) mod token;
+#[cfg(feature = "yew")]
 mod utils;
 
+use crate::agent::OAuth2Error;
+pub use claims::*;
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
+#[cfg(feature = "yew")]
 pub use utils::*;
 
 #[cfg(feature = "openid")]
@@ -12,21 +19,92 @@ pub type Claims = openidconnect::IdTokenClaims<
     openidconnect::core::CoreGenderClaim,
 >;
 
+/// Claims returned by the user info endpoint
+#[cfg(feature = "openid")]
+pub type UserInfo = openidconnect::StandardClaims<openidconnect::core::CoreGenderClaim>;
+
 /// The authentication information
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(not(feature = "openid"), derive(Eq))]
 pub struct Authentication {
     /// The access token
+    ///
+    /// This is empty for a session of the [`crate::agent::client::BffClient`], as the backend
+    /// holds the tokens of the session.
     pub access_token: String,
     /// An optional refresh token
     pub refresh_token: Option<String>,
     /// OpenID claims
     #[cfg(feature = "openid")]
     pub claims: Option<Rc<Claims>>,
+    /// OpenID user info, if requested
+    #[cfg(feature = "openid")]
+    pub user_info: Option<Rc<UserInfo>>,
     /// Expiration timestamp in seconds
+    ///
+    /// This is meant for display. The agent tracks the expiration using the monotonic clock, so
+    /// that jumps of the wall clock don't affect refreshing the session.
     pub expires: Option<u64>,
 }
 
+impl Authentication {
+    /// Compare two authentications, ignoring the refresh token.
+    pub fn eq_ignore_refresh_token(&self, other: &Self) -> bool {
+        let Self {
+            access_token,
+            refresh_token: _,
+            #[cfg(feature = "openid")]
+            claims,
+            #[cfg(feature = "openid")]
+            user_info,
+            expires,
+        } = self;
+
+        #[cfg(feature = "openid")]
+        if claims != &other.claims || user_info != &other.user_info {
+            return false;
+        }
+
+        access_token == &other.access_token && expires == &other.expires
+    }
+
+    /// Get the claims of the access token, if it is a JWT.
+    ///
+    /// The access token is intended for the resource server, and not validated by the client. The
+    /// claims must only be used for deciding what to show, not for granting access.
+    pub fn access_token_claims(&self) -> Option<serde_json::Map<String, serde_json::Value>> {
+        token::jwt_claims(&self.access_token)
+    }
+
+    /// Get a claim of the session, navigating nested claims using dots.
+    ///
+    /// This looks up the claim in the claims of the access token first, and then in the claims
+    /// of the ID token.
+    pub fn claim(&self, path: &str) -> Option<serde_json::Value> {
+        let claim = self
+            .access_token_claims()
+            .and_then(|claims| token::lookup(&serde_json::Value::Object(claims), path));
+
+        #[cfg(feature = "openid")]
+        let claim = claim.or_else(|| {
+            let claims = serde_json::to_value(self.claims.as_deref()?).ok()?;
+            token::lookup(&claims, path)
+        });
+
+        claim
+    }
+
+    /// Get the scopes of the session, using the default [`ClaimPaths`].
+    pub fn scopes(&self) -> Vec<String> {
+        ClaimPaths::default().scopes(self)
+    }
+
+    /// Get the roles of the session, using the default [`ClaimPaths`].
+    pub fn roles(&self) -> Vec<String> {
+        ClaimPaths::default().roles(self)
+    }
+}
+
 /// The authentication context
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(not(feature = "openid"), derive(Eq))]
@@ -41,7 +119,35 @@ pub enum OAuth2Context {
     /// Session is authenticated.
     Authenticated(Authentication),
     /// Something failed.
-    Failed(String),
+    Failed {
+        /// The error.
+        error: OAuth2Error,
+        /// The operation which failed, and can be retried.
+        operation: Operation,
+    },
+}
+
+/// An operation of the agent, which might fail.
+///
+/// A failed operation can be retried using [`crate::agent::OAuth2Operations::retry`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    /// Creating the client, including the discovery of the issuer's metadata.
+    ///
+    /// Retrying creates the client again.
+    Discovery,
+    /// Starting the login.
+    ///
+    /// Retrying starts a new login.
+    Login,
+    /// Handling the authorization response, exchanging the code for tokens.
+    ///
+    /// As the code can only be used once, retrying starts a new login.
+    CodeExchange,
+    /// Refreshing the access token.
+    ///
+    /// Retrying uses the refresh token of the failed session again.
+    Refresh,
 }
 
 impl OAuth2Context {
@@ -56,17 +162,57 @@ impl OAuth2Context {
         }
     }
 
+    /// Create a failed context.
+    pub fn failed(operation: Operation, error: OAuth2Error) -> Self {
+        Self::Failed { error, operation }
+    }
+
+    /// Get the error, if the context is [`OAuth2Context::Failed`]
+    pub fn error(&self) -> Option<&OAuth2Error> {
+        match self {
+            Self::Failed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Get the failed operation, if the context is [`OAuth2Context::Failed`]
+    pub fn failed_operation(&self) -> Option<Operation> {
+        match self {
+            Self::Failed { operation, .. } => Some(*operation),
+            _ => None,
+        }
+    }
+
     /// Get the access token, if the context is [`OAuth2Context::Authenticated`]
     pub fn access_token(&self) -> Option<&str> {
         self.authentication().map(|auth| auth.access_token.as_str())
     }
 
+    /// Compare two contexts, ignoring differences in the refresh token.
+    ///
+    /// Some issuers rotate the refresh token, without the access token changing. For rendering,
+    /// such contexts can be considered equal.
+    pub fn eq_ignore_refresh_token(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Authenticated(a), Self::Authenticated(b)) => a.eq_ignore_refresh_token(b),
+            _ => self == other,
+        }
+    }
+
     /// Get the claims, if the context is [`OAuth2Context::Authenticated`]
     #[cfg(feature = "openid")]
     pub fn claims(&self) -> Option<&Claims> {
         self.authentication()
             .and_then(|auth| auth.claims.as_ref().map(|claims| claims.as_ref()))
     }
+
+    /// Get the user info, if the context is [`OAuth2Context::Authenticated`] and the user info
+    /// was fetched
+    #[cfg(feature = "openid")]
+    pub fn user_info(&self) -> Option<&UserInfo> {
+        self.authentication()
+            .and_then(|auth| auth.user_info.as_ref().map(|user_info| user_info.as_ref()))
+    }
 }
 
 /// The reason why the context is un-authenticated.
@@ -76,8 +222,55 @@ pub enum Reason {
     NewSession,
     /// Because there was a session, but now it expired.
     Expired,
-    /// Because the user chose to log out.
-    Logout,
+    /// Because the session was logged out.
+    Logout(LogoutReason),
+    /// Because the session was rejected, see [`crate::agent::UnauthorizedPolicy`].
+    Invalid,
+    /// Because the issuer is temporarily unavailable, the agent will retry the login, see
+    /// [`crate::agent::RetryPolicy`].
+    TemporarilyUnavailable,
+}
+
+/// Why the session was logged out.
+///
+/// This allows post-logout screens to show an appropriate message. Applications can set the
+/// reason when triggering the logout, using [`crate::agent::LogoutOptions::with_reason`]. It is
+/// kept when navigating to the issuer for ending the session, and back.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogoutReason {
+    /// The user chose to log out.
+    #[default]
+    User,
+    /// The session was revoked, e.g. by an administrator, and rejected, see
+    /// [`crate::agent::UnauthorizedPolicy::Logout`].
+    Revoked,
+}
+
+/// The reachability of the issuer.
+///
+/// This is only checked when health checks are enabled on the
+/// [`crate::components::context::OAuth2`] component.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IssuerHealth {
+    /// Not checked yet.
+    #[default]
+    Unknown,
+    /// The issuer could be reached with the last check.
+    Reachable,
+    /// The issuer could not be reached with the last check.
+    Unreachable,
+}
+
+/// A hint, if the last visit of the application ended with a session.
+///
+/// The hint is kept in the local storage of the browser. It allows rendering content
+/// optimistically while the agent initializes, for users who are likely to get their session
+/// back. It is only a hint, the content must not grant anything the session would be required
+/// for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionHint {
+    /// The last visit ended with a session.
+    pub returning: bool,
 }
 
 /// A handle to access the latest access token.
@@ -101,6 +294,7 @@ impl LatestAccessToken {
         }
     }
 
+    #[cfg(feature = "yew")]
     pub(crate) fn set_access_token(&self, access_token: Option<impl Into<String>>) {
         *self.access_token.borrow_mut() = access_token.map(|s| s.into());
     }
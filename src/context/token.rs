@@ -0,0 +1,132 @@
+//! Claims of JWT access tokens.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{Map, Value};
+
+/// Look up a claim, navigating nested claims using dots.
+pub(crate) fn lookup(claims: &Value, path: &str) -> Option<Value> {
+    path.split('.')
+        .try_fold(claims, |value, name| value.get(name))
+        .cloned()
+}
+
+/// Decode the claims of a JWT, without validating it.
+pub(crate) fn jwt_claims(token: &str) -> Option<Map<String, Value>> {
+    jwt_part(token, 1)
+}
+
+/// Decode the header of a JWT, without validating it.
+#[cfg(feature = "openid")]
+pub(crate) fn jwt_header(token: &str) -> Option<Map<String, Value>> {
+    jwt_part(token, 0)
+}
+
+fn jwt_part(token: &str, index: usize) -> Option<Map<String, Value>> {
+    let part = token.split('.').nth(index)?;
+    let part = URL_SAFE_NO_PAD.decode(part.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&part).ok()
+}
+
+/// Check if a token was issued for an audience.
+///
+/// If the token is not a JWT, or has no `aud` claim, it is assumed to match.
+pub(crate) fn matches_audience(token: &str, audience: &str) -> bool {
+    match jwt_claims(token).and_then(|claims| claims.get("aud").cloned()) {
+        Some(aud) => string_list(&aud).iter().any(|aud| aud == audience),
+        None => true,
+    }
+}
+
+/// Get a list of strings from a claim, which can be an array or a space separated string.
+pub(crate) fn string_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(value) => value.split_whitespace().map(ToString::to_string).collect(),
+        Value::Array(values) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(ToString::to_string))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    /// Create an (unsigned) JWT with the claims.
+    fn jwt(claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": "none"}).to_string());
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{claims}.")
+    }
+
+    #[test]
+    fn claims() {
+        let claims = jwt_claims(&jwt(json!({"sub": "user"}))).expect("claims");
+        assert_eq!(claims.get("sub"), Some(&json!("user")));
+
+        // padded, as some issuers do
+        let token = format!("{}==.", jwt(json!({"sub": "user"})).trim_end_matches('.'));
+        assert!(jwt_claims(&token).is_some());
+    }
+
+    #[test]
+    fn claims_invalid() {
+        assert_eq!(jwt_claims("opaque-token"), None);
+        assert_eq!(jwt_claims("header.not-base64!.signature"), None);
+        let token = format!("header.{}.", URL_SAFE_NO_PAD.encode("not json"));
+        assert_eq!(jwt_claims(&token), None);
+        let token = format!("header.{}.", URL_SAFE_NO_PAD.encode("[]"));
+        assert_eq!(jwt_claims(&token), None);
+    }
+
+    #[test]
+    fn lookup_nested() {
+        let claims = json!({
+            "scope": "read",
+            "realm_access": {"roles": ["admin"]},
+        });
+        assert_eq!(lookup(&claims, "scope"), Some(json!("read")));
+        assert_eq!(
+            lookup(&claims, "realm_access.roles"),
+            Some(json!(["admin"]))
+        );
+        assert_eq!(lookup(&claims, "realm_access.groups"), None);
+        assert_eq!(lookup(&claims, "scope.roles"), None);
+        assert_eq!(lookup(&claims, "roles"), None);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(string_list(&json!("read write")), vec!["read", "write"]);
+        assert_eq!(
+            string_list(&json!(["admin", 1, "user"])),
+            vec!["admin", "user"]
+        );
+        assert_eq!(string_list(&json!("")), Vec::<String>::new());
+        assert_eq!(string_list(&json!(42)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn audience_accepts() {
+        assert!(matches_audience(&jwt(json!({"aud": "api"})), "api"));
+        assert!(matches_audience(
+            &jwt(json!({"aud": ["web", "api"]})),
+            "api"
+        ));
+        // nothing to check against
+        assert!(matches_audience(&jwt(json!({"sub": "user"})), "api"));
+        assert!(matches_audience("opaque-token", "api"));
+    }
+
+    #[test]
+    fn audience_rejects() {
+        assert!(!matches_audience(&jwt(json!({"aud": "other"})), "api"));
+        assert!(!matches_audience(
+            &jwt(json!({"aud": ["web", "other"]})),
+            "api"
+        ));
+        assert!(!matches_audience(&jwt(json!({"aud": []})), "api"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Sending requests with the access token of the session, using `gloo-net`.
+//!
+//! ```rust,no_run
+//! use gloo_net::http::Request;
+//! use yew_oauth2::fetch::{AuthenticatedRequest, FetchError};
+//!
+//! async fn fetch_items() -> Result<String, FetchError> {
+//!     let request = Request::get("/api/items").build()?;
+//!     let response = AuthenticatedRequest::new(request).send().await?;
+//!     Ok(response.text().await?)
+//! }
+//! ```
+
+use crate::{
+    agent::{OAuth2Agent, TokenRequest},
+    bearer::{self, BearerError, Sender},
+};
+use async_trait::async_trait;
+use gloo_net::http::{Request, Response};
+
+/// An error sending an [`AuthenticatedRequest`].
+pub type FetchError = BearerError<gloo_net::Error, Response>;
+
+impl<R> From<gloo_net::Error> for BearerError<gloo_net::Error, R> {
+    fn from(err: gloo_net::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+/// Sending requests of the Fetch API.
+struct Fetch;
+
+#[async_trait(?Send)]
+impl Sender for Fetch {
+    type Request = web_sys::Request;
+    type Response = Response;
+    type Error = gloo_net::Error;
+
+    fn try_clone(request: &web_sys::Request) -> Option<web_sys::Request> {
+        web_sys::Request::clone(request).ok()
+    }
+
+    fn is_unauthorized(response: &Response) -> bool {
+        response.status() == 401
+    }
+
+    async fn send(
+        &mut self,
+        request: web_sys::Request,
+        token: &str,
+    ) -> Result<Response, gloo_net::Error> {
+        request
+            .headers()
+            .set("Authorization", &format!("Bearer {token}"))
+            .map_err(|err| gloo_net::Error::GlooError(format!("failed to set token: {err:?}")))?;
+
+        Request::from(request).send().await
+    }
+}
+
+/// A request, sent with the access token of the session.
+///
+/// The token is acquired from the agent right before sending the request, refreshing the session
+/// first if the token is about to expire. It is sent as `Authorization: Bearer` header. If the
+/// request is rejected as unauthorized (`401`), the session is refreshed and the request is sent
+/// once more. If that is rejected as well, this is reported to the agent (see
+/// [`crate::agent::UnauthorizedPolicy`]), and surfaced as [`BearerError::Unauthorized`].
+///
+/// The request is copied before sending it, so that the body can be sent again.
+#[derive(Debug)]
+pub struct AuthenticatedRequest {
+    request: Request,
+    agent: Option<OAuth2Agent>,
+    token_request: TokenRequest,
+}
+
+impl From<Request> for AuthenticatedRequest {
+    fn from(request: Request) -> Self {
+        Self::new(request)
+    }
+}
+
+impl AuthenticatedRequest {
+    /// Send the request, using the [global agent](OAuth2Agent::global).
+    pub fn new(request: Request) -> Self {
+        Self {
+            request,
+            agent: None,
+            token_request: TokenRequest::new(),
+        }
+    }
+
+    /// Set the agent, providing the token
+    pub fn with_agent(mut self, agent: OAuth2Agent) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+
+    /// Set the request for the token, e.g. for a different audience
+    ///
+    /// Defaults to the access token of the session.
+    pub fn with_token_request(mut self, token_request: TokenRequest) -> Self {
+        self.token_request = token_request;
+        self
+    }
+
+    /// Send the request.
+    pub async fn send(self) -> Result<Response, FetchError> {
+        let request = web_sys::Request::from(self.request);
+        bearer::send(
+            self.agent.as_ref(),
+            &self.token_request,
+            &mut Fetch,
+            request,
+        )
+        .await
+    }
+}
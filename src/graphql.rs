@@ -0,0 +1,147 @@
+//! Executing GraphQL operations with the access token of the session.
+//!
+//! The [`GraphQLClient`] works with the types generated by `graphql_client`, without depending on
+//! it: the body of a request is anything serializable, like `graphql_client::QueryBody`, and the
+//! response anything deserializable, like `graphql_client::Response`.
+//!
+//! ```rust,ignore
+//! use graphql_client::{GraphQLQuery, Response};
+//! use yew_oauth2::{agent::TokenRequest, graphql::GraphQLClient};
+//!
+//! let client = GraphQLClient::new("https://api/graphql".parse()?)
+//!     // the mutation requires an additional scope
+//!     .with_operation_token("DeleteItem", TokenRequest::new().add_scope("items:write"));
+//!
+//! let body = DeleteItem::build_query(delete_item::Variables { id });
+//! let response: Response<delete_item::ResponseData> = client.execute(&body).await?;
+//! ```
+
+use crate::{
+    agent::TokenRequest,
+    middleware::{BearerAuth, BearerAuthError},
+};
+use core::fmt::{Display, Formatter};
+use reqwest::{header::CONTENT_TYPE, Client, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+/// An error executing a GraphQL operation.
+///
+/// Errors reported by the GraphQL server are part of the response, and not an error.
+#[derive(Debug)]
+pub enum GraphQLError {
+    /// Failed to send the request, see [`BearerAuth`].
+    Request(BearerAuthError),
+    /// Failed to read the response.
+    Response(reqwest::Error),
+    /// Failed to encode the request, or decode the response.
+    Json(serde_json::Error),
+}
+
+impl Display for GraphQLError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(err) => write!(f, "{err}"),
+            Self::Response(err) => write!(f, "failed to read response: {err}"),
+            Self::Json(err) => write!(f, "JSON error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphQLError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Request(err) => Some(err),
+            Self::Response(err) => Some(err),
+            Self::Json(err) => Some(err),
+        }
+    }
+}
+
+impl From<BearerAuthError> for GraphQLError {
+    fn from(err: BearerAuthError) -> Self {
+        Self::Request(err)
+    }
+}
+
+impl From<serde_json::Error> for GraphQLError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+/// A client for a GraphQL endpoint, sending operations with the access token.
+///
+/// The token is acquired and refreshed like with [`BearerAuth`]. By default, the access token of
+/// the session is used. Operations requiring different scopes, or a different audience, can get
+/// a token of their own, selected by the name of the operation (`operationName`).
+#[derive(Clone, Debug)]
+pub struct GraphQLClient {
+    endpoint: Url,
+    client: Client,
+    auth: BearerAuth,
+    operations: HashMap<String, TokenRequest>,
+}
+
+impl GraphQLClient {
+    /// Send operations to the endpoint.
+    pub fn new(endpoint: Url) -> Self {
+        Self {
+            endpoint,
+            client: Client::new(),
+            auth: BearerAuth::new(),
+            operations: HashMap::new(),
+        }
+    }
+
+    /// Set the HTTP client
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the authentication, e.g. for using a specific agent
+    pub fn with_auth(mut self, auth: BearerAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Set the request for the token of an operation, by its name
+    pub fn with_operation_token(
+        mut self,
+        operation: impl Into<String>,
+        token_request: TokenRequest,
+    ) -> Self {
+        self.operations.insert(operation.into(), token_request);
+        self
+    }
+
+    /// Execute an operation.
+    ///
+    /// The body is the GraphQL request, with the `query`, `variables`, and `operationName`.
+    pub async fn execute<B, R>(&self, body: &B) -> Result<R, GraphQLError>
+    where
+        B: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_value(body)?;
+        let auth = match body
+            .get("operationName")
+            .and_then(|name| name.as_str())
+            .and_then(|name| self.operations.get(name))
+        {
+            Some(token_request) => self.auth.clone().with_token_request(token_request.clone()),
+            None => self.auth.clone(),
+        };
+
+        let request = self
+            .client
+            .post(self.endpoint.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?);
+        let response = auth.send(request).await?;
+        let body = response.bytes().await.map_err(GraphQLError::Response)?;
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
@@ -1,6 +1,16 @@
 //! Hooks for Yew
 
-use crate::{context::LatestAccessToken, prelude::OAuth2Context};
+use crate::{
+    agent::{AccessToken, Client, Error, OAuth2Operations, TokenRequest},
+    components::{
+        context::{use_auth_agent, Agent},
+        ScopedLogin,
+    },
+    context::{ClaimPaths, IssuerHealth, LatestAccessToken},
+    prelude::OAuth2Context,
+};
+#[cfg(feature = "openid")]
+use std::rc::Rc;
 use yew::prelude::*;
 
 #[cfg(feature = "openid")]
@@ -11,6 +21,13 @@ pub mod openid {
     pub fn use_auth_agent() -> Option<crate::components::context::Agent<Client>> {
         crate::components::context::use_auth_agent::<Client>()
     }
+
+    #[yew::hook]
+    pub fn use_access_token_for(
+        scopes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Option<super::AccessTokenFor<Client>> {
+        super::use_access_token_for::<Client>(scopes)
+    }
 }
 
 pub mod oauth2 {
@@ -20,6 +37,13 @@ pub mod oauth2 {
     pub fn use_auth_agent() -> Option<crate::components::context::Agent<Client>> {
         crate::components::context::use_auth_agent::<Client>()
     }
+
+    #[yew::hook]
+    pub fn use_access_token_for(
+        scopes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Option<super::AccessTokenFor<Client>> {
+        super::use_access_token_for::<Client>(scopes)
+    }
 }
 
 /// Get the authentication state.
@@ -33,3 +57,113 @@ pub fn use_auth_state() -> Option<OAuth2Context> {
 pub fn use_latest_access_token() -> Option<LatestAccessToken> {
     use_context()
 }
+
+/// Get the reachability of the issuer.
+#[hook]
+pub fn use_issuer_health() -> Option<IssuerHealth> {
+    use_context()
+}
+
+/// Check if the session has a role.
+///
+/// The roles are taken from the claims configured using [`ClaimPaths`]. Returns `false` if the
+/// session is not authenticated.
+#[hook]
+pub fn use_has_role(role: &str) -> bool {
+    let auth = use_auth_state();
+    let paths = use_context::<ClaimPaths>().unwrap_or_default();
+
+    auth.as_ref()
+        .and_then(|auth| auth.authentication())
+        .is_some_and(|auth| paths.roles(auth).iter().any(|r| r == role))
+}
+
+/// Check if the session has a scope.
+///
+/// The scopes are taken from the claims configured using [`ClaimPaths`]. Returns `false` if the
+/// session is not authenticated.
+#[hook]
+pub fn use_has_scope(scope: &str) -> bool {
+    let auth = use_auth_state();
+    let paths = use_context::<ClaimPaths>().unwrap_or_default();
+
+    auth.as_ref()
+        .and_then(|auth| auth.authentication())
+        .is_some_and(|auth| paths.scopes(auth).iter().any(|s| s == scope))
+}
+
+/// Get a handle to start the login of the wrapping [`crate::components::scoped::LoginScope`].
+#[hook]
+pub fn use_scoped_login() -> Option<ScopedLogin> {
+    use_context()
+}
+
+/// A handle to acquire access tokens for a set of scopes.
+pub struct AccessTokenFor<C: Client> {
+    agent: Agent<C>,
+    request: TokenRequest,
+}
+
+impl<C: Client> AccessTokenFor<C> {
+    /// Get an access token, valid for the scopes of the handle.
+    ///
+    /// The token is acquired silently if possible. Otherwise, a login is started, requesting the
+    /// additional scopes, and [`Error::InteractionRequired`] is returned.
+    pub async fn get(&self) -> Result<AccessToken, Error> {
+        self.agent.acquire_token(self.request.clone()).await
+    }
+
+    /// Declare the audience the token is used for, e.g. the API an HTTP integration serves.
+    ///
+    /// Tokens are only returned if they are issued for the audience.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.request = self.request.with_audience(audience);
+        self
+    }
+}
+
+impl<C: Client> Clone for AccessTokenFor<C> {
+    fn clone(&self) -> Self {
+        Self {
+            agent: self.agent.clone(),
+            request: self.request.clone(),
+        }
+    }
+}
+
+impl<C: Client> PartialEq for AccessTokenFor<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.agent == other.agent && self.request == other.request
+    }
+}
+
+/// Get a handle to acquire access tokens for a set of scopes.
+///
+/// Returns `None` if the component is not nested inside an [`crate::components::context::OAuth2`]
+/// component.
+#[hook]
+pub fn use_access_token_for<C>(
+    scopes: impl IntoIterator<Item = impl Into<String>>,
+) -> Option<AccessTokenFor<C>>
+where
+    C: Client,
+{
+    let agent = use_auth_agent::<C>();
+    let request = TokenRequest::new()
+        .with_scopes(scopes)
+        .with_interactive(true);
+
+    agent.map(|agent| AccessTokenFor { agent, request })
+}
+
+/// Get the user model, provided by a [`crate::components::UserProvider`].
+///
+/// Returns `None` if the session is not authenticated, or there is no provider of the user model.
+#[cfg(feature = "openid")]
+#[hook]
+pub fn use_user<U>() -> Option<Rc<U>>
+where
+    U: PartialEq + 'static,
+{
+    use_context::<Option<Rc<U>>>().flatten()
+}
@@ -0,0 +1,112 @@
+//! Helpers specific to Keycloak.
+//!
+//! Keycloak puts the roles of the user into the access token, and not into the ID token. Both
+//! the roles of the realm (`realm_access`) and the roles of clients (`resource_access`) can be
+//! accessed using [`KeycloakRoles`], or the hooks of this module.
+//!
+//! **NOTE**: The access token is not validated by the client. The roles must only be used for
+//! deciding what to show, the backend must still check them.
+
+use crate::context::Authentication;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The roles of a Keycloak session, taken from the access token.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeycloakRoles {
+    /// The roles of the realm.
+    pub realm: Vec<String>,
+    /// The roles of clients, by client ID.
+    pub resources: HashMap<String, Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+struct Access {
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+impl KeycloakRoles {
+    /// Extract the roles from the access token of the session.
+    ///
+    /// If the access token is not a JWT, or has no roles, the result is empty.
+    pub fn from_authentication(auth: &Authentication) -> Self {
+        let Some(mut claims) = auth.access_token_claims() else {
+            return Self::default();
+        };
+
+        let realm = claims
+            .remove("realm_access")
+            .and_then(|value| serde_json::from_value::<Access>(value).ok())
+            .unwrap_or_default()
+            .roles;
+
+        let resources = claims
+            .remove("resource_access")
+            .and_then(|value| serde_json::from_value::<HashMap<String, Access>>(value).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(client, access)| (client, access.roles))
+            .collect();
+
+        Self { realm, resources }
+    }
+
+    /// Check if the session has a role of the realm.
+    pub fn has_realm_role(&self, role: &str) -> bool {
+        self.realm.iter().any(|r| r == role)
+    }
+
+    /// Check if the session has a role of a client.
+    pub fn has_client_role(&self, client_id: &str, role: &str) -> bool {
+        self.resources
+            .get(client_id)
+            .is_some_and(|roles| roles.iter().any(|r| r == role))
+    }
+}
+
+impl From<&Authentication> for KeycloakRoles {
+    fn from(auth: &Authentication) -> Self {
+        Self::from_authentication(auth)
+    }
+}
+
+#[cfg(feature = "yew")]
+pub use hooks::*;
+
+#[cfg(feature = "yew")]
+mod hooks {
+    use super::KeycloakRoles;
+    use crate::hook::use_auth_state;
+    use yew::prelude::*;
+
+    /// Get the Keycloak roles of the session.
+    ///
+    /// Returns `None` if the session is not authenticated.
+    #[hook]
+    pub fn use_keycloak_roles() -> Option<KeycloakRoles> {
+        let auth = use_auth_state();
+        let authentication = auth
+            .as_ref()
+            .and_then(|auth| auth.authentication())
+            .cloned();
+
+        let roles = use_memo(authentication, |auth| {
+            auth.as_ref().map(KeycloakRoles::from_authentication)
+        });
+
+        (*roles).clone()
+    }
+
+    /// Check if the session has a role of the realm.
+    #[hook]
+    pub fn use_has_realm_role(role: &str) -> bool {
+        use_keycloak_roles().is_some_and(|roles| roles.has_realm_role(role))
+    }
+
+    /// Check if the session has a role of a client.
+    #[hook]
+    pub fn use_has_client_role(client_id: &str, role: &str) -> bool {
+        use_keycloak_roles().is_some_and(|roles| roles.has_client_role(client_id, role))
+    }
+}
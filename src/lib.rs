@@ -8,6 +8,29 @@
 //!
 //! In order to use OIDC, you will need to enable the feature `openid`.
 //!
+//! ## Keycloak
+//!
+//! Helpers specific to Keycloak, like accessing the roles of the session, can be enabled using
+//! the feature `keycloak`. See [`keycloak`].
+//!
+//! ## Conformance
+//!
+//! A client checking an issuer against the OpenID Connect Basic RP conformance profile, reporting
+//! the outcome of each check, can be enabled using the feature `conformance`. See
+//! [`conformance`].
+//!
+//! ## Layers
+//!
+//! The crate consists of two layers. The core, consisting of the [`agent`], [`config`],
+//! [`context`] and [`presets`] modules, manages the session and doesn't depend on Yew. The Yew
+//! layer, consisting of the `components` and `hook` modules, as well as the `oauth2` and `openid`
+//! shortcuts, binds the core to Yew. It is enabled by the default feature `yew`. Applications
+//! using a different framework can disable the default features and drive the [`agent::Agent`]
+//! directly.
+//!
+//! Code outside of the component tree, like service layers, can use the session through the
+//! global [`agent::OAuth2Agent`] handle, registered by the `OAuth2` component.
+//!
 //! ## Example
 //!
 //! **NOTE:** Also see the [readme](https://github.com/ctron/yew-oauth2/blob/main/README.md#examples) for more examples.
@@ -60,39 +83,114 @@
 //! ```
 
 pub mod agent;
+pub mod bearer;
+#[cfg(feature = "yew")]
 pub mod components;
 pub mod config;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 pub mod context;
+#[cfg(feature = "gloo-net")]
+pub mod fetch;
+pub mod graphql;
+#[cfg(feature = "yew")]
 pub mod hook;
+#[cfg(feature = "keycloak")]
+pub mod keycloak;
+pub mod middleware;
 pub mod prelude;
+pub mod presets;
+#[cfg(feature = "service-worker")]
+pub mod service_worker;
+#[cfg(feature = "sse")]
+pub mod sse;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "worker")]
+pub mod worker;
 
-#[cfg(feature = "openid")]
+#[cfg(all(feature = "openid", feature = "yew"))]
 pub mod openid {
     //! Common used Open ID Connect features
     pub use crate::agent::client::OpenIdClient as Client;
+    pub use crate::components::account::openid::*;
     pub use crate::components::context::openid::*;
     pub use crate::components::redirect::location::openid::*;
     #[cfg(feature = "yew-nested-router")]
     pub use crate::components::redirect::router::openid::*;
+    #[cfg(feature = "yew-router")]
+    pub mod yew_router {
+        //! The integration with `yew-router`, for the Open ID Connect variant
+        pub use crate::components::redirect::yew_router::openid::*;
+    }
+    pub use crate::components::retry::openid::*;
+    pub use crate::components::scoped::openid::*;
     pub use crate::config::openid::*;
 
     #[yew::hook]
     pub fn use_auth_agent() -> Option<crate::components::context::Agent<Client>> {
         crate::components::context::use_auth_agent::<Client>()
     }
+
+    #[yew::hook]
+    pub fn use_access_token_for(
+        scopes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Option<crate::hook::AccessTokenFor<Client>> {
+        crate::hook::use_access_token_for::<Client>(scopes)
+    }
 }
 
+#[cfg(feature = "yew")]
 pub mod oauth2 {
     //! Common used OAuth2 features
     pub use crate::agent::client::OAuth2Client as Client;
+    pub use crate::components::account::oauth2::*;
     pub use crate::components::context::oauth2::*;
     pub use crate::components::redirect::location::oauth2::*;
     #[cfg(feature = "yew-nested-router")]
     pub use crate::components::redirect::router::oauth2::*;
+    #[cfg(feature = "yew-router")]
+    pub mod yew_router {
+        //! The integration with `yew-router`, for the OAuth2 variant
+        pub use crate::components::redirect::yew_router::oauth2::*;
+    }
+    pub use crate::components::retry::oauth2::*;
+    pub use crate::components::scoped::oauth2::*;
     pub use crate::config::oauth2::*;
 
     #[yew::hook]
     pub fn use_auth_agent() -> Option<crate::components::context::Agent<Client>> {
         crate::components::context::use_auth_agent::<Client>()
     }
+
+    #[yew::hook]
+    pub fn use_access_token_for(
+        scopes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Option<crate::hook::AccessTokenFor<Client>> {
+        crate::hook::use_access_token_for::<Client>(scopes)
+    }
+}
+
+#[cfg(test)]
+mod test_util {
+    //! Helpers shared by the unit tests
+
+    use crate::agent::RandomSource;
+    use reqwest::Url;
+
+    /// Parse a URL, which is known to be valid.
+    pub(crate) fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    /// A source of randomness, filling in the same bytes each time.
+    #[derive(Debug)]
+    pub(crate) struct Fixed;
+
+    impl RandomSource for Fixed {
+        fn fill_bytes(&self, dest: &mut [u8]) -> Result<(), String> {
+            dest.fill(7);
+            Ok(())
+        }
+    }
 }
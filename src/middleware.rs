@@ -0,0 +1,197 @@
+//! Sending `reqwest` requests with the access token of the session.
+//!
+//! Existing API clients, based on `reqwest`, can send their requests through [`BearerAuth`],
+//! instead of adding the `Authorization` header themselves:
+//!
+//! ```rust,no_run
+//! use yew_oauth2::middleware::{BearerAuth, BearerAuthError};
+//!
+//! async fn fetch_items(client: &reqwest::Client) -> Result<String, BearerAuthError> {
+//!     let auth = BearerAuth::new();
+//!     let response = auth.send(client.get("https://api/items")).await?;
+//!     Ok(response.text().await?)
+//! }
+//! ```
+//!
+//! With the feature `reqwest-middleware`, a client of `reqwest-middleware` can use the
+//! [`BearerAuthMiddleware`] instead.
+//!
+//! **NOTE:** This uses the version of `reqwest` the crate depends on, the API client must use
+//! the same.
+
+use crate::{
+    agent::{OAuth2Agent, TokenRequest},
+    bearer::{self, BearerError, Sender},
+};
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+/// An error sending a request with [`BearerAuth`].
+pub type BearerAuthError = BearerError<reqwest::Error, Response>;
+
+impl<R> From<reqwest::Error> for BearerError<reqwest::Error, R> {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+/// Sending the requests of a [`RequestBuilder`].
+struct Builder;
+
+#[async_trait(?Send)]
+impl Sender for Builder {
+    type Request = RequestBuilder;
+    type Response = Response;
+    type Error = reqwest::Error;
+
+    fn try_clone(request: &RequestBuilder) -> Option<RequestBuilder> {
+        request.try_clone()
+    }
+
+    fn is_unauthorized(response: &Response) -> bool {
+        response.status() == StatusCode::UNAUTHORIZED
+    }
+
+    async fn send(&mut self, request: RequestBuilder, token: &str) -> reqwest::Result<Response> {
+        request.bearer_auth(token).send().await
+    }
+}
+
+/// Adding the access token of the session to `reqwest` requests.
+///
+/// The token is acquired from the agent right before sending the request, refreshing the session
+/// first if the token is about to expire. If the request is rejected as unauthorized (`401`), the
+/// session is refreshed and the request is sent once more. If that is rejected as well, this is
+/// reported to the agent (see [`crate::agent::UnauthorizedPolicy`]), and surfaced as
+/// [`BearerError::Unauthorized`]. Requests with a streaming body can't be sent again, and
+/// are not retried.
+#[derive(Clone, Debug, Default)]
+pub struct BearerAuth {
+    agent: Option<OAuth2Agent>,
+    token_request: TokenRequest,
+}
+
+impl BearerAuth {
+    /// Use the [global agent](OAuth2Agent::global).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the agent, providing the token
+    pub fn with_agent(mut self, agent: OAuth2Agent) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+
+    /// Set the request for the token, e.g. for a different audience
+    ///
+    /// Defaults to the access token of the session.
+    pub fn with_token_request(mut self, token_request: TokenRequest) -> Self {
+        self.token_request = token_request;
+        self
+    }
+
+    /// Send the request, with the access token.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, BearerAuthError> {
+        bearer::send(
+            self.agent.as_ref(),
+            &self.token_request,
+            &mut Builder,
+            request,
+        )
+        .await
+    }
+}
+
+/// Adding the access token of the session to the requests of a `reqwest-middleware` client.
+///
+/// This works like [`BearerAuth`], using the [global agent](OAuth2Agent::global). When the
+/// request is still rejected as unauthorized after refreshing the token, this is reported to the
+/// agent, and the response is returned like any other.
+///
+/// ```rust,ignore
+/// use yew_oauth2::middleware::BearerAuthMiddleware;
+///
+/// let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+///     .with(BearerAuthMiddleware::new())
+///     .build();
+/// let response = client.get("https://api/items").send().await?;
+/// ```
+///
+/// **NOTE:** As the agent runs on the main thread of the browser, the middleware is only
+/// implemented for the target `wasm32`.
+#[cfg(feature = "reqwest-middleware")]
+#[derive(Clone, Debug, Default)]
+pub struct BearerAuthMiddleware {
+    token_request: TokenRequest,
+}
+
+#[cfg(feature = "reqwest-middleware")]
+impl BearerAuthMiddleware {
+    /// Use the [global agent](OAuth2Agent::global).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the request for the token, e.g. for a different audience
+    ///
+    /// Defaults to the access token of the session.
+    pub fn with_token_request(mut self, token_request: TokenRequest) -> Self {
+        self.token_request = token_request;
+        self
+    }
+}
+
+#[cfg(all(feature = "reqwest-middleware", target_arch = "wasm32"))]
+#[async_trait(?Send)]
+impl reqwest_middleware::Middleware for BearerAuthMiddleware {
+    async fn handle(
+        &self,
+        request: reqwest::Request,
+        extensions: &mut task_local_extensions::Extensions,
+        next: reqwest_middleware::Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mut chain = Chain { extensions, next };
+        match bearer::send(None, &self.token_request, &mut chain, request).await {
+            Ok(response) | Err(BearerError::Unauthorized(response)) => Ok(response),
+            Err(BearerError::Request(err)) => Err(err),
+            Err(BearerError::Token(err)) => Err(reqwest_middleware::Error::middleware(err)),
+        }
+    }
+}
+
+/// Sending the requests to the next middleware.
+#[cfg(all(feature = "reqwest-middleware", target_arch = "wasm32"))]
+struct Chain<'a> {
+    extensions: &'a mut task_local_extensions::Extensions,
+    next: reqwest_middleware::Next<'a>,
+}
+
+#[cfg(all(feature = "reqwest-middleware", target_arch = "wasm32"))]
+#[async_trait(?Send)]
+impl Sender for Chain<'_> {
+    type Request = reqwest::Request;
+    type Response = Response;
+    type Error = reqwest_middleware::Error;
+
+    fn try_clone(request: &reqwest::Request) -> Option<reqwest::Request> {
+        request.try_clone()
+    }
+
+    fn is_unauthorized(response: &Response) -> bool {
+        response.status() == StatusCode::UNAUTHORIZED
+    }
+
+    async fn send(
+        &mut self,
+        mut request: reqwest::Request,
+        token: &str,
+    ) -> reqwest_middleware::Result<Response> {
+        let value = reqwest::header::HeaderValue::from_str(&format!("Bearer {token}"))
+            .map_err(reqwest_middleware::Error::middleware)?;
+        request
+            .headers_mut()
+            .insert(reqwest::header::AUTHORIZATION, value);
+        self.next.clone().run(request, self.extensions).await
+    }
+}
@@ -1,10 +1,16 @@
 //! The prelude, includes most things you will need.
 
-pub use crate::agent::{LoginOptions, OAuth2Error, OAuth2Operations};
+pub use crate::agent::{
+    AccessToken, AccessTokenProvider, IdpHint, LoginOptions, OAuth2Error, OAuth2Operations, Prompt,
+    TokenRequest,
+};
+#[cfg(feature = "yew")]
 pub use crate::components::*;
 pub use crate::context::*;
+#[cfg(feature = "yew")]
 pub use crate::hook::*;
 
+#[cfg(feature = "yew")]
 pub use crate::oauth2;
-#[cfg(feature = "openid")]
+#[cfg(all(feature = "openid", feature = "yew"))]
 pub use crate::openid;
@@ -0,0 +1,699 @@
+//! Presets for specific issuers.
+//!
+//! Some issuers deviate from plain OpenID Connect, or require specific settings. A preset
+//! creates the client configuration, and the scopes and audience to use with the
+//! [`crate::components::context::OAuth2`] component. Most issuers support OpenID Connect, those
+//! which don't (like GitHub) are used with the plain OAuth2 client.
+//!
+//! ```rust
+//! use yew::prelude::*;
+//! use yew_oauth2::presets::{Auth0, Preset};
+//! # #[cfg(feature = "openid")]
+//! use yew_oauth2::openid::*;
+//!
+//! # #[cfg(feature = "openid")]
+//! #[function_component(MyApplication)]
+//! fn my_app() -> Html {
+//!     let preset = Auth0::new("my-tenant.eu.auth0.com", "my-client")
+//!         .with_audience("https://api.example.com");
+//!
+//!     html!(
+//!         <OAuth2 config={preset.config()} scopes={preset.scopes()} audience={preset.audience()}>
+//!             // the application
+//!         </OAuth2>
+//!     )
+//! }
+//! ```
+
+use crate::config::{oauth2, openid};
+
+/// A preset for a specific issuer.
+pub trait Preset {
+    /// The configuration of the client, [`openid::Config`] for OpenID Connect issuers, and
+    /// [`oauth2::Config`] for plain OAuth2 ones.
+    type Config;
+
+    /// The client configuration.
+    fn config(&self) -> Self::Config;
+
+    /// The scopes to request.
+    fn scopes(&self) -> Vec<String>;
+
+    /// The audience of the access token.
+    fn audience(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Ensure the URL has a scheme, and no trailing slash.
+fn base_url(domain: &str) -> String {
+    let domain = domain.trim_end_matches('/');
+    match domain.starts_with("https://") || domain.starts_with("http://") {
+        true => domain.to_string(),
+        false => format!("https://{domain}"),
+    }
+}
+
+/// A preset for Auth0.
+///
+/// Auth0 requires an audience for issuing access tokens for an API, logs out using the
+/// `/v2/logout` endpoint (with `returnTo` and `client_id`), and supports logging in to an
+/// organization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Auth0 {
+    domain: String,
+    client_id: String,
+    audience: Option<String>,
+    organization: Option<String>,
+}
+
+impl Auth0 {
+    /// Create a new preset, for the domain of the tenant (like `my-tenant.eu.auth0.com`).
+    pub fn new(domain: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            domain: domain.into(),
+            client_id: client_id.into(),
+            audience: None,
+            organization: None,
+        }
+    }
+
+    /// Set the audience, the identifier of the API
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Set the organization to log in to
+    pub fn with_organization(mut self, organization: impl Into<String>) -> Self {
+        self.organization = Some(organization.into());
+        self
+    }
+}
+
+impl Preset for Auth0 {
+    type Config = openid::Config;
+
+    fn config(&self) -> openid::Config {
+        let base_url = base_url(&self.domain);
+
+        // the issuer of Auth0 has a trailing slash
+        let mut config = openid::Config::new(&self.client_id, format!("{base_url}/"))
+            .with_end_session_url(format!("{base_url}/v2/logout"))
+            .with_post_logout_redirect_name("returnTo")
+            .add_additional_logout_param("client_id", &self.client_id);
+
+        if let Some(organization) = &self.organization {
+            config = config.add_additional_auth_param("organization", organization);
+        }
+
+        config
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        // `offline_access` is required for getting a refresh token
+        ["openid", "profile", "email", "offline_access"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    fn audience(&self) -> Option<String> {
+        self.audience.clone()
+    }
+}
+
+/// A preset for Azure AD (Microsoft Entra ID), using the v2.0 endpoints.
+///
+/// The `common` and `organizations` tenants announce an issuer containing a `{tenantid}`
+/// placeholder, so those are configured as multi-tenant (see
+/// [`openid::Config::multi_tenant`]). Access tokens for an API are requested using its scopes (like
+/// `api://my-api/access_as_user`), Azure AD doesn't use an audience parameter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Azure {
+    authority: String,
+    tenant: String,
+    client_id: String,
+    api_scopes: Vec<String>,
+}
+
+impl Azure {
+    /// Create a new preset, for a tenant (its ID or domain, or `common` or `organizations`).
+    pub fn new(tenant: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            authority: "https://login.microsoftonline.com".to_string(),
+            tenant: tenant.into(),
+            client_id: client_id.into(),
+            api_scopes: vec![],
+        }
+    }
+
+    /// Set the authority, defaults to `https://login.microsoftonline.com`
+    ///
+    /// This is required for national clouds, like `https://login.microsoftonline.us`.
+    pub fn with_authority(mut self, authority: impl Into<String>) -> Self {
+        self.authority = authority.into();
+        self
+    }
+
+    /// Add a scope of an API, like `api://my-api/access_as_user`
+    pub fn add_api_scope(mut self, scope: impl Into<String>) -> Self {
+        self.api_scopes.push(scope.into());
+        self
+    }
+
+    /// Check if the tenant is one of the multi-tenant endpoints.
+    fn is_multi_tenant(&self) -> bool {
+        matches!(self.tenant.as_str(), "common" | "organizations")
+    }
+}
+
+impl Preset for Azure {
+    type Config = openid::Config;
+
+    fn config(&self) -> openid::Config {
+        let base_url = format!("{}/{}", base_url(&self.authority), self.tenant);
+
+        openid::Config::new(&self.client_id, format!("{base_url}/v2.0"))
+            .with_end_session_url(format!("{base_url}/oauth2/v2.0/logout"))
+            .with_multi_tenant(self.is_multi_tenant())
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        // `offline_access` is required for getting a refresh token
+        ["openid", "profile", "offline_access"]
+            .into_iter()
+            .map(ToString::to_string)
+            .chain(self.api_scopes.iter().cloned())
+            .collect()
+    }
+}
+
+/// A preset for AWS Cognito user pools.
+///
+/// The issuer is derived from the ID of the user pool, which starts with its region (like
+/// `eu-west-1_AbCdEf123`). Logging out is performed using the `/logout` endpoint of the hosted UI,
+/// which is not announced by the discovery endpoint, and uses `logout_uri` and `client_id`.
+///
+/// **NOTE:** The URL to return to after the logout must be registered as a "sign out URL" of the
+/// app client.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cognito {
+    user_pool_id: String,
+    domain: String,
+    client_id: String,
+    scopes: Vec<String>,
+}
+
+impl Cognito {
+    /// Create a new preset, for a user pool and the domain of its hosted UI (like
+    /// `my-app.auth.eu-west-1.amazoncognito.com`, or a custom domain).
+    pub fn new(
+        user_pool_id: impl Into<String>,
+        domain: impl Into<String>,
+        client_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            user_pool_id: user_pool_id.into(),
+            domain: domain.into(),
+            client_id: client_id.into(),
+            scopes: vec![],
+        }
+    }
+
+    /// Add a scope, like a custom scope of a resource server
+    pub fn add_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// The region of the user pool.
+    fn region(&self) -> &str {
+        self.user_pool_id
+            .split_once('_')
+            .map_or(self.user_pool_id.as_str(), |(region, _)| region)
+    }
+}
+
+impl Preset for Cognito {
+    type Config = openid::Config;
+
+    fn config(&self) -> openid::Config {
+        let issuer_url = format!(
+            "https://cognito-idp.{}.amazonaws.com/{}",
+            self.region(),
+            self.user_pool_id
+        );
+
+        openid::Config::new(&self.client_id, issuer_url)
+            .with_end_session_url(format!("{}/logout", base_url(&self.domain)))
+            .with_post_logout_redirect_name("logout_uri")
+            .add_additional_logout_param("client_id", &self.client_id)
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        // Cognito issues a refresh token without requesting `offline_access`
+        ["openid", "profile", "email"]
+            .into_iter()
+            .map(ToString::to_string)
+            .chain(self.scopes.iter().cloned())
+            .collect()
+    }
+}
+
+/// A preset for Google.
+///
+/// Google doesn't support the `offline_access` scope. A refresh token is only issued when
+/// requesting offline access, and Google only issues it with the first consent of the user. So
+/// offline access also asks the user for consent again on every login. The `prompt` of the
+/// [`crate::agent::LoginOptions`] takes precedence, e.g. for silent logins.
+///
+/// With incremental authorization, the scopes granted with previous logins are kept when
+/// requesting additional ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Google {
+    client_id: String,
+    scopes: Vec<String>,
+    offline: bool,
+    incremental: bool,
+}
+
+impl Google {
+    /// Create a new preset, requesting offline access.
+    pub fn new(client_id: impl Into<String>) -> Self {
+        Self {
+            client_id: client_id.into(),
+            scopes: vec![],
+            offline: true,
+            incremental: false,
+        }
+    }
+
+    /// Add a scope, like `https://www.googleapis.com/auth/calendar.readonly`
+    pub fn add_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+
+    /// Set if offline access (a refresh token) should be requested
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Set if previously granted scopes should be included (incremental authorization)
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+}
+
+impl Preset for Google {
+    type Config = openid::Config;
+
+    fn config(&self) -> openid::Config {
+        let mut config = openid::Config::new(&self.client_id, "https://accounts.google.com");
+
+        if self.offline {
+            config = config
+                .add_additional_auth_param("access_type", "offline")
+                .add_additional_auth_param("prompt", "consent");
+        }
+
+        if self.incremental {
+            config = config.add_additional_auth_param("include_granted_scopes", "true");
+        }
+
+        config
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        ["openid", "profile", "email"]
+            .into_iter()
+            .map(ToString::to_string)
+            .chain(self.scopes.iter().cloned())
+            .collect()
+    }
+}
+
+/// A preset for GitHub (and GitHub Enterprise), using the plain OAuth2 client.
+///
+/// GitHub doesn't support OpenID Connect for users, and its token endpoint doesn't follow the
+/// specification: errors are reported with a status of `200`. So the token response is parsed
+/// leniently.
+///
+/// **NOTE:** The token endpoint of GitHub doesn't allow requests from the browser (CORS). The token
+/// requests must be forwarded using a proxy of the backend, see [`GitHub::with_token_url`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitHub {
+    host: String,
+    client_id: String,
+    token_url: Option<String>,
+    scopes: Vec<String>,
+}
+
+impl GitHub {
+    /// Create a new preset, for `github.com`.
+    pub fn new(client_id: impl Into<String>) -> Self {
+        Self {
+            host: "github.com".to_string(),
+            client_id: client_id.into(),
+            token_url: None,
+            scopes: vec![],
+        }
+    }
+
+    /// Set the host, for using a GitHub Enterprise server
+    pub fn with_host(mut self, host: impl Into<String>) -> Self {
+        self.host = host.into();
+        self
+    }
+
+    /// Set the URL of a proxy, forwarding requests to the token endpoint
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = Some(token_url.into());
+        self
+    }
+
+    /// Add a scope, like `repo`
+    pub fn add_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scopes.push(scope.into());
+        self
+    }
+}
+
+impl Preset for GitHub {
+    type Config = oauth2::Config;
+
+    fn config(&self) -> oauth2::Config {
+        let base_url = base_url(&self.host);
+        let token_url = self
+            .token_url
+            .clone()
+            .unwrap_or_else(|| format!("{base_url}/login/oauth/access_token"));
+
+        oauth2::Config::new(
+            &self.client_id,
+            format!("{base_url}/login/oauth/authorize"),
+            token_url,
+        )
+        .with_account_url(format!("{base_url}/settings/profile"))
+        .with_lenient_token_response(true)
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        ["read:user"]
+            .into_iter()
+            .map(ToString::to_string)
+            .chain(self.scopes.iter().cloned())
+            .collect()
+    }
+}
+
+/// A preset for Okta.
+///
+/// Okta has an org authorization server, whose issuer is the domain itself. Its access tokens are
+/// only meant for Okta's own APIs. Access tokens for custom APIs are issued by custom
+/// authorization servers (like `default`), whose issuer is `{domain}/oauth2/{id}`.
+///
+/// Logging out requires the ID token as hint, and the URL to return to must be registered as a
+/// "sign-out redirect URI" of the application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Okta {
+    domain: String,
+    client_id: String,
+    authorization_server: Option<String>,
+}
+
+impl Okta {
+    /// Create a new preset, for the org authorization server of the domain (like
+    /// `my-org.okta.com`).
+    pub fn new(domain: impl Into<String>, client_id: impl Into<String>) -> Self {
+        Self {
+            domain: domain.into(),
+            client_id: client_id.into(),
+            authorization_server: None,
+        }
+    }
+
+    /// Use a custom authorization server, like `default`
+    pub fn with_authorization_server(mut self, authorization_server: impl Into<String>) -> Self {
+        self.authorization_server = Some(authorization_server.into());
+        self
+    }
+}
+
+impl Preset for Okta {
+    type Config = openid::Config;
+
+    fn config(&self) -> openid::Config {
+        let base_url = base_url(&self.domain);
+        let issuer_url = match &self.authorization_server {
+            Some(id) => format!("{base_url}/oauth2/{id}"),
+            None => base_url,
+        };
+
+        // the org authorization server has its endpoints below `/oauth2` as well
+        let endpoints = match &self.authorization_server {
+            Some(_) => issuer_url.clone(),
+            None => format!("{issuer_url}/oauth2"),
+        };
+
+        openid::Config::new(&self.client_id, &issuer_url)
+            .with_end_session_url(format!("{endpoints}/v1/logout"))
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        // `offline_access` is required for getting a refresh token
+        ["openid", "profile", "email", "offline_access"]
+            .into_iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+        params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn base_urls() {
+        assert_eq!(
+            base_url("my-tenant.auth0.com"),
+            "https://my-tenant.auth0.com"
+        );
+        assert_eq!(
+            base_url("https://my-tenant.auth0.com/"),
+            "https://my-tenant.auth0.com"
+        );
+        assert_eq!(base_url("http://localhost:8080"), "http://localhost:8080");
+    }
+
+    #[test]
+    fn auth0() {
+        let preset = Auth0::new("my-tenant.eu.auth0.com", "client")
+            .with_audience("https://api.example.com")
+            .with_organization("org_123");
+        let config = preset.config();
+
+        assert_eq!(config.issuer_url, "https://my-tenant.eu.auth0.com/");
+        assert_eq!(
+            config.end_session_url.as_deref(),
+            Some("https://my-tenant.eu.auth0.com/v2/logout")
+        );
+        assert_eq!(
+            config.post_logout_redirect_name.as_deref(),
+            Some("returnTo")
+        );
+        assert_eq!(
+            config.additional_auth_params,
+            params(&[("organization", "org_123")])
+        );
+        assert_eq!(
+            config.additional_logout_params,
+            params(&[("client_id", "client")])
+        );
+        assert_eq!(
+            preset.scopes(),
+            ["openid", "profile", "email", "offline_access"]
+        );
+        assert_eq!(
+            preset.audience().as_deref(),
+            Some("https://api.example.com")
+        );
+    }
+
+    #[test]
+    fn azure() {
+        let preset = Azure::new("contoso.onmicrosoft.com", "client")
+            .add_api_scope("api://my-api/access_as_user");
+        let config = preset.config();
+
+        assert_eq!(
+            config.issuer_url,
+            "https://login.microsoftonline.com/contoso.onmicrosoft.com/v2.0"
+        );
+        assert_eq!(
+            config.end_session_url.as_deref(),
+            Some("https://login.microsoftonline.com/contoso.onmicrosoft.com/oauth2/v2.0/logout")
+        );
+        assert!(!config.multi_tenant);
+        assert!(config.additional_auth_params.is_empty());
+        assert_eq!(
+            preset.scopes(),
+            [
+                "openid",
+                "profile",
+                "offline_access",
+                "api://my-api/access_as_user"
+            ]
+        );
+        assert_eq!(preset.audience(), None);
+    }
+
+    #[test]
+    fn azure_multi_tenant() {
+        let config = Azure::new("common", "client")
+            .with_authority("https://login.microsoftonline.us/")
+            .config();
+
+        assert_eq!(
+            config.issuer_url,
+            "https://login.microsoftonline.us/common/v2.0"
+        );
+        assert!(config.multi_tenant);
+        assert!(Azure::new("organizations", "client").config().multi_tenant);
+    }
+
+    #[test]
+    fn cognito() {
+        let preset = Cognito::new(
+            "eu-west-1_AbCdEf123",
+            "my-app.auth.eu-west-1.amazoncognito.com",
+            "client",
+        )
+        .add_scope("api/read");
+        let config = preset.config();
+
+        assert_eq!(
+            config.issuer_url,
+            "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_AbCdEf123"
+        );
+        assert_eq!(
+            config.end_session_url.as_deref(),
+            Some("https://my-app.auth.eu-west-1.amazoncognito.com/logout")
+        );
+        assert_eq!(
+            config.post_logout_redirect_name.as_deref(),
+            Some("logout_uri")
+        );
+        assert_eq!(
+            config.additional_logout_params,
+            params(&[("client_id", "client")])
+        );
+        assert_eq!(preset.scopes(), ["openid", "profile", "email", "api/read"]);
+    }
+
+    #[test]
+    fn google() {
+        let preset = Google::new("client")
+            .add_scope("https://www.googleapis.com/auth/calendar.readonly")
+            .with_incremental(true);
+        let config = preset.config();
+
+        assert_eq!(config.issuer_url, "https://accounts.google.com");
+        assert_eq!(
+            config.additional_auth_params,
+            params(&[
+                ("access_type", "offline"),
+                ("prompt", "consent"),
+                ("include_granted_scopes", "true")
+            ])
+        );
+        assert_eq!(
+            preset.scopes(),
+            [
+                "openid",
+                "profile",
+                "email",
+                "https://www.googleapis.com/auth/calendar.readonly"
+            ]
+        );
+    }
+
+    #[test]
+    fn google_online() {
+        let config = Google::new("client").with_offline(false).config();
+        assert!(config.additional_auth_params.is_empty());
+    }
+
+    #[test]
+    fn github() {
+        let preset = GitHub::new("client").add_scope("repo");
+        let config = preset.config();
+
+        assert_eq!(config.auth_url, "https://github.com/login/oauth/authorize");
+        assert_eq!(
+            config.token_url,
+            "https://github.com/login/oauth/access_token"
+        );
+        assert_eq!(
+            config.account_url.as_deref(),
+            Some("https://github.com/settings/profile")
+        );
+        assert!(config.lenient_token_response);
+        assert!(config.additional_auth_params.is_empty());
+        assert_eq!(preset.scopes(), ["read:user", "repo"]);
+    }
+
+    #[test]
+    fn github_enterprise() {
+        let config = GitHub::new("client")
+            .with_host("github.example.com")
+            .with_token_url("/github/token")
+            .config();
+
+        assert_eq!(
+            config.auth_url,
+            "https://github.example.com/login/oauth/authorize"
+        );
+        assert_eq!(config.token_url, "/github/token");
+    }
+
+    #[test]
+    fn okta() {
+        let preset = Okta::new("my-org.okta.com", "client");
+        let config = preset.config();
+
+        assert_eq!(config.issuer_url, "https://my-org.okta.com");
+        assert_eq!(
+            config.end_session_url.as_deref(),
+            Some("https://my-org.okta.com/oauth2/v1/logout")
+        );
+        assert!(config.additional_auth_params.is_empty());
+        assert_eq!(
+            preset.scopes(),
+            ["openid", "profile", "email", "offline_access"]
+        );
+    }
+
+    #[test]
+    fn okta_authorization_server() {
+        let config = Okta::new("my-org.okta.com", "client")
+            .with_authorization_server("default")
+            .config();
+
+        assert_eq!(config.issuer_url, "https://my-org.okta.com/oauth2/default");
+        assert_eq!(
+            config.end_session_url.as_deref(),
+            Some("https://my-org.okta.com/oauth2/default/v1/logout")
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Attaching the access token to requests, using a Service Worker.
+//!
+//! Instead of handing the access token to every HTTP client of the application, the agent sends it
+//! to a Service Worker. The worker attaches it, as `Authorization: Bearer` header, to the
+//! same-origin requests of the page, optionally limited to a set of path prefixes. Requests which
+//! already carry an `Authorization` header, as well as navigations, are left alone. Authorized
+//! requests bypass the HTTP cache, so that their responses aren't stored.
+//!
+//! The script of the Service Worker is provided as [`SCRIPT`]. It must be served from the origin
+//! of the application, e.g. by copying it into the distribution of the application. It may be
+//! used as the Service Worker itself, or imported into an existing one, using `importScripts`.
+//!
+//! ```rust,no_run
+//! use yew_oauth2::{agent::OAuth2Agent, service_worker::TokenInjection};
+//!
+//! async fn start(agent: OAuth2Agent) -> Result<(), String> {
+//!     let injector = TokenInjection::new("/token-injection.js")
+//!         .add_path("/api/")
+//!         .start(agent)
+//!         .await?;
+//!     // attach the token for the lifetime of the page
+//!     injector.forget();
+//!     Ok(())
+//! }
+//! ```
+//!
+//! **NOTE:** The Service Worker keeps the token in memory only. When the browser stops the idle
+//! worker, it asks the page for the token again, with the next request.
+
+use crate::{agent::OAuth2Agent, context::OAuth2Context};
+use futures_core::Stream;
+use gloo_events::EventListener;
+use gloo_utils::window;
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::{Rc, Weak},
+};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    MessageEvent, RegistrationOptions, ServiceWorker, ServiceWorkerContainer,
+    ServiceWorkerRegistration,
+};
+
+/// The script of the Service Worker, attaching the token.
+pub const SCRIPT: &str = include_str!("token-injection.js");
+
+/// The type of the messages, requesting the token.
+const TOKEN_REQUEST: &str = "yew-oauth2:token-request";
+
+/// The token of the page, sent to the Service Worker.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(tag = "type", rename = "yew-oauth2:token")]
+struct TokenMessage {
+    token: Option<String>,
+    expires: Option<u64>,
+    paths: Vec<String>,
+}
+
+impl TokenMessage {
+    fn post(&self, worker: &ServiceWorker) {
+        let result = serde_json::to_string(self)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+            .and_then(|message| js_sys::JSON::parse(&message))
+            .and_then(|message| worker.post_message(&message));
+        if let Err(err) = result {
+            log::warn!("Failed to send the token to the Service Worker: {err:?}");
+        }
+    }
+}
+
+/// Attaching the access token to requests, using a Service Worker.
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenInjection {
+    script_url: String,
+    scope: Option<String>,
+    paths: Vec<String>,
+}
+
+impl TokenInjection {
+    /// Use the Service Worker of the script at the URL, see [`SCRIPT`].
+    pub fn new(script_url: impl Into<String>) -> Self {
+        Self {
+            script_url: script_url.into(),
+            scope: None,
+            paths: vec![],
+        }
+    }
+
+    /// Set the scope of the Service Worker registration
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Add a path prefix, which requests must match for attaching the token
+    ///
+    /// Without any path prefix, the token is attached to all same-origin requests.
+    pub fn add_path(mut self, path: impl Into<String>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Register the Service Worker, and start sending it the token of the agent.
+    ///
+    /// The token is sent until the returned [`TokenInjector`] is dropped.
+    pub async fn start(self, agent: OAuth2Agent) -> Result<TokenInjector, String> {
+        let container = window().navigator().service_worker();
+
+        let options = RegistrationOptions::new();
+        if let Some(scope) = &self.scope {
+            options.set_scope(scope);
+        }
+        JsFuture::from(container.register_with_options(&self.script_url, &options))
+            .await
+            .map_err(|err| format!("failed to register the Service Worker: {err:?}"))?;
+        let ready = container
+            .ready()
+            .map_err(|err| format!("failed to access the Service Worker: {err:?}"))?;
+        let registration = JsFuture::from(ready)
+            .await
+            .map_err(|err| format!("failed to activate the Service Worker: {err:?}"))?
+            .unchecked_into::<ServiceWorkerRegistration>();
+
+        let state = Rc::new(RefCell::new(Injection {
+            container: container.clone(),
+            registration,
+            message: TokenMessage {
+                paths: self.paths,
+                ..Default::default()
+            },
+        }));
+
+        let listeners = vec![
+            // the worker lost the token, e.g. after being restarted
+            EventListener::new(&container, "message", {
+                let state = Rc::downgrade(&state);
+                move |event| {
+                    let Some(event) = event.dyn_ref::<MessageEvent>() else {
+                        return;
+                    };
+                    let requested = js_sys::Reflect::get(&event.data(), &"type".into())
+                        .ok()
+                        .and_then(|kind| kind.as_string());
+                    if requested.as_deref() != Some(TOKEN_REQUEST) {
+                        return;
+                    }
+                    if let (Some(state), Some(worker)) = (
+                        state.upgrade(),
+                        event.source().and_then(|source| source.dyn_into().ok()),
+                    ) {
+                        state.borrow().message.post(&worker);
+                    }
+                }
+            }),
+            // a new worker took over
+            EventListener::new(&container, "controllerchange", {
+                let state = Rc::downgrade(&state);
+                move |_| {
+                    if let Some(state) = state.upgrade() {
+                        state.borrow().send();
+                    }
+                }
+            }),
+        ];
+
+        spawn_local(Injection::run(Rc::downgrade(&state), agent));
+
+        Ok(TokenInjector {
+            state,
+            _listeners: listeners,
+        })
+    }
+}
+
+struct Injection {
+    container: ServiceWorkerContainer,
+    registration: ServiceWorkerRegistration,
+    message: TokenMessage,
+}
+
+impl Injection {
+    /// Send the token to the workers controlling the page.
+    fn send(&self) {
+        let active = self.registration.active();
+        if let Some(active) = &active {
+            self.message.post(active);
+        }
+        if let Some(controller) = self.container.controller() {
+            if active.as_ref() != Some(&controller) {
+                self.message.post(&controller);
+            }
+        }
+    }
+
+    /// Send each new token of the agent, until the injector is dropped.
+    async fn run(state: Weak<RefCell<Self>>, agent: OAuth2Agent) {
+        let mut stream = agent.stream();
+        while let Some(context) =
+            std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            let Some(state) = state.upgrade() else {
+                break;
+            };
+            let mut state = state.borrow_mut();
+            let authentication = match &context {
+                OAuth2Context::Authenticated(authentication) => Some(authentication),
+                _ => None,
+            };
+            state.message.token = authentication.map(|auth| auth.access_token.clone());
+            state.message.expires = authentication.and_then(|auth| auth.expires);
+            state.send();
+        }
+    }
+}
+
+/// Sending the token to the Service Worker, see [`TokenInjection::start`].
+///
+/// Dropping it removes the token from the Service Worker.
+#[must_use = "dropping the injector removes the token"]
+pub struct TokenInjector {
+    state: Rc<RefCell<Injection>>,
+    _listeners: Vec<EventListener>,
+}
+
+impl TokenInjector {
+    /// Keep sending the token, for the lifetime of the page.
+    pub fn forget(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for TokenInjector {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.message.token = None;
+        state.message.expires = None;
+        state.send();
+    }
+}
+
+impl std::fmt::Debug for TokenInjector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenInjector")
+    }
+}
@@ -0,0 +1,97 @@
+// Service Worker glue of yew-oauth2, attaching the access token to same-origin requests.
+//
+// Use it as the script of the Service Worker, or import it into an existing one, using
+// `importScripts`. The tokens are sent by the pages, see the module `service_worker` of the crate.
+(() => {
+  const TOKEN = "yew-oauth2:token";
+  const TOKEN_REQUEST = "yew-oauth2:token-request";
+  // the time to wait for a page to send its token, after the worker was restarted
+  const TOKEN_TIMEOUT = 1000;
+
+  // the state of each page, by the ID of its client: { token, expires, paths }
+  const pages = new Map();
+  let waiting = [];
+
+  self.addEventListener("install", () => self.skipWaiting());
+  self.addEventListener("activate", (event) => event.waitUntil(self.clients.claim()));
+
+  self.addEventListener("message", (event) => {
+    const data = event.data;
+    if (!data || data.type !== TOKEN || !event.source) {
+      return;
+    }
+
+    pages.set(event.source.id, data);
+    const resolved = waiting;
+    waiting = [];
+    resolved.forEach((resolve) => resolve());
+
+    // forget about pages which are gone
+    event.waitUntil(
+      self.clients.matchAll({ type: "window" }).then((clients) => {
+        const ids = new Set(clients.map((client) => client.id));
+        for (const id of pages.keys()) {
+          if (!ids.has(id)) {
+            pages.delete(id);
+          }
+        }
+      }),
+    );
+  });
+
+  const usable = (page) =>
+    !!page && !!page.token && (page.expires == null || page.expires * 1000 > Date.now());
+
+  const matches = (page, url) =>
+    page.paths.length === 0 || page.paths.some((path) => url.pathname.startsWith(path));
+
+  // ask the page for its token, in case the worker was restarted, losing it
+  const requestToken = async (clientId) => {
+    const client = await self.clients.get(clientId);
+    if (!client) {
+      return;
+    }
+    const received = new Promise((resolve) => waiting.push(resolve));
+    client.postMessage({ type: TOKEN_REQUEST });
+    await Promise.race([received, new Promise((resolve) => setTimeout(resolve, TOKEN_TIMEOUT))]);
+  };
+
+  const inject = async (event, url) => {
+    if (!usable(pages.get(event.clientId))) {
+      await requestToken(event.clientId);
+    }
+    const page = pages.get(event.clientId);
+    if (!usable(page) || !matches(page, url)) {
+      return fetch(event.request);
+    }
+
+    const headers = new Headers(event.request.headers);
+    headers.set("Authorization", `Bearer ${page.token}`);
+    // keep responses to authorized requests out of the HTTP cache
+    return fetch(new Request(event.request, { headers, cache: "no-store" }));
+  };
+
+  self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (
+      url.origin !== self.location.origin ||
+      request.mode === "navigate" ||
+      request.headers.has("Authorization") ||
+      !event.clientId
+    ) {
+      return;
+    }
+
+    const page = pages.get(event.clientId);
+    if (page && !page.token) {
+      // the page has no session
+      return;
+    }
+    if (page && !matches(page, url)) {
+      return;
+    }
+
+    event.respondWith(inject(event, url));
+  });
+})();
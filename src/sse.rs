@@ -0,0 +1,494 @@
+//! Receiving server-sent events, authenticated with the access token of the session.
+//!
+//! Browsers can't set headers on an `EventSource`. So the [`AuthenticatedEventSource`] either
+//! streams the events using `fetch`, sending the `Authorization` header, or uses an
+//! `EventSource`, adding the token as query parameter. In both cases, the token is acquired from
+//! the agent before each (re-)connect, so that a reconnect uses a fresh token.
+//!
+//! ```rust,no_run
+//! use yew_oauth2::{
+//!     agent::Error,
+//!     sse::{AuthenticatedEventSource, EventSourceConnection, ServerSentEvent},
+//! };
+//!
+//! fn listen() -> Result<EventSourceConnection, Error> {
+//!     AuthenticatedEventSource::new("https://api/events".parse().unwrap())
+//!         .open(|event: ServerSentEvent| log::info!("{}: {}", event.event, event.data))
+//! }
+//! ```
+
+use crate::agent::{AccessToken, AgentCallback, Error, OAuth2Agent, TokenRequest};
+use gloo_events::EventListener;
+use gloo_timers::future::sleep;
+use gloo_utils::window;
+use js_sys::{Reflect, Uint8Array};
+use reqwest::Url;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    AbortController, EventSource, Headers, MessageEvent, ReadableStreamDefaultReader, RequestCache,
+    RequestInit, Response,
+};
+
+/// How the access token is sent, when connecting.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum SseTransport {
+    /// Stream the events using `fetch`, sending the token as `Authorization` header.
+    #[default]
+    Fetch,
+    /// Use an `EventSource`, adding the token as the query parameter of the name.
+    ///
+    /// The token becomes part of the URL, which may end up in the logs of servers and proxies.
+    /// Prefer short-lived tokens, e.g. using a [`TokenRequest`] for a dedicated audience.
+    QueryParameter(String),
+}
+
+/// An event, sent by the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerSentEvent {
+    /// The type of the event, `message` by default.
+    pub event: String,
+    /// The data of the event.
+    pub data: String,
+    /// The ID of the last event.
+    pub id: Option<String>,
+}
+
+/// A source of server-sent events, authenticated with the access token.
+///
+/// A lost connection is re-established after the retry delay, which the server may change using
+/// the `retry` field. When streaming using `fetch`, the server rejecting the token as
+/// unauthorized (`401`) refreshes the session, before reconnecting.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedEventSource {
+    url: Url,
+    transport: SseTransport,
+    agent: Option<OAuth2Agent>,
+    token_request: TokenRequest,
+    retry: Duration,
+    events: Vec<String>,
+}
+
+impl AuthenticatedEventSource {
+    /// Receive the events of the URL.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            transport: SseTransport::default(),
+            agent: None,
+            token_request: TokenRequest::new(),
+            retry: Duration::from_secs(3),
+            events: vec![],
+        }
+    }
+
+    /// Set how the access token is sent
+    pub fn with_transport(mut self, transport: SseTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set the agent, providing the token
+    ///
+    /// Defaults to the [global agent](OAuth2Agent::global).
+    pub fn with_agent(mut self, agent: OAuth2Agent) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+
+    /// Set the request for the token, e.g. for a different audience
+    pub fn with_token_request(mut self, token_request: TokenRequest) -> Self {
+        self.token_request = token_request;
+        self
+    }
+
+    /// Set the delay before reconnecting
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Add a type of events to receive, in addition to `message`
+    ///
+    /// This is only required for [`SseTransport::QueryParameter`], as an `EventSource` only
+    /// reports the types it listens to. Streaming using `fetch` reports events of all types.
+    pub fn add_event(mut self, event: impl Into<String>) -> Self {
+        self.events.push(event.into());
+        self
+    }
+
+    /// Connect, and keep receiving events until the connection is dropped.
+    pub fn open(
+        self,
+        callback: impl Into<AgentCallback<ServerSentEvent>>,
+    ) -> Result<EventSourceConnection, Error> {
+        let agent = match &self.agent {
+            Some(agent) => agent.clone(),
+            None => OAuth2Agent::global().ok_or(Error::NoAgent)?,
+        };
+
+        let shared = Rc::new(Shared::default());
+        spawn_local(self.run(agent, callback.into(), shared.clone()));
+
+        Ok(EventSourceConnection(shared))
+    }
+
+    async fn run(
+        self,
+        agent: OAuth2Agent,
+        callback: AgentCallback<ServerSentEvent>,
+        shared: Rc<Shared>,
+    ) {
+        let mut parser = EventParser::new(self.retry);
+
+        while !shared.closed.get() {
+            match agent.acquire_token(self.token_request.clone()).await {
+                Ok(token) => {
+                    let outcome = match &self.transport {
+                        SseTransport::Fetch => {
+                            self.stream(&token, &mut parser, &callback, &shared).await
+                        }
+                        SseTransport::QueryParameter(name) => {
+                            self.event_source(name, &token, &callback, &shared).await
+                        }
+                    };
+                    match outcome {
+                        Outcome::Closed => {}
+                        Outcome::Unauthorized => {
+                            log::debug!("Event stream unauthorized, refreshing the token");
+                            if let Ok(None) = agent
+                                .replace_token(&token, self.token_request.clone())
+                                .await
+                            {
+                                let _ = agent.report_unauthorized();
+                            }
+                        }
+                        Outcome::Failed(err) => log::info!("Event stream failed: {err}"),
+                    }
+                }
+                Err(err) => log::info!("Failed to acquire token for event stream: {err}"),
+            }
+
+            if shared.closed.get() {
+                break;
+            }
+            sleep(parser.retry).await;
+        }
+    }
+
+    /// Stream the events using `fetch`.
+    async fn stream(
+        &self,
+        token: &AccessToken,
+        parser: &mut EventParser,
+        callback: &AgentCallback<ServerSentEvent>,
+        shared: &Shared,
+    ) -> Outcome {
+        parser.reset();
+        let result = async {
+            let headers = Headers::new()?;
+            headers.set("Accept", "text/event-stream")?;
+            headers.set("Authorization", &format!("Bearer {}", token.access_token))?;
+            if let Some(id) = &parser.last_event_id {
+                headers.set("Last-Event-ID", id)?;
+            }
+
+            let abort = AbortController::new()?;
+            let init = RequestInit::new();
+            init.set_method("GET");
+            init.set_headers(&headers);
+            init.set_cache(RequestCache::NoStore);
+            init.set_signal(Some(&abort.signal()));
+            shared.set(Current::Fetch(abort));
+
+            let response =
+                JsFuture::from(window().fetch_with_str_and_init(self.url.as_str(), &init))
+                    .await?
+                    .unchecked_into::<Response>();
+            if response.status() == 401 {
+                return Ok(Outcome::Unauthorized);
+            }
+            if !response.ok() {
+                return Ok(Outcome::Failed(format!("status {}", response.status())));
+            }
+            let Some(body) = response.body() else {
+                return Ok(Outcome::Closed);
+            };
+
+            let reader = ReadableStreamDefaultReader::new(&body)?;
+            while !shared.closed.get() {
+                let chunk = JsFuture::from(reader.read()).await?;
+                if Reflect::get(&chunk, &"done".into())?.is_truthy() {
+                    break;
+                }
+                let value = Reflect::get(&chunk, &"value".into())?;
+                for event in parser.feed(&Uint8Array::new(&value).to_vec()) {
+                    callback.emit(event);
+                }
+            }
+
+            Ok::<_, JsValue>(Outcome::Closed)
+        }
+        .await;
+
+        shared.set(Current::None);
+        result.unwrap_or_else(|err| Outcome::Failed(format!("{err:?}")))
+    }
+
+    /// Receive the events using an `EventSource`, until it fails.
+    async fn event_source(
+        &self,
+        name: &str,
+        token: &AccessToken,
+        callback: &AgentCallback<ServerSentEvent>,
+        shared: &Shared,
+    ) -> Outcome {
+        let mut url = self.url.clone();
+        url.query_pairs_mut().append_pair(name, &token.access_token);
+
+        let source = match EventSource::new(url.as_str()) {
+            Ok(source) => source,
+            Err(err) => return Outcome::Failed(format!("{err:?}")),
+        };
+
+        let mut listeners = std::iter::once("message")
+            .chain(self.events.iter().map(String::as_str))
+            .map(|event| {
+                let callback = callback.clone();
+                EventListener::new(&source, event.to_string(), move |event| {
+                    let Some(event) = event.dyn_ref::<MessageEvent>() else {
+                        return;
+                    };
+                    let id = event.last_event_id();
+                    callback.emit(ServerSentEvent {
+                        event: event.type_(),
+                        data: event.data().as_string().unwrap_or_default(),
+                        id: (!id.is_empty()).then_some(id),
+                    });
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // the event source reconnects by itself, but re-using the token, so reconnect ourselves
+        let (tx, rx) = oneshot::channel();
+        let tx = RefCell::new(Some(tx));
+        listeners.push(EventListener::new(&source, "error", move |_| {
+            if let Some(tx) = tx.borrow_mut().take() {
+                let _ = tx.send(());
+            }
+        }));
+
+        shared.set(Current::EventSource(source));
+        let _ = rx.await;
+        shared.set(Current::None);
+
+        Outcome::Failed("event source failed".into())
+    }
+}
+
+/// The outcome of a connection.
+enum Outcome {
+    Closed,
+    Unauthorized,
+    Failed(String),
+}
+
+/// The current connection.
+#[derive(Default)]
+enum Current {
+    #[default]
+    None,
+    Fetch(AbortController),
+    EventSource(EventSource),
+}
+
+#[derive(Default)]
+struct Shared {
+    closed: Cell<bool>,
+    current: RefCell<Current>,
+}
+
+impl Shared {
+    /// Set the current connection, closing the previous one.
+    fn set(&self, current: Current) {
+        match self.current.replace(current) {
+            Current::None => {}
+            Current::Fetch(abort) => abort.abort(),
+            Current::EventSource(source) => source.close(),
+        }
+    }
+}
+
+/// A connection, opened by [`AuthenticatedEventSource::open`].
+///
+/// Dropping it closes the connection.
+#[must_use = "dropping the connection closes it"]
+pub struct EventSourceConnection(Rc<Shared>);
+
+impl Drop for EventSourceConnection {
+    fn drop(&mut self) {
+        self.0.closed.set(true);
+        self.0.set(Current::None);
+    }
+}
+
+impl std::fmt::Debug for EventSourceConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSourceConnection")
+    }
+}
+
+/// Parsing the `text/event-stream` format, across chunks.
+struct EventParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Option<String>,
+    last_event_id: Option<String>,
+    retry: Duration,
+}
+
+impl EventParser {
+    fn new(retry: Duration) -> Self {
+        Self {
+            buffer: vec![],
+            event: None,
+            data: None,
+            last_event_id: None,
+            retry,
+        }
+    }
+
+    /// Drop a partially received event, of a lost connection.
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.event = None;
+        self.data = None;
+    }
+
+    /// Feed a chunk, returning the completed events.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<ServerSentEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = vec![];
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<ServerSentEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            let data = self.data.take()?;
+            return Some(ServerSentEvent {
+                event: event.unwrap_or_else(|| "message".into()),
+                data,
+                id: self.last_event_id.clone(),
+            });
+        }
+        if line.starts_with(':') {
+            // a comment
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.into()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.into()),
+            },
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.into()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Duration::from_millis(retry);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> ServerSentEvent {
+        ServerSentEvent {
+            event: event.into(),
+            data: data.into(),
+            id: id.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn multi_line_data() {
+        let mut parser = EventParser::new(Duration::from_secs(3));
+        let events = parser.feed(b"data: first\ndata:second\r\ndata\n\n");
+        assert_eq!(events, vec![event("message", "first\nsecond\n", None)]);
+    }
+
+    #[test]
+    fn across_chunks() {
+        let mut parser = EventParser::new(Duration::from_secs(3));
+        assert!(parser.feed(b"event: upd").is_empty());
+        assert!(parser.feed(b"ate\ndata: fo").is_empty());
+        let events = parser.feed(b"o\n\n: a comment\ndata: bar\n\n");
+        assert_eq!(
+            events,
+            vec![event("update", "foo", None), event("message", "bar", None)]
+        );
+    }
+
+    #[test]
+    fn last_event_id() {
+        let mut parser = EventParser::new(Duration::from_secs(3));
+        let events = parser.feed(b"id: 1\ndata: a\n\ndata: b\n\nid: a\0b\ndata: c\n\n");
+        assert_eq!(
+            events,
+            vec![
+                event("message", "a", Some("1")),
+                // the ID is kept for the following events
+                event("message", "b", Some("1")),
+                // an ID containing NULL is ignored
+                event("message", "c", Some("1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn retry() {
+        let mut parser = EventParser::new(Duration::from_secs(3));
+        assert!(parser.feed(b"retry: 1500\n\n").is_empty());
+        assert_eq!(parser.retry, Duration::from_millis(1500));
+        assert!(parser.feed(b"retry: soon\n\n").is_empty());
+        assert_eq!(parser.retry, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn reset() {
+        let mut parser = EventParser::new(Duration::from_secs(3));
+        assert!(parser
+            .feed(b"id: 1\nevent: update\ndata: partial")
+            .is_empty());
+        parser.reset();
+        let events = parser.feed(b"data: complete\n\n");
+        assert_eq!(events, vec![event("message", "complete", Some("1"))]);
+    }
+}
@@ -0,0 +1,947 @@
+//! Keeping refresh tokens in a Web Worker.
+//!
+//! With a [`TokenVault`] configured, the clients send their requests to the token endpoint
+//! through the [`TokenVaultWorker`]. The worker keeps the refresh tokens of the responses, and
+//! hands out opaque handles instead. When a request contains a handle, the worker replaces it
+//! with the refresh token before sending it. So the refresh tokens never reach the main thread,
+//! where they would be exposed to any script running on the page. A handle is only replaced in
+//! requests to the token endpoint, and of the client, it was issued for.
+//!
+//! The worker also refreshes the session. The agent still decides when the session is due for
+//! refreshing, and hands the timer over to the worker, which isn't throttled or blocked by a busy
+//! main thread. Once the worker refreshed the session, it sends the response to the agent, which
+//! applies it like a response to a refresh of its own.
+//!
+//! The worker runs in a binary of its own, which only registers it:
+//!
+//! ```rust,no_run
+//! use yew_oauth2::worker::TokenVaultWorker;
+//!
+//! fn main() {
+//!     TokenVaultWorker::register();
+//! }
+//! ```
+//!
+//! The application spawns it, and adds it to the configuration of the client:
+//!
+//! ```rust,no_run
+//! use yew_oauth2::{openid::Config, worker::TokenVault};
+//!
+//! let vault = TokenVault::spawn("/token-vault.js");
+//! let config = Config::new("my-client", "https://issuer/realms/my-realm").with_token_vault(vault);
+//! ```
+//!
+//! ## Sharing the worker between tabs
+//!
+//! A [`SharedTokenVaultWorker`] runs in a `SharedWorker`, which all tabs of the application
+//! connect to, using [`TokenVault::spawn_shared`]. Its handles are valid in every tab. The worker
+//! is the only one refreshing the session, once for all tabs, and sends the new tokens to every
+//! tab. So that tabs refreshing the same session don't race each other, invalidating the refresh
+//! tokens of the others when the issuer rotates them. Identical requests, sent by multiple tabs at
+//! the same time, are only sent to the issuer once as well.
+//!
+//! ```rust,no_run
+//! use yew_oauth2::worker::SharedTokenVaultWorker;
+//!
+//! fn main() {
+//!     SharedTokenVaultWorker::register();
+//! }
+//! ```
+//!
+//! The requests and responses are exchanged with the shared worker as JSON.
+//!
+//! **NOTE:** The access tokens, and ID tokens, are still handed to the main thread, as the
+//! application needs them. As the refresh tokens only live in the memory of the worker, a session
+//! restored from the storage of the browser (see [`crate::agent::SessionPersistence`]) can only be
+//! refreshed while the shared worker is still running, i.e. another tab of the application is
+//! open. Otherwise, it requires a new login.
+
+mod shared;
+
+pub use shared::SharedTokenVaultWorker;
+
+use ::oauth2::{
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    reqwest::async_http_client,
+    url::{form_urlencoded, Url},
+    HttpRequest, HttpResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use gloo_timers::callback::Timeout;
+use gloo_worker::{HandlerId, Registrable, Spawnable, Worker, WorkerBridge, WorkerScope};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+/// The prefix of the handles, standing in for refresh tokens.
+const HANDLE_PREFIX: &str = "vault:";
+
+/// The name of the refresh token, in requests and responses.
+const REFRESH_TOKEN: &str = "refresh_token";
+
+/// An HTTP request, sent to the worker.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WireRequest {
+    url: String,
+    method: String,
+    headers: Vec<(String, Vec<u8>)>,
+    body: Vec<u8>,
+}
+
+impl From<HttpRequest> for WireRequest {
+    fn from(request: HttpRequest) -> Self {
+        Self {
+            url: request.url.to_string(),
+            method: request.method.to_string(),
+            headers: wire_headers(&request.headers),
+            body: request.body,
+        }
+    }
+}
+
+impl TryFrom<WireRequest> for HttpRequest {
+    type Error = String;
+
+    fn try_from(request: WireRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            url: Url::parse(&request.url).map_err(|err| format!("invalid URL: {err}"))?,
+            method: Method::from_bytes(request.method.as_bytes())
+                .map_err(|err| format!("invalid method: {err}"))?,
+            headers: header_map(request.headers)?,
+            body: request.body,
+        })
+    }
+}
+
+/// An HTTP response, sent back by the worker.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WireResponse {
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    body: Vec<u8>,
+}
+
+impl From<HttpResponse> for WireResponse {
+    fn from(response: HttpResponse) -> Self {
+        Self {
+            status: response.status_code.as_u16(),
+            headers: wire_headers(&response.headers),
+            body: response.body,
+        }
+    }
+}
+
+impl TryFrom<WireResponse> for HttpResponse {
+    type Error = String;
+
+    fn try_from(response: WireResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            status_code: StatusCode::from_u16(response.status)
+                .map_err(|err| format!("invalid status: {err}"))?,
+            headers: header_map(response.headers)?,
+            body: response.body,
+        })
+    }
+}
+
+fn wire_headers(headers: &HeaderMap) -> Vec<(String, Vec<u8>)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+        .collect()
+}
+
+fn header_map(headers: Vec<(String, Vec<u8>)>) -> Result<HeaderMap, String> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            Ok((
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| format!("invalid header name: {err}"))?,
+                HeaderValue::from_bytes(&value)
+                    .map_err(|err| format!("invalid header value: {err}"))?,
+            ))
+        })
+        .collect()
+}
+
+/// A message to the [`TokenVaultWorker`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum VaultInput {
+    /// Perform a request to the token endpoint.
+    Request { id: u64, request: WireRequest },
+    /// Refresh the session of a handle, after the delay in milliseconds.
+    Schedule { handle: String, delay: u64 },
+    /// The tab disconnects from a [`SharedTokenVaultWorker`].
+    Disconnect,
+}
+
+/// A message of the [`TokenVaultWorker`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum VaultOutput {
+    /// The response to a request.
+    Response {
+        id: u64,
+        result: Result<WireResponse, String>,
+    },
+    /// The worker refreshed the session of a handle.
+    Refreshed {
+        handle: String,
+        result: Result<WireResponse, String>,
+    },
+}
+
+/// A message of the [`TokenVaultWorker`] to itself.
+#[doc(hidden)]
+#[derive(Debug)]
+pub enum VaultMessage {
+    /// A request was completed, for a tab or a scheduled refresh.
+    Completed {
+        handler: Option<(HandlerId, u64)>,
+        handle: Option<String>,
+        request: WireRequest,
+        result: Result<WireResponse, String>,
+    },
+    /// A scheduled refresh is due.
+    Refresh(String),
+}
+
+/// A refresh token, kept by a worker.
+///
+/// The handle of a refresh token is only valid for the token endpoint, and the client, it was
+/// issued for. So that a handle can't be used to send the refresh token to another endpoint.
+#[derive(Debug)]
+struct Sealed {
+    refresh_token: String,
+    url: String,
+    client_id: Option<String>,
+}
+
+impl Sealed {
+    /// Check if the request is sent to the endpoint, by the client, the refresh token was issued
+    /// for.
+    fn accepts(&self, request: &WireRequest) -> bool {
+        self.url == request.url && self.client_id == client_id(request)
+    }
+}
+
+/// The refresh tokens, kept by a worker.
+#[derive(Debug, Default)]
+struct Vault {
+    tokens: HashMap<String, Sealed>,
+    /// The requests refreshing the session of a handle.
+    refresh_requests: HashMap<String, WireRequest>,
+    next: u64,
+}
+
+impl Vault {
+    /// Replace the handle of a request with its refresh token.
+    ///
+    /// Returns the handle, or a response rejecting the request if the handle is unknown, or was
+    /// issued for another endpoint or client.
+    fn unseal(
+        &self,
+        mut request: WireRequest,
+    ) -> Result<(WireRequest, Option<String>), Box<WireResponse>> {
+        let mut handle = None;
+        let mut pairs = Vec::new();
+        for (key, value) in form_urlencoded::parse(&request.body) {
+            if key == REFRESH_TOKEN && value.starts_with(HANDLE_PREFIX) {
+                let Some(sealed) = self.tokens.get(value.as_ref()) else {
+                    return Err(Box::new(invalid_grant()));
+                };
+                if !sealed.accepts(&request) {
+                    log::warn!("Rejecting a handle, sent to another endpoint or by another client");
+                    return Err(Box::new(invalid_grant()));
+                }
+                handle = Some(value.into_owned());
+                pairs.push((key.into_owned(), sealed.refresh_token.clone()));
+            } else {
+                pairs.push((key.into_owned(), value.into_owned()));
+            }
+        }
+
+        if handle.is_some() {
+            request.body = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish()
+                .into_bytes();
+        }
+
+        Ok((request, handle))
+    }
+
+    /// Replace the refresh token of a response with a handle.
+    ///
+    /// A rotated refresh token is kept under the handle of the request. The `request` is the one
+    /// sent by the tab, before unsealing it.
+    fn seal(
+        &mut self,
+        mut response: WireResponse,
+        handle: Option<String>,
+        request: &WireRequest,
+    ) -> WireResponse {
+        if let Ok(mut body) = serde_json::from_slice::<Map<String, Value>>(&response.body) {
+            if let Some(Value::String(refresh_token)) = body.remove(REFRESH_TOKEN) {
+                let handle = self.keep(refresh_token, handle, request);
+                body.insert(REFRESH_TOKEN.into(), Value::String(handle));
+                response.body = serde_json::to_vec(&body).unwrap_or_default();
+            }
+            return response;
+        }
+
+        // a form-encoded response, of an issuer not following the specification
+        let mut pairs = form_urlencoded::parse(&response.body)
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+        if let Some((_, value)) = pairs.iter_mut().find(|(key, _)| key == REFRESH_TOKEN) {
+            *value = self.keep(std::mem::take(value), handle, request);
+            response.body = form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(pairs)
+                .finish()
+                .into_bytes();
+        }
+
+        response
+    }
+
+    /// Keep a refresh token, returning its handle.
+    fn keep(
+        &mut self,
+        refresh_token: String,
+        handle: Option<String>,
+        request: &WireRequest,
+    ) -> String {
+        let handle = handle.unwrap_or_else(|| {
+            self.next += 1;
+            format!("{HANDLE_PREFIX}{}", self.next)
+        });
+        let sealed = Sealed {
+            refresh_token,
+            url: request.url.clone(),
+            client_id: client_id(request),
+        };
+        self.tokens.insert(handle.clone(), sealed);
+        self.refresh_requests
+            .insert(handle.clone(), refresh_request(request, &handle));
+        handle
+    }
+
+    /// The request refreshing the session of a handle, still containing the handle.
+    fn refresh_request(&self, handle: &str) -> Option<WireRequest> {
+        self.refresh_requests.get(handle).cloned()
+    }
+}
+
+/// The parameters of a token request, which are specific to its grant.
+const GRANT_PARAMS: &[&str] = &[
+    "grant_type",
+    "code",
+    "code_verifier",
+    "redirect_uri",
+    REFRESH_TOKEN,
+];
+
+/// A request refreshing the handle, based on the request which returned it.
+///
+/// This keeps the endpoint, the headers (e.g. the client authentication) and the parameters not
+/// specific to the grant, like the client ID.
+fn refresh_request(request: &WireRequest, handle: &str) -> WireRequest {
+    let params = form_urlencoded::parse(&request.body)
+        .filter(|(key, _)| !GRANT_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()));
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("grant_type", REFRESH_TOKEN)
+        .append_pair(REFRESH_TOKEN, handle)
+        .extend_pairs(params)
+        .finish();
+
+    WireRequest {
+        url: request.url.clone(),
+        method: request.method.clone(),
+        headers: request.headers.clone(),
+        body: body.into_bytes(),
+    }
+}
+
+/// The ID of the client sending a request.
+///
+/// This is the `client_id` parameter, or the user of the basic authentication of confidential
+/// clients.
+fn client_id(request: &WireRequest) -> Option<String> {
+    if let Some((_, client_id)) =
+        form_urlencoded::parse(&request.body).find(|(key, _)| key == "client_id")
+    {
+        return Some(client_id.into_owned());
+    }
+
+    request
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.strip_prefix(b"Basic "))
+        .and_then(|credentials| STANDARD.decode(credentials).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(client_id, _)| client_id.to_string())
+        })
+}
+
+/// The handle of a refresh request.
+fn refresh_handle(request: &WireRequest) -> Option<String> {
+    form_urlencoded::parse(&request.body)
+        .find(|(key, value)| key == REFRESH_TOKEN && value.starts_with(HANDLE_PREFIX))
+        .map(|(_, value)| value.into_owned())
+}
+
+/// The refreshes, scheduled by the tabs.
+///
+/// There is one timer per handle, the earliest refresh wins.
+#[derive(Debug, Default)]
+struct Schedule(HashMap<String, (f64, Timeout)>);
+
+impl Schedule {
+    /// Schedule refreshing the handle, unless it is scheduled earlier already.
+    fn schedule(&mut self, handle: String, delay: u64, refresh: impl FnOnce() + 'static) {
+        let due = js_sys::Date::now() + delay as f64;
+        if self
+            .0
+            .get(&handle)
+            .is_some_and(|(scheduled, _)| *scheduled <= due)
+        {
+            return;
+        }
+        log::debug!("Scheduling refresh in the worker: {delay}ms");
+        let millis = delay.to_u32().unwrap_or(u32::MAX);
+        self.0.insert(handle, (due, Timeout::new(millis, refresh)));
+    }
+
+    /// The refresh of the handle is due.
+    fn due(&mut self, handle: &str) {
+        self.0.remove(handle);
+    }
+}
+
+/// The response of an issuer, rejecting an unknown refresh token.
+fn invalid_grant() -> WireResponse {
+    WireResponse {
+        status: StatusCode::BAD_REQUEST.as_u16(),
+        headers: vec![("content-type".into(), b"application/json".to_vec())],
+        body: serde_json::to_vec(&serde_json::json!({
+            "error": "invalid_grant",
+            "error_description": "unknown refresh token",
+        }))
+        .unwrap_or_default(),
+    }
+}
+
+/// Perform a request, after replacing its handle.
+async fn perform(request: WireRequest) -> Result<WireResponse, String> {
+    let request = HttpRequest::try_from(request)?;
+    async_http_client(request)
+        .await
+        .map(WireResponse::from)
+        .map_err(|err| err.to_string())
+}
+
+/// The worker, keeping the refresh tokens.
+///
+/// This runs inside the Web Worker, see the [module documentation](self).
+#[derive(Debug, Default)]
+pub struct TokenVaultWorker {
+    vault: Vault,
+    schedule: Schedule,
+    handlers: HashSet<HandlerId>,
+}
+
+impl TokenVaultWorker {
+    /// Register the worker, from the `main` function of the binary of the worker.
+    pub fn register() {
+        Self::registrar().register();
+    }
+}
+
+impl TokenVaultWorker {
+    /// Perform a request, after unsealing it.
+    fn perform(
+        &mut self,
+        scope: &WorkerScope<Self>,
+        handler: Option<(HandlerId, u64)>,
+        request: WireRequest,
+    ) {
+        let (unsealed, handle) = match self.vault.unseal(request.clone()) {
+            Ok(unsealed) => unsealed,
+            Err(response) => {
+                self.respond(scope, handler, refresh_handle(&request), Ok(*response));
+                return;
+            }
+        };
+
+        scope.send_future(async move {
+            VaultMessage::Completed {
+                handler,
+                handle,
+                request,
+                result: perform(unsealed).await,
+            }
+        });
+    }
+
+    /// Respond to a tab, or send a scheduled refresh to all tabs.
+    fn respond(
+        &self,
+        scope: &WorkerScope<Self>,
+        handler: Option<(HandlerId, u64)>,
+        handle: Option<String>,
+        result: Result<WireResponse, String>,
+    ) {
+        match (handler, handle) {
+            (Some((handler, id)), _) => {
+                scope.respond(handler, VaultOutput::Response { id, result })
+            }
+            (None, Some(handle)) => {
+                for handler in &self.handlers {
+                    let output = VaultOutput::Refreshed {
+                        handle: handle.clone(),
+                        result: result.clone(),
+                    };
+                    scope.respond(*handler, output);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+impl Worker for TokenVaultWorker {
+    type Message = VaultMessage;
+    type Input = VaultInput;
+    type Output = VaultOutput;
+
+    fn create(_: &WorkerScope<Self>) -> Self {
+        Self::default()
+    }
+
+    fn update(&mut self, scope: &WorkerScope<Self>, msg: Self::Message) {
+        match msg {
+            VaultMessage::Completed {
+                handler,
+                handle,
+                request,
+                result,
+            } => {
+                let result = result.map(|response| self.vault.seal(response, handle, &request));
+                self.respond(scope, handler, refresh_handle(&request), result);
+            }
+            VaultMessage::Refresh(handle) => {
+                self.schedule.due(&handle);
+                if let Some(request) = self.vault.refresh_request(&handle) {
+                    log::debug!("Refreshing the session in the worker");
+                    self.perform(scope, None, request);
+                }
+            }
+        }
+    }
+
+    fn connected(&mut self, _: &WorkerScope<Self>, id: HandlerId) {
+        self.handlers.insert(id);
+    }
+
+    fn received(&mut self, scope: &WorkerScope<Self>, msg: Self::Input, handler: HandlerId) {
+        match msg {
+            VaultInput::Request { id, request } => {
+                self.perform(scope, Some((handler, id)), request)
+            }
+            VaultInput::Schedule { handle, delay } => {
+                let scope = scope.clone();
+                self.schedule.schedule(handle.clone(), delay, move || {
+                    scope.send_message(VaultMessage::Refresh(handle));
+                });
+            }
+            // the bridge reports disconnecting itself
+            VaultInput::Disconnect => {}
+        }
+    }
+
+    fn disconnected(&mut self, _: &WorkerScope<Self>, id: HandlerId) {
+        self.handlers.remove(&id);
+    }
+}
+
+/// A callback, notified about the handle of a session the worker refreshed.
+type RefreshedCallback = Box<dyn Fn(&str)>;
+
+/// The state of a [`TokenVault`], shared with the transport.
+#[derive(Default)]
+struct Shared {
+    pending: RefCell<HashMap<u64, oneshot::Sender<Result<WireResponse, String>>>>,
+    /// The latest responses of the refreshes performed by the worker, by handle.
+    refreshed: RefCell<HashMap<String, Result<WireResponse, String>>>,
+    on_refreshed: RefCell<Option<RefreshedCallback>>,
+}
+
+impl Shared {
+    /// Handle a message of the worker.
+    fn received(&self, output: VaultOutput) {
+        match output {
+            VaultOutput::Response { id, result } => {
+                if let Some(tx) = self.pending.borrow_mut().remove(&id) {
+                    let _ = tx.send(result);
+                }
+            }
+            VaultOutput::Refreshed { handle, result } => {
+                self.refreshed.borrow_mut().insert(handle.clone(), result);
+                if let Some(on_refreshed) = &*self.on_refreshed.borrow() {
+                    on_refreshed(&handle);
+                }
+            }
+        }
+    }
+}
+
+/// The connection to a worker.
+enum Transport {
+    Dedicated(WorkerBridge<TokenVaultWorker>),
+    Shared(shared::Connection),
+}
+
+struct Inner {
+    transport: Transport,
+    shared: Rc<Shared>,
+    next: Cell<u64>,
+}
+
+/// A handle to the worker keeping the refresh tokens, from the main thread.
+///
+/// Either a [`TokenVaultWorker`], dedicated to the page, or a [`SharedTokenVaultWorker`],
+/// shared by all tabs of the application.
+#[derive(Clone)]
+pub struct TokenVault(Rc<Inner>);
+
+impl TokenVault {
+    /// Spawn a [`TokenVaultWorker`], from the script at the path.
+    pub fn spawn(path: &str) -> Self {
+        let shared = Rc::new(Shared::default());
+        let bridge = TokenVaultWorker::spawner()
+            .callback({
+                let shared = shared.clone();
+                move |output: VaultOutput| shared.received(output)
+            })
+            .spawn(path);
+
+        Self::new(Transport::Dedicated(bridge), shared)
+    }
+
+    /// Connect to the [`SharedTokenVaultWorker`] of the script at the path, starting it if it
+    /// isn't running yet.
+    pub fn spawn_shared(path: &str) -> Result<Self, String> {
+        let shared = Rc::new(Shared::default());
+        let connection = shared::Connection::new(path, {
+            let shared = shared.clone();
+            move |output| shared.received(output)
+        })?;
+
+        Ok(Self::new(Transport::Shared(connection), shared))
+    }
+
+    fn new(transport: Transport, shared: Rc<Shared>) -> Self {
+        Self(Rc::new(Inner {
+            transport,
+            shared,
+            next: Cell::new(0),
+        }))
+    }
+
+    fn send(&self, input: VaultInput) -> Result<(), String> {
+        match &self.0.transport {
+            Transport::Dedicated(bridge) => {
+                bridge.send(input);
+                Ok(())
+            }
+            Transport::Shared(connection) => connection.send(&input),
+        }
+    }
+
+    /// Check if the refresh token is a handle of the vault.
+    pub(crate) fn is_handle(refresh_token: &str) -> bool {
+        refresh_token.starts_with(HANDLE_PREFIX)
+    }
+
+    /// Let the worker refresh the session of the handle, after the delay.
+    ///
+    /// Returns `false` if the worker can't do this, and the agent needs to refresh on its own.
+    pub(crate) fn schedule_refresh(&self, handle: &str, delay: Duration) -> bool {
+        let input = VaultInput::Schedule {
+            handle: handle.to_string(),
+            delay: delay.as_millis().to_u64().unwrap_or(u64::MAX),
+        };
+        match self.send(input) {
+            Ok(()) => true,
+            Err(err) => {
+                log::warn!("Failed to schedule the refresh in the worker: {err}");
+                false
+            }
+        }
+    }
+
+    /// Set the callback, notified whenever the worker refreshed the session of a handle.
+    ///
+    /// Refreshing the handle afterwards returns the response of the worker, without another
+    /// request to the issuer.
+    pub(crate) fn on_refreshed(&self, on_refreshed: impl Fn(&str) + 'static) {
+        *self.0.shared.on_refreshed.borrow_mut() = Some(Box::new(on_refreshed));
+    }
+
+    /// Perform a request to the token endpoint, through the worker.
+    pub(crate) async fn request(&self, request: HttpRequest) -> Result<HttpResponse, String> {
+        let request = WireRequest::from(request);
+        if let Some(result) = refresh_handle(&request)
+            .and_then(|handle| self.0.shared.refreshed.borrow_mut().remove(&handle))
+        {
+            log::debug!("Using the refresh performed by the worker");
+            return result?.try_into();
+        }
+
+        let id = self.0.next.get();
+        self.0.next.set(id + 1);
+
+        let (tx, rx) = oneshot::channel();
+        self.0.shared.pending.borrow_mut().insert(id, tx);
+        if let Err(err) = self.send(VaultInput::Request { id, request }) {
+            self.0.shared.pending.borrow_mut().remove(&id);
+            return Err(err);
+        }
+
+        rx.await
+            .map_err(|_| "the token vault is gone".to_string())??
+            .try_into()
+    }
+}
+
+impl PartialEq for TokenVault {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for TokenVault {}
+
+impl std::fmt::Debug for TokenVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenVault")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(body: &str) -> WireRequest {
+        WireRequest {
+            url: "https://issuer/token".into(),
+            method: "POST".into(),
+            headers: vec![],
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn response(body: Value) -> WireResponse {
+        WireResponse {
+            status: 200,
+            headers: vec![],
+            body: serde_json::to_vec(&body).unwrap(),
+        }
+    }
+
+    fn body(response: &WireResponse) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    fn param(request: &WireRequest, name: &str) -> Option<String> {
+        form_urlencoded::parse(&request.body)
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut vault = Vault::default();
+
+        // the code exchange doesn't contain a handle
+        let (exchange, handle) = vault
+            .unseal(request("grant_type=authorization_code&code=abc"))
+            .unwrap();
+        assert_eq!(handle, None);
+        assert_eq!(exchange.body, b"grant_type=authorization_code&code=abc");
+
+        let code = request("grant_type=authorization_code&code=abc");
+        let sealed = vault.seal(
+            response(serde_json::json!({"access_token": "at1", "refresh_token": "rt1"})),
+            handle,
+            &code,
+        );
+        let sealed = body(&sealed);
+        assert_eq!(sealed["access_token"], "at1");
+        let handle = sealed["refresh_token"].as_str().unwrap().to_string();
+        assert!(handle.starts_with(HANDLE_PREFIX));
+
+        // refreshing exchanges the handle for the refresh token
+        let sent = request(&format!("grant_type=refresh_token&refresh_token={handle}"));
+        let (refresh, unsealed) = vault.unseal(sent.clone()).unwrap();
+        assert_eq!(unsealed.as_deref(), Some(handle.as_str()));
+        assert_eq!(param(&refresh, REFRESH_TOKEN).as_deref(), Some("rt1"));
+        assert_eq!(
+            param(&refresh, "grant_type").as_deref(),
+            Some("refresh_token")
+        );
+
+        // a rotated refresh token is kept under the same handle
+        let sealed = vault.seal(
+            response(serde_json::json!({"access_token": "at2", "refresh_token": "rt2"})),
+            unsealed,
+            &sent,
+        );
+        assert_eq!(body(&sealed)["refresh_token"], handle.as_str());
+        let (refresh, _) = vault
+            .unseal(request(&format!("refresh_token={handle}")))
+            .unwrap();
+        assert_eq!(param(&refresh, REFRESH_TOKEN).as_deref(), Some("rt2"));
+    }
+
+    #[test]
+    fn refresh_request() {
+        let mut vault = Vault::default();
+        let mut code = request(
+            "grant_type=authorization_code&code=abc&redirect_uri=https%3A%2F%2Fapp&client_id=app&code_verifier=v",
+        );
+        code.headers = vec![("accept".into(), b"application/json".to_vec())];
+        let sealed = vault.seal(
+            response(serde_json::json!({"access_token": "at", "refresh_token": "rt"})),
+            None,
+            &code,
+        );
+        let handle = body(&sealed)["refresh_token"].as_str().unwrap().to_string();
+
+        // the worker refreshes the handle, keeping the client and the headers
+        let refresh = vault.refresh_request(&handle).unwrap();
+        assert_eq!(refresh.url, code.url);
+        assert_eq!(refresh.headers, code.headers);
+        assert_eq!(
+            String::from_utf8(refresh.body.clone()).unwrap(),
+            "grant_type=refresh_token&refresh_token=vault%3A1&client_id=app"
+        );
+        assert_eq!(refresh_handle(&refresh), Some(handle));
+
+        let (refresh, _) = vault.unseal(refresh).unwrap();
+        assert_eq!(param(&refresh, REFRESH_TOKEN).as_deref(), Some("rt"));
+        assert!(vault.refresh_request("vault:2").is_none());
+    }
+
+    #[test]
+    fn unknown_handle() {
+        let vault = Vault::default();
+        let rejected = vault
+            .unseal(request("grant_type=refresh_token&refresh_token=vault:42"))
+            .unwrap_err();
+        assert_eq!(rejected.status, 400);
+        assert_eq!(body(&rejected)["error"], "invalid_grant");
+    }
+
+    #[test]
+    fn plain_refresh_token() {
+        // a refresh token, which isn't a handle, is passed through
+        let vault = Vault::default();
+        let (request, handle) = vault
+            .unseal(request("grant_type=refresh_token&refresh_token=plain"))
+            .unwrap();
+        assert_eq!(handle, None);
+        assert_eq!(param(&request, REFRESH_TOKEN).as_deref(), Some("plain"));
+    }
+
+    #[test]
+    fn seal_form_response() {
+        let mut vault = Vault::default();
+        let sealed = vault.seal(
+            WireResponse {
+                status: 200,
+                headers: vec![],
+                body: b"access_token=at&refresh_token=rt".to_vec(),
+            },
+            None,
+            &request("grant_type=authorization_code&code=abc"),
+        );
+        let handle = form_urlencoded::parse(&sealed.body)
+            .find(|(key, _)| key == REFRESH_TOKEN)
+            .map(|(_, value)| value.into_owned())
+            .unwrap();
+        assert!(handle.starts_with(HANDLE_PREFIX));
+        assert_eq!(
+            vault
+                .tokens
+                .get(&handle)
+                .map(|sealed| sealed.refresh_token.as_str()),
+            Some("rt")
+        );
+    }
+
+    #[test]
+    fn seal_without_refresh_token() {
+        let mut vault = Vault::default();
+        let sealed = vault.seal(
+            response(serde_json::json!({"access_token": "at"})),
+            None,
+            &request("grant_type=authorization_code&code=abc"),
+        );
+        assert_eq!(body(&sealed), serde_json::json!({"access_token": "at"}));
+        assert!(vault.tokens.is_empty());
+    }
+
+    #[test]
+    fn bound_handle() {
+        let mut vault = Vault::default();
+        let sealed = vault.seal(
+            response(serde_json::json!({"access_token": "at", "refresh_token": "rt"})),
+            None,
+            &request("grant_type=authorization_code&code=abc&client_id=app"),
+        );
+        let handle = body(&sealed)["refresh_token"].as_str().unwrap().to_string();
+        let refresh = request(&format!(
+            "grant_type=refresh_token&refresh_token={handle}&client_id=app"
+        ));
+        assert!(vault.unseal(refresh.clone()).is_ok());
+
+        // another endpoint
+        let mut other = refresh.clone();
+        other.url = "https://attacker/token".into();
+        assert_eq!(vault.unseal(other).unwrap_err().status, 400);
+
+        // another client
+        let other = request(&format!(
+            "grant_type=refresh_token&refresh_token={handle}&client_id=other"
+        ));
+        assert_eq!(vault.unseal(other).unwrap_err().status, 400);
+
+        // no client at all
+        let other = request(&format!("grant_type=refresh_token&refresh_token={handle}"));
+        assert_eq!(vault.unseal(other).unwrap_err().status, 400);
+    }
+
+    #[test]
+    fn basic_client_id() {
+        let mut request = request("grant_type=refresh_token");
+        assert_eq!(client_id(&request), None);
+
+        // "app:secret"
+        request.headers = vec![("Authorization".into(), b"Basic YXBwOnNlY3JldA==".to_vec())];
+        assert_eq!(client_id(&request).as_deref(), Some("app"));
+    }
+}
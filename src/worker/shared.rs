@@ -0,0 +1,270 @@
+//! The token vault, shared by all tabs of the application.
+
+use super::{
+    client_id, perform, refresh_handle, Schedule, Vault, VaultInput, VaultOutput, WireRequest,
+    WireResponse,
+};
+use gloo_events::EventListener;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{MessageEvent, MessagePort, SharedWorker, SharedWorkerGlobalScope};
+
+/// The key of a request, identical requests of the same client are only performed once.
+type RequestKey = (String, Option<String>, Vec<u8>);
+
+/// A tab waiting for the response of a request, or `None` for a scheduled refresh, which is sent
+/// to all tabs.
+type Waiter = Option<(MessagePort, u64)>;
+
+#[derive(Default)]
+struct State {
+    vault: Vault,
+    in_flight: HashMap<RequestKey, Vec<Waiter>>,
+    schedule: Schedule,
+    /// The tabs connected, until they disconnect or close their port.
+    tabs: Vec<Tab>,
+}
+
+/// A tab connected to the worker.
+struct Tab {
+    port: MessagePort,
+    _listeners: [EventListener; 2],
+}
+
+/// The worker, keeping the refresh tokens for all tabs of the application.
+///
+/// The worker is the only one refreshing the sessions. The earliest refresh scheduled by any of the
+/// tabs wins, and the response is sent to all of them.
+///
+/// This runs inside a `SharedWorker`, see the [module documentation](super).
+#[derive(Debug)]
+pub struct SharedTokenVaultWorker;
+
+impl SharedTokenVaultWorker {
+    /// Register the worker, from the `main` function of the binary of the worker.
+    ///
+    /// Each tab connecting to the worker is served from then on.
+    pub fn register() {
+        let scope = js_sys::global().unchecked_into::<SharedWorkerGlobalScope>();
+        let state = Rc::new(RefCell::new(State::default()));
+
+        EventListener::new(&scope, "connect", move |event| {
+            let Some(port) = event
+                .dyn_ref::<MessageEvent>()
+                .and_then(|event| event.ports().get(0).dyn_into::<MessagePort>().ok())
+            else {
+                return;
+            };
+            log::debug!("Tab connected to the token vault");
+            Self::serve(state.clone(), port);
+        })
+        .forget();
+    }
+
+    /// Serve the requests of a tab.
+    fn serve(state: Rc<RefCell<State>>, port: MessagePort) {
+        let message = EventListener::new(&port, "message", {
+            let state = state.clone();
+            let port = port.clone();
+            move |event| {
+                let Some(input) = event
+                    .dyn_ref::<MessageEvent>()
+                    .and_then(|event| event.data().as_string())
+                    .and_then(|data| serde_json::from_str::<VaultInput>(&data).ok())
+                else {
+                    log::warn!("Invalid message to the token vault");
+                    return;
+                };
+                Self::received(&state, &port, input);
+            }
+        });
+        // browsers not supporting this only drop the port when the tab disconnects itself
+        let close = EventListener::new(&port, "close", {
+            let state = Rc::downgrade(&state);
+            let port = port.clone();
+            move |_| {
+                if let Some(state) = state.upgrade() {
+                    Self::disconnect(&state, &port);
+                }
+            }
+        });
+
+        state.borrow_mut().tabs.push(Tab {
+            port: port.clone(),
+            _listeners: [message, close],
+        });
+        port.start();
+    }
+
+    /// Stop serving the tab of a port.
+    fn disconnect(state: &Rc<RefCell<State>>, port: &MessagePort) {
+        let mut state = state.borrow_mut();
+        let Some(index) = state.tabs.iter().position(|tab| &tab.port == port) else {
+            return;
+        };
+        log::debug!("Tab disconnected from the token vault");
+        let tab = state.tabs.swap_remove(index);
+        // the listeners are still running, drop them afterwards
+        spawn_local(async move { drop(tab) });
+    }
+
+    fn received(state: &Rc<RefCell<State>>, port: &MessagePort, input: VaultInput) {
+        match input {
+            VaultInput::Request { id, request } => {
+                Self::perform(state, Some((port.clone(), id)), request)
+            }
+            VaultInput::Schedule { handle, delay } => {
+                let weak = Rc::downgrade(state);
+                state
+                    .borrow_mut()
+                    .schedule
+                    .schedule(handle.clone(), delay, move || {
+                        if let Some(state) = weak.upgrade() {
+                            Self::refresh(&state, handle);
+                        }
+                    });
+            }
+            VaultInput::Disconnect => Self::disconnect(state, port),
+        }
+    }
+
+    /// Refresh the session of a handle, sending the response to all tabs.
+    fn refresh(state: &Rc<RefCell<State>>, handle: String) {
+        let request = {
+            let mut state = state.borrow_mut();
+            state.schedule.due(&handle);
+            state.vault.refresh_request(&handle)
+        };
+        if let Some(request) = request {
+            log::debug!("Refreshing the session for all tabs");
+            Self::perform(state, None, request);
+        }
+    }
+
+    fn perform(state: &Rc<RefCell<State>>, waiter: Waiter, request: WireRequest) {
+        let key = (
+            request.url.clone(),
+            client_id(&request),
+            request.body.clone(),
+        );
+
+        let mut guard = state.borrow_mut();
+        if let Some(waiters) = guard.in_flight.get_mut(&key) {
+            // another tab sent the same request, share its response
+            waiters.push(waiter);
+            return;
+        }
+
+        let (unsealed, handle) = match guard.vault.unseal(request.clone()) {
+            Ok(unsealed) => unsealed,
+            Err(response) => {
+                guard.complete(vec![waiter], &request, Ok(*response));
+                return;
+            }
+        };
+        guard.in_flight.insert(key.clone(), vec![waiter]);
+        drop(guard);
+
+        let state = state.clone();
+        spawn_local(async move {
+            let result = perform(unsealed).await;
+
+            let mut state = state.borrow_mut();
+            let result = result.map(|response| state.vault.seal(response, handle, &request));
+            let waiters = state.in_flight.remove(&key).unwrap_or_default();
+            state.complete(waiters, &request, result);
+        });
+    }
+}
+
+impl State {
+    /// Send the result of a request to the tabs waiting for it.
+    fn complete(
+        &self,
+        waiters: Vec<Waiter>,
+        request: &WireRequest,
+        result: Result<WireResponse, String>,
+    ) {
+        let mut broadcast = false;
+        for waiter in waiters {
+            match waiter {
+                Some((port, id)) => respond(
+                    &port,
+                    &VaultOutput::Response {
+                        id,
+                        result: result.clone(),
+                    },
+                ),
+                None => broadcast = true,
+            }
+        }
+
+        if let (true, Some(handle)) = (broadcast, refresh_handle(request)) {
+            let output = VaultOutput::Refreshed { handle, result };
+            for tab in &self.tabs {
+                respond(&tab.port, &output);
+            }
+        }
+    }
+}
+
+fn respond(port: &MessagePort, output: &VaultOutput) {
+    let result = serde_json::to_string(output)
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+        .and_then(|data| port.post_message(&JsValue::from_str(&data)));
+    if let Err(err) = result {
+        log::warn!("Failed to respond to a tab: {err:?}");
+    }
+}
+
+/// The connection of a tab to the [`SharedTokenVaultWorker`].
+pub(super) struct Connection {
+    port: MessagePort,
+    _listener: EventListener,
+}
+
+impl Connection {
+    pub fn new<F>(path: &str, callback: F) -> Result<Self, String>
+    where
+        F: Fn(VaultOutput) + 'static,
+    {
+        let worker = SharedWorker::new(path)
+            .map_err(|err| format!("failed to start the shared worker: {err:?}"))?;
+        let port = worker.port();
+
+        let listener = EventListener::new(&port, "message", move |event| {
+            let Some(output) = event
+                .dyn_ref::<MessageEvent>()
+                .and_then(|event| event.data().as_string())
+                .and_then(|data| serde_json::from_str::<VaultOutput>(&data).ok())
+            else {
+                log::warn!("Invalid message from the token vault");
+                return;
+            };
+            callback(output);
+        });
+        port.start();
+
+        Ok(Self {
+            port,
+            _listener: listener,
+        })
+    }
+
+    pub fn send(&self, input: &VaultInput) -> Result<(), String> {
+        let data = serde_json::to_string(input).map_err(|err| err.to_string())?;
+        self.port
+            .post_message(&JsValue::from_str(&data))
+            .map_err(|err| format!("failed to send to the shared worker: {err:?}"))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Err(err) = self.send(&VaultInput::Disconnect) {
+            log::debug!("Failed to disconnect from the shared worker: {err}");
+        }
+        self.port.close();
+    }
+}
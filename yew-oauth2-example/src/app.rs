@@ -52,6 +52,7 @@ pub fn content() -> Html {
                     <ul>
                         <li><FailureMessage/></li>
                     </ul>
+                    <Retry>{ "Retry" }</Retry>
                 </Failure>
                 <Authenticated>
                     <p>
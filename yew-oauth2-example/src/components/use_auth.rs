@@ -20,6 +20,8 @@ fn dummy() -> Authentication {
         refresh_token: None,
         #[cfg(feature = "openid")]
         claims: None,
+        #[cfg(feature = "openid")]
+        user_info: None,
         expires: None,
     }
 }